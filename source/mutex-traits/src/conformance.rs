@@ -0,0 +1,105 @@
+//! Conformance checks for [`ScopedRawMutex`] implementations.
+//!
+//! These functions exercise a mutex implementation, and panic if it does not
+//! uphold the behavior documented by the [`ScopedRawMutex`] trait. They are
+//! intended to be called from the tests of crates implementing the trait.
+//!
+//! All checks expect to be passed a mutex which is not currently locked, and
+//! leave the mutex unlocked when they return.
+
+use core::cell::Cell;
+
+use crate::ScopedRawMutex;
+
+/// Runs all of the conformance checks in this module against `mutex`.
+#[track_caller]
+pub fn check_all<M: ScopedRawMutex>(mutex: &M) {
+    is_locked_in_scope(mutex);
+    return_value_dropped_outside_lock(mutex);
+}
+
+/// Checks that [`ScopedRawMutex::is_locked`] returns `true` for the duration
+/// of the closures passed to [`ScopedRawMutex::with_lock`] and
+/// [`ScopedRawMutex::try_with_lock`], and `false` otherwise.
+#[track_caller]
+pub fn is_locked_in_scope<M: ScopedRawMutex>(mutex: &M) {
+    assert!(
+        !mutex.is_locked(),
+        "mutex must not be locked before `with_lock`"
+    );
+    mutex.with_lock(|| {
+        assert!(
+            mutex.is_locked(),
+            "mutex must be locked inside the `with_lock` closure"
+        )
+    });
+    assert!(
+        !mutex.is_locked(),
+        "mutex must not be locked after `with_lock`"
+    );
+
+    let res = mutex.try_with_lock(|| {
+        assert!(
+            mutex.is_locked(),
+            "mutex must be locked inside the `try_with_lock` closure"
+        )
+    });
+    assert!(
+        res.is_some(),
+        "`try_with_lock` must succeed if the mutex is not locked"
+    );
+    assert!(
+        !mutex.is_locked(),
+        "mutex must not be locked after `try_with_lock`"
+    );
+}
+
+/// Checks that the value returned by the closures passed to
+/// [`ScopedRawMutex::with_lock`] and [`ScopedRawMutex::try_with_lock`] is
+/// moved out of the locked scope, and is not dropped until after the lock has
+/// been released.
+///
+/// This is checked by returning a value whose [`Drop`] implementation
+/// attempts to lock the mutex again, which must succeed.
+#[track_caller]
+pub fn return_value_dropped_outside_lock<M: ScopedRawMutex>(mutex: &M) {
+    struct Relock<'a, M: ScopedRawMutex> {
+        mutex: &'a M,
+        relocked: &'a Cell<Option<bool>>,
+    }
+
+    impl<M: ScopedRawMutex> Drop for Relock<'_, M> {
+        fn drop(&mut self) {
+            let relocked = self.mutex.try_with_lock(|| ()).is_some();
+            self.relocked.set(Some(relocked));
+        }
+    }
+
+    let relocked = Cell::new(None);
+    drop(mutex.with_lock(|| Relock {
+        mutex,
+        relocked: &relocked,
+    }));
+    assert_eq!(
+        relocked.take(),
+        Some(true),
+        "the value returned from `with_lock` must be dropped after the \
+         mutex is unlocked"
+    );
+
+    let res = mutex.try_with_lock(|| Relock {
+        mutex,
+        relocked: &relocked,
+    });
+    assert!(
+        res.is_some(),
+        "`try_with_lock` must succeed if the mutex is not locked"
+    );
+    drop(res);
+    assert_eq!(
+        relocked.take(),
+        Some(true),
+        "the value returned from `try_with_lock` must be dropped after the \
+         mutex is unlocked"
+    );
+}
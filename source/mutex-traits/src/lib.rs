@@ -3,6 +3,9 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 
+pub mod conformance;
+pub mod scoped;
+
 /// Const Init Trait
 ///
 /// This trait is intended for use when implementers of [`ScopedRawMutex`] that can
@@ -37,6 +40,10 @@ pub unsafe trait ScopedRawMutex {
     ///
     /// If this was successful, `Some(R)` will be returned. If the mutex was already locked,
     /// `None` will be returned
+    ///
+    /// The value returned by `f()` is moved out of the locked scope, and is not
+    /// dropped until after the lock has been released. See
+    /// [`with_lock`](ScopedRawMutex::with_lock) for details.
     #[must_use]
     fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R>;
 
@@ -50,10 +57,72 @@ pub unsafe trait ScopedRawMutex {
     ///
     /// For implementations where a single thread is present, panicking immediately may be
     /// the correct choice.
+    ///
+    /// Implementations must move the value returned by `f()` out of the locked
+    /// scope, releasing the lock *before* returning it. The value is therefore
+    /// never dropped while the lock is held, so its [`Drop`] implementation may
+    /// itself lock the mutex. The [`conformance`] module provides a check for
+    /// this guarantee.
     fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R;
 
     /// Is this mutex currently locked?
+    ///
+    /// Implementations must return `true` for the entire duration of the
+    /// `f()` call in [`with_lock`] and [`try_with_lock`]. This may be relied
+    /// upon, e.g. by assertions within the closure. The [`conformance`]
+    /// module provides a check for this guarantee.
+    ///
+    /// Otherwise, the result is only an advisory hint. Implementations may
+    /// use a [`Relaxed`] load, so observing `false` does *not* synchronize
+    /// with the context which last released the lock, and writes it made
+    /// while holding the lock may not yet be visible. Use
+    /// [`is_locked_acquire`] when the result is used to make a decision which
+    /// depends on those writes.
+    ///
+    /// [`with_lock`]: ScopedRawMutex::with_lock
+    /// [`try_with_lock`]: ScopedRawMutex::try_with_lock
+    /// [`is_locked_acquire`]: ScopedRawMutex::is_locked_acquire
+    /// [`Relaxed`]: core::sync::atomic::Ordering::Relaxed
     fn is_locked(&self) -> bool;
+
+    /// Is this mutex currently locked, synchronizing with the last release of
+    /// the lock?
+    ///
+    /// This returns the same result as [`is_locked`], but with [`Acquire`]
+    /// semantics: if it returns `false`, everything written by the context
+    /// which last released the lock, before releasing it, is visible to the
+    /// caller. This makes it suitable as a synchronization snapshot, e.g.
+    /// when checking from another core that a critical section has finished.
+    ///
+    /// By default, this calls [`is_locked`] followed by an [`Acquire`]
+    /// [`fence`]. Implementations backed by an atomic lock state should
+    /// override it to perform an [`Acquire`] load of that state instead.
+    ///
+    /// [`is_locked`]: ScopedRawMutex::is_locked
+    /// [`Acquire`]: core::sync::atomic::Ordering::Acquire
+    /// [`fence`]: core::sync::atomic::fence
+    #[inline]
+    fn is_locked_acquire(&self) -> bool {
+        let locked = self.is_locked();
+        core::sync::atomic::fence(core::sync::atomic::Ordering::Acquire);
+        locked
+    }
+
+    /// Is this mutex currently locked by the current execution context?
+    ///
+    /// Implementations which are able to track which context holds the lock
+    /// may override this method to return `true` if the lock is held by the
+    /// calling context (e.g. from within the closure passed to [`with_lock`]).
+    ///
+    /// Implementations which do not track ownership conservatively return
+    /// `false`, which is the default. Therefore, a return value of `false`
+    /// does *not* imply that the current context does not hold the lock.
+    ///
+    /// [`with_lock`]: ScopedRawMutex::with_lock
+    #[inline]
+    fn is_owned(&self) -> bool {
+        false
+    }
 }
 
 /// Raw mutex trait.
@@ -96,7 +165,7 @@ pub unsafe trait ScopedRawMutex {
 /// [blanket]: ScopedRawMutex#impl-ScopedRawMutex-for-M
 /// [s]: https://doc.rust-lang.org/stable/std/sync/struct.Mutex.html
 /// [cs]: https://docs.rs/mutex/latest/mutex/raw_impls/cs/index.html
-/// [critical-section]: https://docs.rs/critical-section/latest/critical_section/
+/// [`critical-section`]: https://docs.rs/critical-section/latest/critical_section/
 pub unsafe trait RawMutex {
     /// Marker type which determines whether a lock guard should be [`Send`].
     type GuardMarker;
@@ -121,26 +190,109 @@ pub unsafe trait RawMutex {
     /// [`try_lock`]: RawMutex::try_lock
     unsafe fn unlock(&self);
 
+    /// Unlocks this mutex, and wakes a waiter blocked on acquiring it, if
+    /// there is one.
+    ///
+    /// Implementations which maintain a queue of waiters may override this
+    /// method to wake the next waiter in the queue. Any such wakeup must occur
+    /// *after* the lock has been released, so that the woken waiter is able to
+    /// acquire it. By default, this method simply calls [`unlock`].
+    ///
+    /// Lock guards and the [blanket implementation][blanket] of
+    /// [`ScopedRawMutex`] release the lock using this method, rather than
+    /// [`unlock`].
+    ///
+    /// # Safety
+    ///
+    /// This method has the same safety requirements as [`unlock`]: it may
+    /// only be called if the mutex is held in the current context.
+    ///
+    /// [`unlock`]: RawMutex::unlock
+    /// [blanket]: ScopedRawMutex#impl-ScopedRawMutex-for-M
+    #[inline]
+    unsafe fn unlock_and_wake(&self) {
+        self.unlock()
+    }
+
     /// Returns `true` if the mutex is currently locked.
+    ///
+    /// Implementations must return `true` between a successful call to
+    /// [`lock`] or [`try_lock`] and the subsequent call to [`unlock`].
+    ///
+    /// Otherwise, the result is only an advisory hint, which may be obtained
+    /// using a [`Relaxed`] load. See [`ScopedRawMutex::is_locked`] for
+    /// details.
+    ///
+    /// [`lock`]: RawMutex::lock
+    /// [`try_lock`]: RawMutex::try_lock
+    /// [`unlock`]: RawMutex::unlock
+    /// [`Relaxed`]: core::sync::atomic::Ordering::Relaxed
     fn is_locked(&self) -> bool;
+
+    /// Returns `true` if the mutex is currently locked, synchronizing with
+    /// the last call to [`unlock`].
+    ///
+    /// See [`ScopedRawMutex::is_locked_acquire`] for details. By default,
+    /// this calls [`is_locked`] followed by an [`Acquire`] [`fence`].
+    ///
+    /// [`unlock`]: RawMutex::unlock
+    /// [`is_locked`]: RawMutex::is_locked
+    /// [`Acquire`]: core::sync::atomic::Ordering::Acquire
+    /// [`fence`]: core::sync::atomic::fence
+    #[inline]
+    fn is_locked_acquire(&self) -> bool {
+        let locked = self.is_locked();
+        core::sync::atomic::fence(core::sync::atomic::Ordering::Acquire);
+        locked
+    }
+
+    /// Returns `true` if the mutex is currently locked by the current
+    /// execution context.
+    ///
+    /// Implementations which are able to track which context holds the lock
+    /// may override this method to return `true` if the lock is held by the
+    /// calling context.
+    ///
+    /// Implementations which do not track ownership conservatively return
+    /// `false`, which is the default. Therefore, a return value of `false`
+    /// does *not* imply that the current context does not hold the lock.
+    #[inline]
+    fn is_owned(&self) -> bool {
+        false
+    }
 }
 
+/// A [`RawMutex`] whose lock guards may be sent to other threads.
+///
+/// This is a convenience trait which is implemented for all [`RawMutex`]
+/// types whose [`GuardMarker`] is [`Send`]. Code which is generic over a raw
+/// mutex may use a `R: SendGuard` bound, rather than the more verbose `R:
+/// RawMutex, R::GuardMarker: Send`.
+///
+/// [`GuardMarker`]: RawMutex::GuardMarker
+pub trait SendGuard: RawMutex<GuardMarker: Send> {}
+
+impl<M> SendGuard for M where M: RawMutex<GuardMarker: Send> + ?Sized {}
+
 unsafe impl<M: RawMutex> ScopedRawMutex for M {
-    #[must_use]
-    #[inline]
+    // NOTE: these methods are `#[inline(always)]` so that, along with the
+    // drop guards they use, they reliably collapse into a call to `lock`, the
+    // body of `f`, and a call to `unlock` at the call site.
+    #[inline(always)]
     #[track_caller]
     fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
-        if self.try_lock() {
-            // Using a drop guard ensures that the mutex is unlocked when this
-            // function exits, even if `f()` panics.
-            let _unlock = Unlock(self);
-            Some(f())
-        } else {
-            None
-        }
+        scoped::run_locked(
+            || self.try_lock(),
+            || unsafe {
+                // Safety: the release function is only called if `try_lock`
+                // succeeded.
+                self.unlock_and_wake()
+            },
+            f,
+        )
     }
 
-    #[inline]
+    #[inline(always)]
     #[track_caller]
     fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
         self.lock();
@@ -155,22 +307,33 @@ unsafe impl<M: RawMutex> ScopedRawMutex for M {
     fn is_locked(&self) -> bool {
         RawMutex::is_locked(self)
     }
+
+    #[inline]
+    fn is_locked_acquire(&self) -> bool {
+        RawMutex::is_locked_acquire(self)
+    }
+
+    #[inline]
+    fn is_owned(&self) -> bool {
+        RawMutex::is_owned(self)
+    }
 }
 
 /// Implementation detail of the `ScopedRawMutex` implementation for `RawMutex`.
 /// This is a drop guard that unlocks the `RawMutex` when it's dropped. This is
 /// used to ensure that the `RawMutex` is always unlocked when the
-/// `ScopedRawMutex::with_lock` or `ScopedRawMutex::try_with_lock` closures are
-/// exited, even if they are exited by a panic rather than by a normal return.
+/// `ScopedRawMutex::with_lock` closure is exited, even if it is exited by a
+/// panic rather than by a normal return.
 struct Unlock<'mutex, M: RawMutex>(&'mutex M);
 
 impl<M: RawMutex> Drop for Unlock<'_, M> {
+    #[inline(always)]
     fn drop(&mut self) {
         unsafe {
             // Safety: Constructing an `Unlock` is only safe if the mutex has
             // been locked. Callers are responsible for ensuring this invariant;
             // since this struct is only constructed in this module, we do so.
-            self.0.unlock()
+            self.0.unlock_and_wake()
         }
     }
 }
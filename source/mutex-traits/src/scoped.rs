@@ -0,0 +1,45 @@
+//! Helpers for implementing [`ScopedRawMutex`] by hand.
+//!
+//! [`ScopedRawMutex`]: crate::ScopedRawMutex
+
+/// Attempts to take a lock, and runs `f` while the lock is held.
+///
+/// This encapsulates the logic of a typical [`ScopedRawMutex::try_with_lock`]
+/// implementation:
+///
+/// 1. `take()` is called to attempt to take the lock. If it returns `false`,
+///    the lock was already held, and `None` is returned without calling `f`.
+/// 2. Otherwise, `f()` is called, and its return value is returned as `Some`.
+/// 3. Finally, `release()` is called to release the lock. This is done using a
+///    drop guard, so that the lock is released even if `f()` panics, rather
+///    than remaining locked forever.
+///
+/// [`ScopedRawMutex::try_with_lock`]: crate::ScopedRawMutex::try_with_lock
+#[inline(always)]
+#[track_caller]
+pub fn run_locked<R>(
+    take: impl FnOnce() -> bool,
+    release: impl FnOnce(),
+    f: impl FnOnce() -> R,
+) -> Option<R> {
+    if !take() {
+        return None;
+    }
+    // Using a drop guard ensures that the lock is released when this
+    // function exits, even if `f()` panics.
+    let _release = Release(Some(release));
+    Some(f())
+}
+
+/// Drop guard used by [`run_locked`], which calls the release function when
+/// it's dropped.
+struct Release<F: FnOnce()>(Option<F>);
+
+impl<F: FnOnce()> Drop for Release<F> {
+    #[inline(always)]
+    fn drop(&mut self) {
+        if let Some(release) = self.0.take() {
+            release()
+        }
+    }
+}
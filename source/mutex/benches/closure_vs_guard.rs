@@ -0,0 +1,77 @@
+//! Compares the overhead of the closure-based and guard-based locking APIs.
+//!
+//! [`BlockingMutex::with_lock`] runs a closure while the lock is held, while
+//! [`BlockingMutex::lock`] returns a [`MutexGuard`] which releases the lock
+//! when it is dropped. Both are available when the raw mutex implements
+//! [`RawMutex`]. This benchmark measures both on the same uncontended
+//! backend, so that the difference is the cost of the API itself.
+//!
+//! Run with:
+//!
+//! ```text
+//! cargo bench -p mutex --bench closure_vs_guard
+//! ```
+//!
+//! # Results
+//!
+//! On an x86_64 Linux machine, the closure and guard paths take the same
+//! time, within measurement noise, which is dominated by the atomic
+//! operations which acquire and release the lock. Once inlined,
+//! neither the `Unlock` drop guard used by the closure API nor the
+//! `MutexGuard` returned by `lock` adds measurable overhead, and
+//! `MutexGuard`'s `PhantomData` marker has no runtime cost. The third case,
+//! which updates the value twice through the guard, is only slower by the
+//! cost of the additional update.
+//!
+//! The `debug_assert!`s checking that the mutex is locked when a
+//! `MutexGuard` is dereferenced or dropped are compiled out when debug
+//! assertions are disabled, as they are in the `bench` and `release`
+//! profiles. They therefore only add a load per dereference in debug builds.
+//!
+//! These results may differ on other targets, and with other backends; run
+//! the benchmark on your target to compare.
+//!
+//! [`RawMutex`]: mutex::RawMutex
+//! [`MutexGuard`]: mutex::MutexGuard
+
+use mutex::{raw_impls::spin::RandomizedSpinRawMutex, BlockingMutex};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ITERS: u32 = 10_000_000;
+const RUNS: usize = 5;
+
+static MUTEX: BlockingMutex<RandomizedSpinRawMutex, u64> =
+    BlockingMutex::const_new(RandomizedSpinRawMutex::new(), 0);
+
+fn main() {
+    bench("with_lock (closure)", || {
+        black_box(&MUTEX).with_lock(|val| *val = black_box(*val + 1));
+    });
+    bench("lock (guard)", || {
+        let mut guard = black_box(&MUTEX).lock();
+        *guard = black_box(*guard + 1);
+    });
+    bench("lock (guard, 4 derefs)", || {
+        let mut guard = black_box(&MUTEX).lock();
+        *guard = black_box(*guard + 1);
+        *guard = black_box(*guard + 1);
+    });
+}
+
+/// Runs `f` `ITERS` times, `RUNS` times over, and prints the fastest run's
+/// time per iteration.
+fn bench(name: &str, mut f: impl FnMut()) {
+    let best = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            for _ in 0..ITERS {
+                f();
+            }
+            start.elapsed()
+        })
+        .min()
+        .unwrap_or(Duration::ZERO);
+    let per_iter = best.as_secs_f64() * 1e9 / f64::from(ITERS);
+    println!("{name:<24} {per_iter:>8.2} ns/iter");
+}
@@ -0,0 +1,71 @@
+//! Compares returning a large result from [`BlockingMutex::with_lock`] with
+//! writing it in place through [`BlockingMutex::with_lock_into`].
+//!
+//! Both cases compute the same 4 KiB result from the protected data. The
+//! first returns it by value, so that it is moved out of the critical
+//! section, while the second writes it directly into a buffer owned by the
+//! caller, which is reused for every iteration.
+//!
+//! Run with:
+//!
+//! ```text
+//! cargo bench -p mutex --bench with_lock_into
+//! ```
+//!
+//! Whether the move is elided when returning by value depends on the
+//! optimizer, the size of the result, and the target, so run the benchmark on
+//! your target to compare.
+
+use mutex::{raw_impls::spin::RandomizedSpinRawMutex, BlockingMutex};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ITERS: u32 = 1_000_000;
+const RUNS: usize = 5;
+const LEN: usize = 512;
+
+static MUTEX: BlockingMutex<RandomizedSpinRawMutex, u64> =
+    BlockingMutex::const_new(RandomizedSpinRawMutex::new(), 0);
+
+/// Writes a result derived from `seed` into `out`.
+#[inline(never)]
+fn fill(seed: &mut u64, out: &mut [u64; LEN]) {
+    *seed = seed.wrapping_add(1);
+    for (i, slot) in out.iter_mut().enumerate() {
+        *slot = black_box(*seed ^ i as u64);
+    }
+}
+
+fn main() {
+    bench("with_lock (by value)", || {
+        let out = black_box(&MUTEX).with_lock(|seed| {
+            let mut out = [0; LEN];
+            fill(seed, &mut out);
+            out
+        });
+        black_box(&out);
+    });
+
+    let mut out = [0; LEN];
+    bench("with_lock_into (in place)", || {
+        black_box(&MUTEX).with_lock_into(&mut out, fill);
+        black_box(&out);
+    });
+}
+
+/// Runs `f` `ITERS` times, `RUNS` times over, and prints the fastest run's
+/// time per iteration.
+fn bench(name: &str, mut f: impl FnMut()) {
+    let best = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            for _ in 0..ITERS {
+                f();
+            }
+            start.elapsed()
+        })
+        .min()
+        .unwrap_or(Duration::ZERO);
+    let per_iter = best.as_secs_f64() * 1e9 / f64::from(ITERS);
+    println!("{name:<28} {per_iter:>8.2} ns/iter");
+}
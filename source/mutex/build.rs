@@ -4,4 +4,8 @@ mod common;
 fn main() {
     let mut cfgs = common::CfgSet::new();
     common::set_target_cfgs(&mut cfgs);
+
+    // Set by the final binary (e.g. using `RUSTFLAGS`) to assert that the
+    // whole program is single-threaded. See the README for details.
+    cfgs.declare("mutex_unsafe_assume_single_threaded");
 }
@@ -0,0 +1,31 @@
+//! A codegen check for the `ScopedRawMutex` blanket impl for `RawMutex`.
+//!
+//! The functions below are compiled to assembly in CI, for
+//! `thumbv7em-none-eabihf`, which then checks that no calls to the `Unlock`
+//! and `Release` drop guards (or to `drop_in_place` for them) remain, i.e.
+//! that `with_lock` and `try_with_lock` collapse into `lock`, the body of the
+//! closure, and `unlock`:
+//!
+//! ```text
+//! cargo rustc -p mutex --release --example inline_with_lock \
+//!     --target thumbv7em-none-eabihf -- --emit asm
+//! ```
+#![no_std]
+
+use mutex::{raw_impls::spin::RandomizedSpinRawMutex, BlockingMutex};
+
+#[no_mangle]
+pub fn codegen_with_lock(mutex: &BlockingMutex<RandomizedSpinRawMutex, u32>) -> u32 {
+    mutex.with_lock(|n| {
+        *n += 1;
+        *n
+    })
+}
+
+#[no_mangle]
+pub fn codegen_try_with_lock(mutex: &BlockingMutex<RandomizedSpinRawMutex, u32>) -> Option<u32> {
+    mutex.try_with_lock(|n| {
+        *n += 1;
+        *n
+    })
+}
@@ -0,0 +1,71 @@
+//! Abstractions over atomic types.
+//!
+//! Some raw mutex implementations in this crate are generic over the type of
+//! atomic used to track their state. By default, the types in
+//! [`core::sync::atomic`] are used, but the [`AtomicBoolLike`] trait allows
+//! substituting another implementation, such as the types provided by the
+//! [`portable-atomic`] crate, on a per-instance basis.
+//!
+//! [`portable-atomic`]: https://crates.io/crates/portable-atomic
+#![allow(clippy::declare_interior_mutable_const)]
+
+use core::sync::atomic::Ordering;
+
+/// A type which behaves like an [`AtomicBool`](core::sync::atomic::AtomicBool).
+///
+/// Only the operations required by the raw mutex implementations in this
+/// crate are included. Notably, this does not include read-modify-write
+/// operations, which are not available for
+/// [`core::sync::atomic::AtomicBool`] on all targets.
+///
+/// # Safety
+///
+/// Implementations must provide the same guarantees as the corresponding
+/// methods on [`AtomicBool`](core::sync::atomic::AtomicBool). Raw mutex
+/// implementations rely on this for soundness.
+pub unsafe trait AtomicBoolLike {
+    /// An atomic whose initial value is `false`.
+    ///
+    /// This is a const, so that raw mutexes using this atomic may be
+    /// constructed in const context.
+    const FALSE: Self;
+
+    /// Loads the value. See [`AtomicBool::load`].
+    ///
+    /// [`AtomicBool::load`]: core::sync::atomic::AtomicBool::load
+    fn load(&self, order: Ordering) -> bool;
+
+    /// Stores a value. See [`AtomicBool::store`].
+    ///
+    /// [`AtomicBool::store`]: core::sync::atomic::AtomicBool::store
+    fn store(&self, val: bool, order: Ordering);
+}
+
+unsafe impl AtomicBoolLike for core::sync::atomic::AtomicBool {
+    const FALSE: Self = Self::new(false);
+
+    #[inline(always)]
+    fn load(&self, order: Ordering) -> bool {
+        self.load(order)
+    }
+
+    #[inline(always)]
+    fn store(&self, val: bool, order: Ordering) {
+        self.store(val, order)
+    }
+}
+
+#[cfg(feature = "portable-atomic")]
+unsafe impl AtomicBoolLike for portable_atomic::AtomicBool {
+    const FALSE: Self = Self::new(false);
+
+    #[inline(always)]
+    fn load(&self, order: Ordering) -> bool {
+        self.load(order)
+    }
+
+    #[inline(always)]
+    fn store(&self, val: bool, order: Ordering) {
+        self.store(val, order)
+    }
+}
@@ -0,0 +1,191 @@
+//! Mutexes which detect corruption of their own memory.
+//!
+//! This module provides [`CanaryMutex`], which surrounds a [`BlockingMutex`]
+//! with canary values, and checks them each time it is locked. This catches
+//! out-of-bounds writes (e.g. through a dangling pointer obtained from
+//! [`BlockingMutex::get_unchecked`]) which clobber the lock state or the
+//! protected data, rather than letting them silently break mutual exclusion.
+
+use core::panic::Location;
+
+use crate::{BlockingMutex, ConstInit, ScopedRawMutex};
+
+/// The value stored in each canary.
+#[cfg(debug_assertions)]
+const CANARY: usize = 0xA5C3_5A3C_A5C3_5A3C_u64 as usize;
+
+/// A [`BlockingMutex`] surrounded by canary values, which are checked each
+/// time it is locked.
+///
+/// In builds with debug assertions enabled, a known value is stored
+/// immediately before and immediately after the wrapped mutex (i.e. adjacent
+/// to the raw mutex's lock state and to the protected data). These canaries
+/// are checked before the lock is acquired, and again before it is released.
+/// If either has changed, something outside of this mutex has written over
+/// its memory, and a panic occurs, reporting the location of the call to
+/// [`with_lock`](Self::with_lock) which detected it.
+///
+/// This is not a replacement for a memory error detector such as
+/// AddressSanitizer: it only detects writes which overrun into the canaries
+/// themselves, and only when the mutex is next locked. However, it is cheap
+/// enough to leave enabled in debug builds on embedded targets, where such
+/// tools are not available.
+///
+/// In builds with debug assertions disabled, the canaries are not stored, and
+/// a `CanaryMutex` behaves exactly like the [`BlockingMutex`] it wraps.
+///
+/// # Examples
+///
+/// ```
+/// use mutex::canary::CanaryMutex;
+/// use mutex::raw_impls::cs::CriticalSectionRawMutex;
+///
+/// static BUFFER: CanaryMutex<CriticalSectionRawMutex, [u8; 16]> =
+///     CanaryMutex::new([0; 16]);
+///
+/// BUFFER.with_lock(|buf| buf[0] = 1);
+/// ```
+#[repr(C)]
+pub struct CanaryMutex<R, T> {
+    #[cfg(debug_assertions)]
+    head: usize,
+    inner: BlockingMutex<R, T>,
+    #[cfg(debug_assertions)]
+    tail: usize,
+}
+
+// === impl CanaryMutex ===
+
+impl<R: ConstInit, T> CanaryMutex<R, T> {
+    /// Creates a new mutex in an unlocked state ready for use.
+    #[inline]
+    pub const fn new(val: T) -> Self {
+        Self::from_inner(BlockingMutex::new(val))
+    }
+}
+
+impl<R, T> CanaryMutex<R, T> {
+    /// Creates a new mutex based on a pre-existing raw mutex.
+    #[inline]
+    pub const fn const_new(raw_mutex: R, val: T) -> Self {
+        Self::from_inner(BlockingMutex::const_new(raw_mutex, val))
+    }
+
+    #[inline]
+    const fn from_inner(inner: BlockingMutex<R, T>) -> Self {
+        Self {
+            #[cfg(debug_assertions)]
+            head: CANARY,
+            inner,
+            #[cfg(debug_assertions)]
+            tail: CANARY,
+        }
+    }
+
+    /// Returns a reference to the underlying raw mutex.
+    #[inline]
+    pub fn raw(&self) -> &R {
+        self.inner.raw()
+    }
+
+    /// Consumes this mutex, returning the underlying data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+
+    /// Returns a pointer to the inner storage
+    ///
+    /// # Safety
+    ///
+    /// Must NOT be called when the lock is taken
+    pub unsafe fn get_unchecked(&self) -> *mut T {
+        self.inner.get_unchecked()
+    }
+
+    #[inline(always)]
+    fn check(&self, location: &Location<'_>) {
+        #[cfg(debug_assertions)]
+        {
+            // SAFETY: the canaries are valid for reads for the lifetime of
+            // `self`. Volatile reads are used so that the compiler does not
+            // assume that they still hold the values they were initialized
+            // with.
+            let (head, tail) = unsafe {
+                (
+                    core::ptr::read_volatile(&self.head),
+                    core::ptr::read_volatile(&self.tail),
+                )
+            };
+            assert!(
+                head == CANARY && tail == CANARY,
+                "memory surrounding `CanaryMutex<_, {}>` was corrupted, detected at \
+                 {location} (head canary: {head:#x}, tail canary: {tail:#x})",
+                core::any::type_name::<T>(),
+            );
+        }
+        #[cfg(not(debug_assertions))]
+        let _ = location;
+    }
+}
+
+impl<R: ScopedRawMutex, T> CanaryMutex<R, T> {
+    /// Locks the raw mutex and grants temporary access to the inner data,
+    /// checking the canaries before acquiring and before releasing the lock.
+    ///
+    /// # Panics
+    ///
+    /// In builds with debug assertions enabled, this panics if either canary
+    /// has been overwritten.
+    ///
+    /// Behavior when the lock is already locked is dependent on the behavior
+    /// of the Raw mutex. See [`ScopedRawMutex::with_lock()`]'s documentation for
+    /// more details
+    #[inline]
+    #[track_caller]
+    pub fn with_lock<U>(&self, f: impl FnOnce(&mut T) -> U) -> U {
+        let location = Location::caller();
+        self.check(location);
+        self.inner.with_lock(|data| {
+            let ret = f(data);
+            self.check(location);
+            ret
+        })
+    }
+
+    /// Locks the raw mutex and grants temporary access to the inner data,
+    /// checking the canaries before acquiring and before releasing the lock.
+    ///
+    /// Returns `Some(U)` if the lock was obtained. Returns `None` if the lock
+    /// was already locked
+    ///
+    /// # Panics
+    ///
+    /// In builds with debug assertions enabled, this panics if either canary
+    /// has been overwritten.
+    #[must_use]
+    #[inline]
+    #[track_caller]
+    pub fn try_with_lock<U>(&self, f: impl FnOnce(&mut T) -> U) -> Option<U> {
+        let location = Location::caller();
+        self.check(location);
+        self.inner.try_with_lock(|data| {
+            let ret = f(data);
+            self.check(location);
+            ret
+        })
+    }
+}
+
+#[cfg(feature = "fmt")]
+impl<R, T> core::fmt::Debug for CanaryMutex<R, T>
+where
+    R: ScopedRawMutex + core::fmt::Debug,
+    T: core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("CanaryMutex")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
@@ -0,0 +1,151 @@
+//! Collections built on [`BlockingMutex`].
+//!
+//! The collections in this module lock their mutex only for the duration of a
+//! single operation, keeping critical sections as short as possible.
+
+use core::mem::MaybeUninit;
+
+use crate::{BlockingMutex, ConstInit, ScopedRawMutex};
+
+/// A fixed-capacity FIFO queue, protected by a [`BlockingMutex`].
+///
+/// The queue can hold up to `N` elements. Each [`push`](Queue::push) or
+/// [`pop`](Queue::pop) locks the mutex only while the element is being moved
+/// into or out of the queue, so a `Queue` may be shared between any number of
+/// producers and consumers (including interrupt handlers, when using an
+/// appropriate raw mutex).
+pub struct Queue<R, T, const N: usize> {
+    mutex: BlockingMutex<R, Ring<T, N>>,
+}
+
+/// A fixed-capacity ring buffer.
+struct Ring<T, const N: usize> {
+    buf: [MaybeUninit<T>; N],
+    head: usize,
+    len: usize,
+}
+
+// === impl Queue ===
+
+impl<R: ConstInit, T, const N: usize> Queue<R, T, N> {
+    /// Creates a new, empty queue.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            mutex: BlockingMutex::new(Ring::EMPTY),
+        }
+    }
+}
+
+impl<R: ConstInit, T, const N: usize> Default for Queue<R, T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R, T, const N: usize> Queue<R, T, N> {
+    /// Creates a new, empty queue based on a pre-existing raw mutex.
+    #[inline]
+    pub const fn const_new(raw_mutex: R) -> Self {
+        Self {
+            mutex: BlockingMutex::const_new(raw_mutex, Ring::EMPTY),
+        }
+    }
+
+    /// Returns the maximum number of elements the queue can hold.
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+}
+
+impl<R: ScopedRawMutex, T, const N: usize> Queue<R, T, N> {
+    /// Pushes an element to the back of the queue.
+    ///
+    /// Returns `Err(value)` if the queue is full.
+    ///
+    /// Behavior when the lock is already locked is dependent on the behavior
+    /// of the Raw mutex. See [`ScopedRawMutex::with_lock()`]'s documentation for
+    /// more details
+    pub fn push(&self, value: T) -> Result<(), T> {
+        self.mutex.with_lock(|ring| ring.push(value))
+    }
+
+    /// Pops an element from the front of the queue.
+    ///
+    /// Returns `None` if the queue is empty.
+    ///
+    /// Behavior when the lock is already locked is dependent on the behavior
+    /// of the Raw mutex. See [`ScopedRawMutex::with_lock()`]'s documentation for
+    /// more details
+    pub fn pop(&self) -> Option<T> {
+        self.mutex.with_lock(Ring::pop)
+    }
+
+    /// Returns the number of elements currently in the queue.
+    pub fn len(&self) -> usize {
+        self.mutex.with_lock(|ring| ring.len)
+    }
+
+    /// Returns `true` if the queue contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if the queue contains `N` elements.
+    pub fn is_full(&self) -> bool {
+        self.len() == N
+    }
+}
+
+#[cfg(feature = "fmt")]
+impl<R, T, const N: usize> core::fmt::Debug for Queue<R, T, N>
+where
+    R: ScopedRawMutex + core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut s = f.debug_struct("Queue");
+        s.field("raw", self.mutex.raw()).field("capacity", &N);
+
+        self.mutex
+            .try_with_lock(|ring| s.field("len", &ring.len).finish())
+            .unwrap_or_else(|| s.field("len", &format_args!("<locked>")).finish())
+    }
+}
+
+// === impl Ring ===
+
+impl<T, const N: usize> Ring<T, N> {
+    const EMPTY: Self = Self {
+        buf: [const { MaybeUninit::uninit() }; N],
+        head: 0,
+        len: 0,
+    };
+
+    fn push(&mut self, value: T) -> Result<(), T> {
+        if self.len == N {
+            return Err(value);
+        }
+        self.buf[(self.head + self.len) % N].write(value);
+        self.len += 1;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        // SAFETY: the `len` elements starting at `head` are initialized, and
+        // we advance `head` past this element so it will not be read again.
+        let value = unsafe { self.buf[self.head].assume_init_read() };
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        Some(value)
+    }
+}
+
+impl<T, const N: usize> Drop for Ring<T, N> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
@@ -0,0 +1,228 @@
+//! Mutexes which defer updates when they cannot be locked.
+//!
+//! This module provides [`DeferredMutex`], which serves the common pattern of
+//! updating shared data from an interrupt handler that may run while the data
+//! is locked by thread-mode code. Rather than dropping the update, it is
+//! queued, and applied when the lock is next released.
+
+use crate::{BlockingMutex, ConstInit, ScopedRawMutex};
+
+/// A [`BlockingMutex`] which may queue operations to run when it is next
+/// unlocked, if it cannot be locked immediately.
+///
+/// Up to `N` deferred operations may be queued at a time. The queue is
+/// protected by a second raw mutex of type `R`, which is only ever held
+/// briefly, while pushing or popping an operation.
+pub struct DeferredMutex<R, T, const N: usize> {
+    mutex: BlockingMutex<R, T>,
+    queue: BlockingMutex<R, Queue<T, N>>,
+}
+
+/// Errors returned by [`DeferredMutex::with_lock_or_defer`].
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "fmt", derive(Debug))]
+#[non_exhaustive]
+pub enum DeferError {
+    /// The mutex was locked, and the queue of deferred operations was full.
+    /// The operation was not run, and was not queued.
+    Full,
+    /// The mutex was locked, and the queue of deferred operations was itself
+    /// locked, so the operation could not be queued. The operation was not
+    /// run, and was not queued.
+    Busy,
+}
+
+/// A fixed-capacity FIFO queue of deferred operations.
+struct Queue<T, const N: usize> {
+    ops: [Option<fn(&mut T)>; N],
+    head: usize,
+    len: usize,
+}
+
+// === impl DeferredMutex ===
+
+impl<R: ConstInit, T, const N: usize> DeferredMutex<R, T, N> {
+    /// Creates a new mutex in an unlocked state ready for use, with an empty
+    /// queue of deferred operations.
+    #[inline]
+    pub const fn new(val: T) -> Self {
+        Self {
+            mutex: BlockingMutex::new(val),
+            queue: BlockingMutex::new(Queue::EMPTY),
+        }
+    }
+}
+
+impl<R, T, const N: usize> DeferredMutex<R, T, N> {
+    /// Creates a new mutex based on pre-existing raw mutexes for the data and
+    /// for the queue of deferred operations.
+    #[inline]
+    pub const fn const_new(raw_mutex: R, queue_raw_mutex: R, val: T) -> Self {
+        Self {
+            mutex: BlockingMutex::const_new(raw_mutex, val),
+            queue: BlockingMutex::const_new(queue_raw_mutex, Queue::EMPTY),
+        }
+    }
+
+    /// Consumes this mutex, returning the underlying data.
+    ///
+    /// Any operations which are still queued are run on the data before it is
+    /// returned.
+    pub fn into_inner(self) -> T {
+        let mut data = self.mutex.into_inner();
+        let mut queue = self.queue.into_inner();
+        while let Some(op) = queue.pop() {
+            op(&mut data);
+        }
+        data
+    }
+}
+
+impl<R: ScopedRawMutex, T, const N: usize> DeferredMutex<R, T, N> {
+    /// Locks the raw mutex and grants temporary access to the inner data.
+    ///
+    /// Any operations deferred while the lock was held are run, in the order
+    /// in which they were deferred, after `f` returns and before the lock is
+    /// released. If an operation is deferred after the queue was drained but
+    /// before the lock was released, the lock is taken again to run it.
+    ///
+    /// Behavior when the lock is already locked is dependent on the behavior
+    /// of the Raw mutex. See [`ScopedRawMutex::with_lock()`]'s documentation for
+    /// more details
+    pub fn with_lock<U>(&self, f: impl FnOnce(&mut T) -> U) -> U {
+        let ret = self.mutex.with_lock(|data| {
+            let ret = f(data);
+            self.drain(data);
+            ret
+        });
+        self.run_stranded();
+        ret
+    }
+
+    /// Locks the raw mutex and grants temporary access to the inner data
+    ///
+    /// Returns `Some(U)` if the lock was obtained. Returns `None` if the lock
+    /// was already locked. As with [`DeferredMutex::with_lock`], deferred
+    /// operations are run after `f` returns.
+    #[must_use]
+    pub fn try_with_lock<U>(&self, f: impl FnOnce(&mut T) -> U) -> Option<U> {
+        let ret = self.mutex.try_with_lock(|data| {
+            let ret = f(data);
+            self.drain(data);
+            ret
+        })?;
+        self.run_stranded();
+        Some(ret)
+    }
+
+    /// Runs `op` on the inner data immediately if the mutex can be locked, or
+    /// queues it to run when the mutex is next unlocked otherwise.
+    ///
+    /// This method never blocks, and is intended for use in contexts, such as
+    /// interrupt handlers, which must not wait for the lock to be released.
+    ///
+    /// # Errors
+    ///
+    /// If the mutex is locked and the operation cannot be queued, it is
+    /// discarded and an error is returned:
+    ///
+    /// - [`DeferError::Full`] if `N` operations are already queued.
+    /// - [`DeferError::Busy`] if the queue itself is locked.
+    pub fn with_lock_or_defer(&self, op: fn(&mut T)) -> Result<(), DeferError> {
+        if self.try_with_lock(op).is_some() {
+            return Ok(());
+        }
+
+        self.queue
+            .try_with_lock(|queue| queue.push(op))
+            .ok_or(DeferError::Busy)??;
+
+        // The lock may have been released after we failed to acquire it, but
+        // before the operation was queued, in which case nothing would run the
+        // queued operation until the next time the mutex is locked. If we can
+        // lock the mutex now, run it ourselves.
+        self.run_stranded();
+        Ok(())
+    }
+
+    /// Runs all queued operations on `data`. This must only be called while
+    /// the mutex is locked.
+    fn drain(&self, data: &mut T) {
+        // Pop operations one at a time, so that the queue is not locked while
+        // the operations run.
+        while let Some(op) = self.queue.with_lock(Queue::pop) {
+            op(data);
+        }
+    }
+
+    /// Runs any operations which were queued after the queue was last
+    /// drained, but before the mutex was unlocked. This must only be called
+    /// while the mutex is not locked by the caller.
+    fn run_stranded(&self) {
+        // If the mutex cannot be locked, whoever holds it will drain the queue
+        // and call this again after releasing it. Likewise, if the queue is
+        // locked, whoever is pushing to it will call this afterwards.
+        while self.queue.try_with_lock(|queue| queue.len != 0) == Some(true) {
+            if self.mutex.try_with_lock(|data| self.drain(data)).is_none() {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(feature = "fmt")]
+impl<R, T, const N: usize> core::fmt::Debug for DeferredMutex<R, T, N>
+where
+    R: ScopedRawMutex + core::fmt::Debug,
+    T: core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut s = f.debug_struct("DeferredMutex");
+        s.field("mutex", &self.mutex);
+
+        self.queue
+            .try_with_lock(|queue| s.field("queued", &queue.len).finish())
+            .unwrap_or_else(|| s.field("queued", &format_args!("<locked>")).finish())
+    }
+}
+
+// === impl DeferError ===
+
+#[cfg(feature = "fmt")]
+impl core::fmt::Display for DeferError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Full => f.write_str("deferred operation queue is full"),
+            Self::Busy => f.write_str("deferred operation queue is locked"),
+        }
+    }
+}
+
+// === impl Queue ===
+
+impl<T, const N: usize> Queue<T, N> {
+    const EMPTY: Self = Self {
+        ops: [None; N],
+        head: 0,
+        len: 0,
+    };
+
+    fn push(&mut self, op: fn(&mut T)) -> Result<(), DeferError> {
+        if self.len == N {
+            return Err(DeferError::Full);
+        }
+        self.ops[(self.head + self.len) % N] = Some(op);
+        self.len += 1;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<fn(&mut T)> {
+        if self.len == 0 {
+            return None;
+        }
+        let op = self.ops[self.head].take();
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        op
+    }
+}
@@ -0,0 +1,182 @@
+//! Mutexes which report whether they are being locked for the first time.
+//!
+//! This module provides [`FirstLockMutex`], which serves the pattern of
+//! lazily initializing shared data the first time it is used, and accessing
+//! it normally thereafter, using a single lock acquisition.
+
+use crate::{BlockingMutex, ConstInit, ScopedRawMutex};
+
+/// A [`BlockingMutex`] which tracks whether it has ever been locked.
+///
+/// [`with_lock_first`](Self::with_lock_first) passes a `bool` to its closure
+/// which is `true` only for the first successful acquisition of the lock
+/// over the lifetime of the mutex. The flag is stored alongside the
+/// protected data, and is only read and updated while the lock is held, so
+/// two contexts racing to lock the mutex for the first time cannot both
+/// observe `true`.
+///
+/// Every successful acquisition counts towards this, including those made
+/// using [`with_lock`](Self::with_lock) and
+/// [`try_with_lock`](Self::try_with_lock), which do not report the flag.
+///
+/// # Examples
+///
+/// ```
+/// use mutex::first::FirstLockMutex;
+/// use mutex::raw_impls::cs::CriticalSectionRawMutex;
+///
+/// static TABLE: FirstLockMutex<CriticalSectionRawMutex, [u32; 8]> =
+///     FirstLockMutex::new([0; 8]);
+///
+/// fn lookup(i: usize) -> u32 {
+///     TABLE.with_lock_first(|table, first| {
+///         if first {
+///             for (n, entry) in table.iter_mut().enumerate() {
+///                 *entry = (n as u32) * (n as u32);
+///             }
+///         }
+///         table[i]
+///     })
+/// }
+///
+/// assert_eq!(lookup(3), 9);
+/// assert_eq!(lookup(7), 49);
+/// ```
+pub struct FirstLockMutex<R, T> {
+    inner: BlockingMutex<R, State<T>>,
+}
+
+/// The data protected by a [`FirstLockMutex`], along with whether it has been
+/// locked.
+struct State<T> {
+    locked_before: bool,
+    data: T,
+}
+
+// === impl FirstLockMutex ===
+
+impl<R: ConstInit, T> FirstLockMutex<R, T> {
+    /// Creates a new mutex in an unlocked state ready for use, which has
+    /// never been locked.
+    #[inline]
+    pub const fn new(val: T) -> Self {
+        Self {
+            inner: BlockingMutex::new(State::new(val)),
+        }
+    }
+}
+
+impl<R, T> FirstLockMutex<R, T> {
+    /// Creates a new mutex based on a pre-existing raw mutex, which has never
+    /// been locked.
+    #[inline]
+    pub const fn const_new(raw_mutex: R, val: T) -> Self {
+        Self {
+            inner: BlockingMutex::const_new(raw_mutex, State::new(val)),
+        }
+    }
+
+    /// Returns a reference to the underlying raw mutex.
+    #[inline]
+    pub fn raw(&self) -> &R {
+        self.inner.raw()
+    }
+
+    /// Consumes this mutex, returning the underlying data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner().data
+    }
+}
+
+impl<R: ScopedRawMutex, T> FirstLockMutex<R, T> {
+    /// Locks the raw mutex and grants temporary access to the inner data,
+    /// along with whether this is the first time the mutex has been locked.
+    ///
+    /// The `bool` passed to `f` is `true` if no previous call has
+    /// successfully locked this mutex, and `false` otherwise.
+    ///
+    /// Behavior when the lock is already locked is dependent on the behavior
+    /// of the Raw mutex. See [`ScopedRawMutex::with_lock()`]'s documentation for
+    /// more details
+    #[inline]
+    #[track_caller]
+    pub fn with_lock_first<U>(&self, f: impl FnOnce(&mut T, bool) -> U) -> U {
+        self.inner.with_lock(|state| state.access(f))
+    }
+
+    /// Locks the raw mutex and grants temporary access to the inner data,
+    /// along with whether this is the first time the mutex has been locked.
+    ///
+    /// Returns `Some(U)` if the lock was obtained. Returns `None` if the lock
+    /// was already locked. A failed attempt does not count as the first
+    /// acquisition.
+    #[must_use]
+    #[inline]
+    #[track_caller]
+    pub fn try_with_lock_first<U>(&self, f: impl FnOnce(&mut T, bool) -> U) -> Option<U> {
+        self.inner.try_with_lock(|state| state.access(f))
+    }
+
+    /// Locks the raw mutex and grants temporary access to the inner data.
+    ///
+    /// Behavior when the lock is already locked is dependent on the behavior
+    /// of the Raw mutex. See [`ScopedRawMutex::with_lock()`]'s documentation for
+    /// more details
+    #[inline]
+    #[track_caller]
+    pub fn with_lock<U>(&self, f: impl FnOnce(&mut T) -> U) -> U {
+        self.with_lock_first(|data, _| f(data))
+    }
+
+    /// Locks the raw mutex and grants temporary access to the inner data.
+    ///
+    /// Returns `Some(U)` if the lock was obtained. Returns `None` if the lock
+    /// was already locked
+    #[must_use]
+    #[inline]
+    #[track_caller]
+    pub fn try_with_lock<U>(&self, f: impl FnOnce(&mut T) -> U) -> Option<U> {
+        self.try_with_lock_first(|data, _| f(data))
+    }
+}
+
+#[cfg(feature = "fmt")]
+impl<R, T> core::fmt::Debug for FirstLockMutex<R, T>
+where
+    R: ScopedRawMutex + core::fmt::Debug,
+    T: core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut s = f.debug_struct("FirstLockMutex");
+        s.field("raw", self.inner.raw());
+
+        // NOTE: this accesses the state directly, rather than using
+        // `State::access`, so that formatting the mutex does not count as
+        // locking it for the first time.
+        self.inner
+            .try_with_lock(|state| {
+                s.field("data", &state.data)
+                    .field("locked_before", &state.locked_before)
+                    .finish()
+            })
+            .unwrap_or_else(|| s.field("data", &format_args!("<locked>")).finish())
+    }
+}
+
+// === impl State ===
+
+impl<T> State<T> {
+    const fn new(data: T) -> Self {
+        Self {
+            locked_before: false,
+            data,
+        }
+    }
+
+    #[inline(always)]
+    fn access<U>(&mut self, f: impl FnOnce(&mut T, bool) -> U) -> U {
+        let first = !core::mem::replace(&mut self.locked_before, true);
+        f(&mut self.data, first)
+    }
+}
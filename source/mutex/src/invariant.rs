@@ -0,0 +1,171 @@
+//! Mutexes which check that the protected data upholds an invariant.
+//!
+//! This module provides [`InvariantMutex`], which turns code that leaves the
+//! protected data in an impossible state into a panic at the exact critical
+//! section which corrupted it.
+
+use core::panic::Location;
+
+use crate::{BlockingMutex, ConstInit, ScopedRawMutex};
+
+/// A [`BlockingMutex`] whose data must uphold an invariant whenever the
+/// mutex is unlocked.
+///
+/// The invariant is a `fn(&T) -> bool`, which returns `true` if the data is
+/// valid. In builds with debug assertions enabled, the invariant is checked
+/// each time the closure passed to [`with_lock`](Self::with_lock) or
+/// [`try_with_lock`](Self::try_with_lock) returns, *before* the lock is
+/// released, and a panic occurs if it does not hold. The panic reports the
+/// location of the call to `with_lock` whose closure violated the invariant.
+/// In builds with debug assertions disabled, the invariant is never checked.
+///
+/// The invariant is not checked when the mutex is constructed. To validate the
+/// initial data, use [`InvariantMutex::try_new()`].
+///
+/// # Examples
+///
+/// ```
+/// use mutex::{ConstInit, ScopedRawMutex};
+/// use mutex::invariant::InvariantMutex;
+///
+/// struct Range {
+///     start: u32,
+///     end: u32,
+/// }
+///
+/// fn new_range<R: ConstInit>(start: u32, end: u32) -> InvariantMutex<R, Range> {
+///     InvariantMutex::new(Range { start, end }, |r| r.start <= r.end)
+/// }
+///
+/// fn shift<R: ScopedRawMutex>(range: &InvariantMutex<R, Range>, by: u32) {
+///     range.with_lock(|r| {
+///         // Temporarily violating the invariant within the closure is fine.
+///         r.start += by;
+///         r.end += by;
+///     })
+/// }
+/// ```
+pub struct InvariantMutex<R, T> {
+    inner: BlockingMutex<R, T>,
+    invariant: fn(&T) -> bool,
+}
+
+// === impl InvariantMutex ===
+
+impl<R: ConstInit, T> InvariantMutex<R, T> {
+    /// Creates a new mutex in an unlocked state ready for use, whose data must
+    /// uphold `invariant`.
+    #[inline]
+    pub const fn new(val: T, invariant: fn(&T) -> bool) -> Self {
+        Self {
+            inner: BlockingMutex::new(val),
+            invariant,
+        }
+    }
+
+    /// Creates a new mutex in an unlocked state ready for use, whose data must
+    /// uphold `invariant`, if `val` upholds it.
+    ///
+    /// Unlike the checks performed when the mutex is unlocked, this check is
+    /// performed regardless of whether debug assertions are enabled. If `val`
+    /// does not uphold the invariant, it is returned as an error.
+    #[inline]
+    pub fn try_new(val: T, invariant: fn(&T) -> bool) -> Result<Self, T> {
+        if !invariant(&val) {
+            return Err(val);
+        }
+        Ok(Self::new(val, invariant))
+    }
+}
+
+impl<R, T> InvariantMutex<R, T> {
+    /// Creates a new mutex based on a pre-existing raw mutex, whose data must
+    /// uphold `invariant`.
+    #[inline]
+    pub const fn const_new(raw_mutex: R, val: T, invariant: fn(&T) -> bool) -> Self {
+        Self {
+            inner: BlockingMutex::const_new(raw_mutex, val),
+            invariant,
+        }
+    }
+
+    /// Returns a reference to the underlying raw mutex.
+    #[inline]
+    pub fn raw(&self) -> &R {
+        self.inner.raw()
+    }
+
+    /// Consumes this mutex, returning the underlying data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
+
+impl<R: ScopedRawMutex, T> InvariantMutex<R, T> {
+    /// Locks the raw mutex and grants temporary access to the inner data,
+    /// checking that the invariant holds before it is unlocked.
+    ///
+    /// # Panics
+    ///
+    /// In builds with debug assertions enabled, this panics if the data does
+    /// not uphold the invariant after `f` returns.
+    ///
+    /// Behavior when the lock is already locked is dependent on the behavior
+    /// of the Raw mutex. See [`ScopedRawMutex::with_lock()`]'s documentation for
+    /// more details
+    #[inline]
+    #[track_caller]
+    pub fn with_lock<U>(&self, f: impl FnOnce(&mut T) -> U) -> U {
+        let location = Location::caller();
+        self.inner.with_lock(|data| {
+            let ret = f(data);
+            self.check(data, location);
+            ret
+        })
+    }
+
+    /// Locks the raw mutex and grants temporary access to the inner data,
+    /// checking that the invariant holds before it is unlocked.
+    ///
+    /// Returns `Some(U)` if the lock was obtained. Returns `None` if the lock
+    /// was already locked
+    ///
+    /// # Panics
+    ///
+    /// In builds with debug assertions enabled, this panics if the data does
+    /// not uphold the invariant after `f` returns.
+    #[must_use]
+    #[inline]
+    #[track_caller]
+    pub fn try_with_lock<U>(&self, f: impl FnOnce(&mut T) -> U) -> Option<U> {
+        let location = Location::caller();
+        self.inner.try_with_lock(|data| {
+            let ret = f(data);
+            self.check(data, location);
+            ret
+        })
+    }
+
+    #[inline(always)]
+    fn check(&self, data: &T, location: &Location<'_>) {
+        debug_assert!(
+            (self.invariant)(data),
+            "invariant of `InvariantMutex<_, {}>` violated within critical section at {location}",
+            core::any::type_name::<T>(),
+        );
+    }
+}
+
+#[cfg(feature = "fmt")]
+impl<R, T> core::fmt::Debug for InvariantMutex<R, T>
+where
+    R: ScopedRawMutex + core::fmt::Debug,
+    T: core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("InvariantMutex")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
@@ -4,12 +4,67 @@
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 #![cfg_attr(feature = "fmt", warn(missing_debug_implementations))]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+pub mod atomic;
+#[cfg(feature = "canary")]
+pub mod canary;
+#[cfg(feature = "collections")]
+pub mod collections;
+pub mod deferred;
+pub mod first;
+pub mod invariant;
+pub mod max_size;
+pub mod peripheral;
 pub mod raw_impls;
+pub mod uninit;
 
 use core::cell::UnsafeCell;
 use core::marker::PhantomData;
+use core::mem::MaybeUninit;
 use core::ops::{Deref, DerefMut};
-pub use mutex_traits::{ConstInit, RawMutex, ScopedRawMutex};
+use core::pin::Pin;
+use core::ptr;
+use core::time::Duration;
+pub use mutex_traits::{ConstInit, RawMutex, ScopedRawMutex, SendGuard};
+
+/// The default raw mutex implementation for the current build configuration.
+///
+/// This allows writing code which uses a sensible mutex implementation both
+/// when running tests on a hosted target and when running on an embedded
+/// device, without selecting an implementation at every use site. It is
+/// selected as follows, in order of precedence:
+///
+/// 1. If the `std` feature is enabled, this is [`StdRawMutex`], which parks
+///    waiting threads.
+/// 2. Otherwise, if the `impl-critical-section` feature is enabled, this is
+///    [`CriticalSectionRawMutex`].
+///
+/// If neither feature is enabled, this alias is not defined.
+///
+/// As cargo features are additive, enabling `std` anywhere in the dependency
+/// graph will select [`StdRawMutex`]. Code which requires a particular
+/// implementation, such as one which may be shared with interrupt handlers,
+/// should name that implementation directly (or define its own alias), rather
+/// than using `DefaultRawMutex`.
+///
+/// [`StdRawMutex`]: crate::raw_impls::std_sync::StdRawMutex
+/// [`CriticalSectionRawMutex`]: crate::raw_impls::cs::CriticalSectionRawMutex
+#[cfg(feature = "std")]
+pub type DefaultRawMutex = raw_impls::std_sync::StdRawMutex;
+
+/// The default raw mutex implementation for the current build configuration.
+///
+/// See the documentation for this alias when the `std` feature is enabled
+/// for details on how it is selected.
+#[cfg(all(not(feature = "std"), feature = "impl-critical-section"))]
+pub type DefaultRawMutex = raw_impls::cs::CriticalSectionRawMutex;
+
+/// A [`BlockingMutex`] using the [`DefaultRawMutex`] for the current build
+/// configuration.
+#[cfg(any(feature = "std", feature = "impl-critical-section"))]
+pub type DefaultMutex<T> = BlockingMutex<DefaultRawMutex, T>;
 
 /// Blocking mutex (not async)
 ///
@@ -29,7 +84,58 @@ pub use mutex_traits::{ConstInit, RawMutex, ScopedRawMutex};
 ///
 /// In all cases, the blocking mutex is intended to be short lived and not held across await points.
 ///
+/// # Pinning
+///
+/// A `BlockingMutex` never moves the protected data after it has been
+/// constructed, except when the mutex itself is moved, or when the data is
+/// moved out of it by user code (such as [`BlockingMutex::into_inner()`], or
+/// by using [`core::mem::replace`] on the `&mut T` passed to a
+/// [`BlockingMutex::with_lock()`] closure). In particular, if the mutex is
+/// pinned (e.g. using [`BlockingMutex::pin_new()`]), the address of the data
+/// is stable for as long as the mutex exists. However, the data is *not*
+/// structurally pinned, since any `&BlockingMutex` may be used to obtain a
+/// `&mut T`. See [`BlockingMutex::get_pin_mut()`] for details.
+///
+/// # Unsized data
+///
+/// The protected data may be a dynamically sized type, such as a trait object
+/// or a slice. As the data is the last field of a `BlockingMutex`, a reference
+/// (or [`Box`]) to a mutex protecting a concrete type may be coerced to one
+/// protecting an unsized type, and the data may then be accessed using
+/// [`BlockingMutex::with_lock()`] or [`BlockingMutex::lock()`] as usual:
+///
+/// ```
+/// use mutex::{BlockingMutex, RawMutex};
+/// use std::io::{self, Write};
+///
+/// fn greet<R: RawMutex>(out: &BlockingMutex<R, dyn Write>) -> io::Result<()> {
+///     let mut guard = out.lock();
+///     guard.write_all(b"hi")
+/// }
+///
+/// fn greet_buf<R: RawMutex>(buf: &BlockingMutex<R, Vec<u8>>) -> io::Result<()> {
+///     // `&BlockingMutex<R, Vec<u8>>` coerces to `&BlockingMutex<R, dyn Write>`.
+///     greet(buf)
+/// }
+/// ```
+///
+/// [`Box`]: https://doc.rust-lang.org/stable/alloc/boxed/struct.Box.html
+///
+/// # `Send` and `Sync`
+///
+/// A `BlockingMutex<R, T>` is [`Send`] if both `R` and `T` are [`Send`], and
+/// it is [`Sync`] if `R` is [`Sync`] and `T` is [`Send`]. Note that `T` need
+/// not be [`Sync`], as the mutex only ever grants access to the data to a
+/// single context at a time. Whether the mutex may be shared between threads
+/// is therefore determined by the raw mutex implementation.
+///
+/// Type aliases for mutexes using each of the provided raw mutex
+/// implementations, such as [`CsMutex`] and [`LocalMutex`], are available in
+/// the corresponding [`raw_impls`] modules.
+///
 /// [`CriticalSectionRawMutex`]: crate::raw_impls::cs::CriticalSectionRawMutex
+/// [`CsMutex`]: crate::raw_impls::cs::CsMutex
+/// [`LocalMutex`]: crate::raw_impls::local::LocalMutex
 /// [`LocalRawMutex`]: crate::raw_impls::local::LocalRawMutex
 /// [`ThreadModeRawMutex`]:
 ///     crate::raw_impls::single_core_thread_mode::ThreadModeRawMutex
@@ -52,6 +158,16 @@ pub struct MutexGuard<'mutex, R: RawMutex, T: ?Sized> {
     _marker: PhantomData<R::GuardMarker>,
 }
 
+/// A handle to the data guarded by a [`BlockingMutex`], which is only valid
+/// within the closure passed to [`BlockingMutex::locked()`].
+///
+/// Unlike a [`MutexGuard`], this does not require the raw mutex to implement
+/// [`RawMutex`], and does not release the lock when it is dropped. Instead,
+/// the lock is released when the closure it was passed to returns.
+pub struct ScopedGuard<'a, T: ?Sized> {
+    data: &'a mut T,
+}
+
 unsafe impl<R: ScopedRawMutex + Send, T: ?Sized + Send> Send for BlockingMutex<R, T> {}
 unsafe impl<R: ScopedRawMutex + Sync, T: ?Sized + Send> Sync for BlockingMutex<R, T> {}
 
@@ -64,6 +180,70 @@ impl<R: ConstInit, T> BlockingMutex<R, T> {
             data: UnsafeCell::new(val),
         }
     }
+
+    /// Creates a new mutex in an unlocked state ready for use, with the
+    /// protected data returned by `f`.
+    ///
+    /// Since this function is always inlined, this gives the compiler the
+    /// opportunity to construct the data directly in the mutex, rather than
+    /// constructing it on the stack and then moving it. However, this is not
+    /// guaranteed; for large values, consider [`BlockingMutex::new_in_place()`]
+    /// instead.
+    #[inline(always)]
+    pub fn new_with(f: impl FnOnce() -> T) -> BlockingMutex<R, T> {
+        Self::new(f())
+    }
+
+    /// Creates a new mutex in an unlocked state ready for use, if `val` is
+    /// accepted by `validate`.
+    ///
+    /// This is useful when the protected data has invariants which must be
+    /// upheld, as it documents the invariant at the site where the mutex is
+    /// constructed. If `validate` returns an error, the mutex is not
+    /// constructed, and the error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mutex::{BlockingMutex, ConstInit};
+    ///
+    /// struct Range {
+    ///     start: u32,
+    ///     end: u32,
+    /// }
+    ///
+    /// fn new_range<R: ConstInit>(start: u32, end: u32) -> Result<BlockingMutex<R, Range>, &'static str> {
+    ///     BlockingMutex::try_new(Range { start, end }, |r| {
+    ///         if r.start <= r.end {
+    ///             Ok(())
+    ///         } else {
+    ///             Err("range start must not exceed its end")
+    ///         }
+    ///     })
+    /// }
+    /// ```
+    #[inline]
+    pub fn try_new<E>(
+        val: T,
+        validate: impl FnOnce(&T) -> Result<(), E>,
+    ) -> Result<BlockingMutex<R, T>, E> {
+        validate(&val)?;
+        Ok(Self::new(val))
+    }
+}
+
+impl<R: ConstInit, T> BlockingMutex<R, T> {
+    /// Creates a new, pinned and heap-allocated mutex in an unlocked state
+    /// ready for use.
+    ///
+    /// Since the mutex is pinned, the protected data will not be moved for as
+    /// long as the mutex exists, unless it is explicitly moved out of the
+    /// mutex by user code.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn pin_new(val: T) -> Pin<alloc::boxed::Box<BlockingMutex<R, T>>> {
+        alloc::boxed::Box::pin(Self::new(val))
+    }
 }
 
 impl<R: ScopedRawMutex, T: ?Sized> BlockingMutex<R, T> {
@@ -72,6 +252,45 @@ impl<R: ScopedRawMutex, T: ?Sized> BlockingMutex<R, T> {
     /// Behavior when the lock is already locked is dependent on the behavior
     /// of the Raw mutex. See [`ScopedRawMutex::with_lock()`]'s documentation for
     /// more details
+    ///
+    /// # Returning borrows
+    ///
+    /// The returned value may not borrow from the inner data, as the lock is
+    /// released when `f` returns. However, it *may* contain borrows of other
+    /// data captured by `f`, which outlive the lock. For example, guarded
+    /// bytes may be parsed into nodes allocated in a caller-provided arena,
+    /// without copying them after the lock is released:
+    ///
+    /// ```
+    /// use mutex::{BlockingMutex, ScopedRawMutex};
+    /// # struct Arena;
+    /// # impl Arena {
+    /// #     fn alloc_str(&self, s: &str) -> &str {
+    /// #         Box::leak(s.into())
+    /// #     }
+    /// # }
+    ///
+    /// /// A node which borrows from an arena, rather than from the mutex.
+    /// struct Node<'a> {
+    ///     words: Vec<&'a str>,
+    /// }
+    ///
+    /// fn parse<'a, R: ScopedRawMutex>(
+    ///     input: &BlockingMutex<R, Vec<u8>>,
+    ///     arena: &'a Arena,
+    /// ) -> Node<'a> {
+    ///     input.with_lock(|bytes| {
+    ///         let text = core::str::from_utf8(bytes).unwrap();
+    ///         let words = text
+    ///             .split_whitespace()
+    ///             .map(|word| arena.alloc_str(word))
+    ///             .collect();
+    ///         Node { words }
+    ///     })
+    /// }
+    /// ```
+    #[inline]
+    #[track_caller]
     pub fn with_lock<U>(&self, f: impl FnOnce(&mut T) -> U) -> U {
         self.raw.with_lock(|| {
             let ptr = self.data.get();
@@ -81,11 +300,190 @@ impl<R: ScopedRawMutex, T: ?Sized> BlockingMutex<R, T> {
         })
     }
 
+    /// Locks the raw mutex and grants temporary access to the inner data,
+    /// panicking if the lock was held for longer than `budget`.
+    ///
+    /// The time spent in `f` is measured using `clock`. If it exceeds
+    /// `budget`, this method panics after the lock has been released. This
+    /// can be used to enforce at runtime that critical sections are short
+    /// lived, e.g. on systems where a watchdog or fault handler should be
+    /// tripped if a critical section overruns.
+    ///
+    /// Behavior when the lock is already locked is dependent on the behavior
+    /// of the Raw mutex. See [`ScopedRawMutex::with_lock()`]'s documentation for
+    /// more details
+    #[track_caller]
+    pub fn with_lock_budget<U>(
+        &self,
+        budget: Duration,
+        clock: &impl Clock,
+        f: impl FnOnce(&mut T) -> U,
+    ) -> U {
+        let (ret, elapsed) = self.with_lock(|inner| {
+            let start = clock.now();
+            let ret = f(inner);
+            (ret, clock.now().saturating_sub(start))
+        });
+        assert!(
+            elapsed <= budget,
+            "critical section took {elapsed:?}, exceeding its budget of {budget:?}",
+        );
+        ret
+    }
+
+    /// Locks the raw mutex and grants temporary access to the inner data, as
+    /// well as to a caller-provided output location.
+    ///
+    /// This is useful when the closure produces a large result: rather than
+    /// returning it by value (and moving it out of the critical section), the
+    /// result can be written directly into `out` while the lock is held.
+    ///
+    /// Behavior when the lock is already locked is dependent on the behavior
+    /// of the Raw mutex. See [`ScopedRawMutex::with_lock()`]'s documentation for
+    /// more details
+    pub fn with_lock_into<O: ?Sized>(&self, out: &mut O, f: impl FnOnce(&mut T, &mut O)) {
+        self.with_lock(|inner| f(inner, out))
+    }
+
+    /// Locks the raw mutex, calls `peek` with a shared reference to the inner
+    /// data and, only if it returns `true`, calls `commit` with a mutable
+    /// reference to the inner data.
+    ///
+    /// Returns `Some` with the return value of `commit`, or `None` if `peek`
+    /// returned `false`. Both closures run within a single acquisition of the
+    /// lock, so the data cannot be modified by another context between the
+    /// decision made by `peek` and the write made by `commit`.
+    ///
+    /// Behavior when the lock is already locked is dependent on the behavior
+    /// of the Raw mutex. See [`ScopedRawMutex::with_lock()`]'s documentation for
+    /// more details
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mutex::{raw_impls::cs::CriticalSectionRawMutex, BlockingMutex};
+    ///
+    /// let credits = BlockingMutex::<CriticalSectionRawMutex, u32>::new(3);
+    ///
+    /// // Spend a credit, if there are any left.
+    /// let spend = || credits.with_lock_peek_then(|&c| c > 0, |c| *c -= 1);
+    /// assert_eq!(spend(), Some(()));
+    /// assert_eq!(spend(), Some(()));
+    /// assert_eq!(spend(), Some(()));
+    /// assert_eq!(spend(), None);
+    /// ```
+    #[track_caller]
+    pub fn with_lock_peek_then<U>(
+        &self,
+        peek: impl FnOnce(&T) -> bool,
+        commit: impl FnOnce(&mut T) -> U,
+    ) -> Option<U> {
+        self.with_lock(|inner| peek(inner).then(|| commit(inner)))
+    }
+
+    /// Locks the raw mutex and passes a [`ScopedGuard`] for the inner data to
+    /// `f`.
+    ///
+    /// This is similar to [`BlockingMutex::with_lock()`], but provides a
+    /// named handle which dereferences to the inner data, and which may be
+    /// passed by `&mut` reference to helper functions called within `f`. This
+    /// provides most of the ergonomics of a [`MutexGuard`] for raw mutexes
+    /// which only implement [`ScopedRawMutex`], while ensuring that the
+    /// handle cannot outlive the closure.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mutex::{BlockingMutex, ScopedGuard, ScopedRawMutex};
+    ///
+    /// fn push_twice(v: &mut ScopedGuard<'_, Vec<u32>>, x: u32) {
+    ///     v.push(x);
+    ///     v.push(x);
+    /// }
+    ///
+    /// fn fill<R: ScopedRawMutex>(mutex: &BlockingMutex<R, Vec<u32>>) -> usize {
+    ///     mutex.locked(|v| {
+    ///         push_twice(v, 1);
+    ///         push_twice(v, 2);
+    ///         v.len()
+    ///     })
+    /// }
+    /// ```
+    ///
+    /// Behavior when the lock is already locked is dependent on the behavior
+    /// of the Raw mutex. See [`ScopedRawMutex::with_lock()`]'s documentation for
+    /// more details
+    #[inline]
+    #[track_caller]
+    pub fn locked<U>(&self, f: impl FnOnce(&mut ScopedGuard<'_, T>) -> U) -> U {
+        self.with_lock(|data| f(&mut ScopedGuard { data }))
+    }
+
+    /// Locks the raw mutex and runs a future which borrows the inner data to
+    /// completion, *without* suspending.
+    ///
+    /// This allows calling an `async fn` which never actually awaits anything
+    /// (e.g. one which is only `async` so that its signature matches other
+    /// functions) with the lock held. `f` is an async closure (or an `async
+    /// fn`), so that the future it returns may borrow the inner data. The
+    /// future is polled exactly once, with a no-op [`Waker`](core::task::Waker).
+    /// As a waker is only used to resume a future which has suspended, a
+    /// future which completes on its first poll never observes that the waker
+    /// does nothing.
+    ///
+    /// This method is only available when the `block-on` feature is enabled,
+    /// as it requires Rust 1.85 or later.
+    ///
+    /// # Panics
+    ///
+    /// A lock must never be held across an `.await` point which actually
+    /// suspends. If the future returns [`Poll::Pending`], it is dropped, the
+    /// lock is released, and this method panics with the message "future
+    /// suspended while holding lock".
+    ///
+    /// Behavior when the lock is already locked is dependent on the behavior
+    /// of the Raw mutex. See [`ScopedRawMutex::with_lock()`]'s documentation for
+    /// more details
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mutex::{BlockingMutex, ScopedRawMutex};
+    ///
+    /// async fn increment(counter: &mut u32) -> u32 {
+    ///     *counter += 1;
+    ///     *counter
+    /// }
+    ///
+    /// fn next_id<R: ScopedRawMutex>(counter: &BlockingMutex<R, u32>) -> u32 {
+    ///     counter.with_lock_block_on(increment)
+    /// }
+    /// ```
+    ///
+    /// [`Poll::Pending`]: core::task::Poll::Pending
+    #[cfg(feature = "block-on")]
+    #[track_caller]
+    pub fn with_lock_block_on<U>(&self, f: impl AsyncFnOnce(&mut T) -> U) -> U {
+        use core::future::Future;
+        use core::task::{Context, Poll, Waker};
+
+        let poll = self.with_lock(|inner| {
+            let fut = core::pin::pin!(f(inner));
+            fut.poll(&mut Context::from_waker(Waker::noop()))
+        });
+        match poll {
+            Poll::Ready(ret) => ret,
+            Poll::Pending => panic!("future suspended while holding lock"),
+        }
+    }
+
     /// Locks the raw mutex and grants temporary access to the inner data
     ///
     /// Returns `Some(U)` if the lock was obtained. Returns `None` if the lock
     /// was already locked
     #[must_use]
+    #[inline]
+    #[track_caller]
     pub fn try_with_lock<U>(&self, f: impl FnOnce(&mut T) -> U) -> Option<U> {
         self.raw.try_with_lock(|| {
             let ptr = self.data.get();
@@ -94,6 +492,106 @@ impl<R: ScopedRawMutex, T: ?Sized> BlockingMutex<R, T> {
             f(inner)
         })
     }
+
+    /// Attempts to lock the raw mutex and grant temporary access to the inner
+    /// data, returning the closure if the lock could not be obtained.
+    ///
+    /// Returns `Ok(U)` if the lock was obtained. Returns `Err(f)` if the lock
+    /// was already locked, in which case `f` has not been called. This allows
+    /// retrying with the same closure, even if it captures state which cannot
+    /// be cheaply reconstructed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mutex::{BlockingMutex, ScopedRawMutex};
+    ///
+    /// fn push_or_discard<R: ScopedRawMutex>(mutex: &BlockingMutex<R, Vec<String>>, s: String) {
+    ///     let mut push = move |v: &mut Vec<String>| v.push(s);
+    ///     for _ in 0..3 {
+    ///         match mutex.try_with_lock_keep(push) {
+    ///             Ok(()) => return,
+    ///             Err(f) => push = f,
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn try_with_lock_keep<U, F>(&self, f: F) -> Result<U, F>
+    where
+        F: FnOnce(&mut T) -> U,
+    {
+        let mut f = Some(f);
+        let res = self.try_with_lock(|inner| {
+            // `f` is only taken here, and this closure is called at most once.
+            let f = f.take().expect("closure is only called once");
+            f(inner)
+        });
+        match (res, f) {
+            (Some(ret), _) => Ok(ret),
+            // If the lock was not obtained, the closure was not called, so `f`
+            // is still present.
+            (None, Some(f)) => Err(f),
+            (None, None) => unreachable!("closure was called, but no value was returned"),
+        }
+    }
+
+    /// Repeatedly attempts to lock the raw mutex until either the lock is
+    /// obtained, or `max` has elapsed according to `clock`, and grants
+    /// temporary access to the inner data if the lock was obtained.
+    ///
+    /// Returns `Some(U)` if the lock was obtained. Returns `None` if the lock
+    /// was still locked after `max` elapsed.
+    ///
+    /// **Note**: this method busy-waits while the lock is held elsewhere, and
+    /// is therefore only appropriate for very short timeouts. Additionally,
+    /// for implementations where the lock cannot be released while another
+    /// context is spinning on it (such as a [`LocalRawMutex`] or a
+    /// [`CriticalSectionRawMutex`] on a single-core system), this will
+    /// always wait for the entire timeout.
+    ///
+    /// [`CriticalSectionRawMutex`]: crate::raw_impls::cs::CriticalSectionRawMutex
+    /// [`LocalRawMutex`]: crate::raw_impls::local::LocalRawMutex
+    #[must_use]
+    pub fn try_with_lock_spin_for<U>(
+        &self,
+        max: Duration,
+        clock: &impl Clock,
+        f: impl FnOnce(&mut T) -> U,
+    ) -> Option<U> {
+        let start = clock.now();
+        let mut f = f;
+        loop {
+            match self.try_with_lock_keep(f) {
+                Ok(ret) => return Some(ret),
+                Err(unused) => f = unused,
+            }
+
+            if clock.now().saturating_sub(start) >= max {
+                return None;
+            }
+
+            core::hint::spin_loop();
+        }
+    }
+}
+
+impl<R: DetectsDeadlock, T: ?Sized> BlockingMutex<R, T> {
+    /// Locks the raw mutex and grants temporary access to the inner data,
+    /// returning an error rather than panicking if the lock is already
+    /// locked.
+    ///
+    /// This method is only available for raw mutexes which implement
+    /// [`DetectsDeadlock`], for which a lock that is already locked can
+    /// *never* be acquired by the current context. For such mutexes,
+    /// [`BlockingMutex::with_lock()`] panics in this case, while this method
+    /// returns `Err(Deadlocked)`, allowing code which must not panic to
+    /// recover. Raw mutexes which instead block until the lock is released do
+    /// not implement [`DetectsDeadlock`]; [`BlockingMutex::with_lock()`]
+    /// cannot fail in this way for those mutexes.
+    #[inline]
+    pub fn checked_with_lock<U>(&self, f: impl FnOnce(&mut T) -> U) -> Result<U, Deadlocked> {
+        self.try_with_lock(f).ok_or(Deadlocked(()))
+    }
 }
 
 impl<R: RawMutex, T: ?Sized> BlockingMutex<R, T> {
@@ -155,6 +653,91 @@ impl<R, T> BlockingMutex<R, T> {
         }
     }
 
+    /// Initializes a new mutex in place, in the provided uninitialized `slot`,
+    /// returning a reference to the initialized mutex.
+    ///
+    /// The raw mutex is moved into the slot, and then `init` is called with a
+    /// pointer to the (uninitialized) storage for the protected data, so that
+    /// the data may be written directly to its final location. This avoids
+    /// constructing large values on the stack and then moving them into the
+    /// mutex.
+    ///
+    /// # Safety
+    ///
+    /// When `init` returns, the pointer passed to it must point to a fully
+    /// initialized, valid value of type `T`. `init` must not read from the
+    /// pointer before it has been initialized, and must not retain the pointer
+    /// after it returns.
+    ///
+    /// If `init` panics, the slot is left uninitialized, and the raw mutex
+    /// passed to this function is leaked.
+    pub unsafe fn new_in_place(
+        slot: &mut MaybeUninit<Self>,
+        raw_mutex: R,
+        init: impl FnOnce(*mut T),
+    ) -> &mut Self {
+        unsafe {
+            // SAFETY: a `&mut MaybeUninit<Self>` is valid for writes, properly
+            // aligned, and not aliased. The caller upholds the requirements
+            // on `init`.
+            Self::init_in_place(slot.as_mut_ptr(), raw_mutex, init);
+            // SAFETY: the mutex was initialized above.
+            slot.assume_init_mut()
+        }
+    }
+
+    /// Initializes a new mutex in place, at the location pointed to by
+    /// `place`.
+    ///
+    /// This is like [`BlockingMutex::new_in_place()`], but takes a raw
+    /// pointer, rather than a `&mut MaybeUninit<Self>`. This allows
+    /// initializing a mutex in storage which is managed manually, such as a
+    /// region reserved in a linker section or handed out by a bump allocator,
+    /// without first creating a reference to it.
+    ///
+    /// The raw mutex is written to its field, and then `init_data` is called
+    /// with a pointer to the (uninitialized) storage for the protected data,
+    /// so that the data may be written directly to its final location.
+    ///
+    /// # Safety
+    ///
+    /// - `place` must be non-null, and properly aligned for a
+    ///   `BlockingMutex<R, T>`.
+    /// - `place` must be valid for writes of `size_of::<BlockingMutex<R,
+    ///   T>>()` bytes, i.e. the entire range must lie within a single live
+    ///   allocation (or static).
+    /// - No other pointer or reference may be used to access any part of
+    ///   that range until this function returns. In particular, the range
+    ///   must not overlap with any data captured by `init_data`.
+    /// - When `init_data` returns, the pointer passed to it must point to a
+    ///   fully initialized, valid value of type `T`. `init_data` must not read
+    ///   from the pointer before it has been initialized, and must not retain
+    ///   the pointer after it returns.
+    ///
+    /// Any value previously stored at `place` is overwritten without being
+    /// dropped. When this function returns, `place` points to an initialized
+    /// `BlockingMutex`, which the caller is responsible for dropping (if
+    /// required).
+    ///
+    /// If `init_data` panics, the storage is left partially initialized, and
+    /// the raw mutex passed to this function is leaked.
+    pub unsafe fn init_in_place(place: *mut Self, raw: R, init_data: impl FnOnce(*mut T)) {
+        unsafe {
+            // SAFETY: the caller guarantees that `place` points to valid (but
+            // possibly uninitialized) storage for a `BlockingMutex`, so we may
+            // write to its fields without creating references to
+            // uninitialized memory.
+            ptr::addr_of_mut!((*place).raw).write(raw);
+            init_data(UnsafeCell::raw_get(ptr::addr_of_mut!((*place).data)));
+        }
+    }
+
+    /// Returns a reference to the underlying raw mutex.
+    #[inline]
+    pub fn raw(&self) -> &R {
+        &self.raw
+    }
+
     /// Consumes this mutex, returning the underlying data.
     #[inline]
     pub fn into_inner(self) -> T {
@@ -170,6 +753,27 @@ impl<R, T> BlockingMutex<R, T> {
         unsafe { &mut *self.data.get() }
     }
 
+    /// Returns a pinned mutable reference to the underlying data.
+    ///
+    /// Since this call borrows the `Mutex` mutably, no actual locking needs to
+    /// take place---the mutable borrow statically guarantees no locks exist.
+    ///
+    /// # Safety
+    ///
+    /// The protected data is not structurally pinned by a pinned
+    /// `BlockingMutex`, because a shared reference to the mutex may always be
+    /// used to obtain a `&mut T`, e.g. through [`BlockingMutex::with_lock()`].
+    ///
+    /// Unless `T` is [`Unpin`], the caller must ensure that, once this method
+    /// has been called, the data is never moved out of the `&mut T` provided
+    /// by any of this mutex's other methods, and that it is not moved out of
+    /// the mutex using [`BlockingMutex::into_inner()`].
+    #[inline]
+    pub unsafe fn get_pin_mut(self: Pin<&mut Self>) -> Pin<&mut T> {
+        // SAFETY: the caller guarantees that the data is treated as pinned.
+        unsafe { self.map_unchecked_mut(|this| this.get_mut()) }
+    }
+
     /// Returns a pointer to the inner storage
     ///
     /// # Safety
@@ -180,6 +784,220 @@ impl<R, T> BlockingMutex<R, T> {
     }
 }
 
+impl<R: ScopedRawMutex, T: PartialEq> BlockingMutex<R, T> {
+    /// Locks the raw mutex and, if the inner data is equal to `expected`,
+    /// replaces it with `new`.
+    ///
+    /// Returns `Ok(())` if the data was replaced. Returns `Err(new)` if the
+    /// data was not equal to `expected`, in which case the data is unchanged.
+    ///
+    /// The previous value is dropped after the lock has been released.
+    ///
+    /// Behavior when the lock is already locked is dependent on the behavior
+    /// of the Raw mutex. See [`ScopedRawMutex::with_lock()`]'s documentation for
+    /// more details
+    pub fn compare_and_set(&self, expected: &T, new: T) -> Result<(), T> {
+        self.with_lock(|data| Self::compare_and_replace(data, expected, new))
+            .map(drop)
+    }
+
+    /// Attempts to lock the raw mutex and, if the inner data is equal to
+    /// `expected`, replaces it with `new`.
+    ///
+    /// Returns `None` if the lock was already locked. Otherwise, returns the
+    /// result of the comparison, as in [`BlockingMutex::compare_and_set()`].
+    #[must_use]
+    pub fn try_compare_and_set(&self, expected: &T, new: T) -> Option<Result<(), T>> {
+        let res = self.try_with_lock(|data| Self::compare_and_replace(data, expected, new))?;
+        Some(res.map(drop))
+    }
+
+    #[inline]
+    fn compare_and_replace(data: &mut T, expected: &T, new: T) -> Result<T, T> {
+        if data == expected {
+            Ok(core::mem::replace(data, new))
+        } else {
+            Err(new)
+        }
+    }
+}
+
+impl<R: ScopedRawMutex, T, const N: usize> BlockingMutex<R, [T; N]> {
+    /// Locks the raw mutex and grants temporary access to two distinct
+    /// elements of the inner array, at indices `i` and `j`.
+    ///
+    /// The first argument passed to `f` is the element at index `i`, and the
+    /// second is the element at index `j`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i` or `j` is out of bounds, or if `i == j`. These checks are
+    /// performed before the lock is acquired.
+    ///
+    /// Behavior when the lock is already locked is dependent on the behavior
+    /// of the Raw mutex. See [`ScopedRawMutex::with_lock()`]'s documentation for
+    /// more details
+    #[track_caller]
+    pub fn with_two_mut<U>(&self, i: usize, j: usize, f: impl FnOnce(&mut T, &mut T) -> U) -> U {
+        assert!(i < N, "index `i` ({i}) out of bounds for length {N}");
+        assert!(j < N, "index `j` ({j}) out of bounds for length {N}");
+        assert_ne!(i, j, "indices passed to `with_two_mut` must be distinct");
+        self.with_lock(|arr| {
+            if i < j {
+                let (head, tail) = arr.split_at_mut(j);
+                f(&mut head[i], &mut tail[0])
+            } else {
+                let (head, tail) = arr.split_at_mut(i);
+                f(&mut tail[0], &mut head[j])
+            }
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: ScopedRawMutex, T: Send> BlockingMutex<R, [T]> {
+    /// Locks the raw mutex, splits the inner slice into `chunks` disjoint
+    /// sub-slices, and calls `f` on each sub-slice in parallel, on its own
+    /// scoped thread.
+    ///
+    /// The slice is split using [`chunks_mut`], with each chunk containing
+    /// `len.div_ceil(chunks)` elements, except (possibly) for the last. There
+    /// may therefore be fewer than `chunks` chunks, and if the slice is empty,
+    /// `f` is not called. The return values of `f` are returned in the order of
+    /// the chunks they were called with.
+    ///
+    /// The lock is held for the entire parallel region: all threads have
+    /// finished before the lock is released. If any call to `f` panics, the
+    /// panic is propagated after all threads have finished, and the lock is
+    /// released.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunks` is zero. This check is performed before the lock is
+    /// acquired.
+    ///
+    /// Behavior when the lock is already locked is dependent on the behavior
+    /// of the Raw mutex. See [`ScopedRawMutex::with_lock()`]'s documentation for
+    /// more details
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mutex::{raw_impls::std_sync::StdRawMutex, BlockingMutex};
+    ///
+    /// let data: Box<BlockingMutex<StdRawMutex, [u64]>> =
+    ///     Box::new(BlockingMutex::new([1, 2, 3, 4, 5, 6, 7]));
+    ///
+    /// let sums = data.with_lock_scoped_chunks(3, |chunk| {
+    ///     chunk.iter_mut().for_each(|x| *x *= 10);
+    ///     chunk.iter().sum::<u64>()
+    /// });
+    /// // 7 elements in 3 chunks gives chunks of length 3, 3, and 1.
+    /// assert_eq!(sums, [60, 150, 70]);
+    /// data.with_lock(|data| assert_eq!(data, [10, 20, 30, 40, 50, 60, 70]));
+    /// ```
+    ///
+    /// [`chunks_mut`]: slice::chunks_mut
+    #[track_caller]
+    pub fn with_lock_scoped_chunks<U: Send>(
+        &self,
+        chunks: usize,
+        f: impl Fn(&mut [T]) -> U + Sync,
+    ) -> Vec<U> {
+        assert_ne!(chunks, 0, "`chunks` must be non-zero");
+        self.with_lock(|data| {
+            let chunk_len = data.len().div_ceil(chunks).max(1);
+            let f = &f;
+            std::thread::scope(|scope| {
+                let threads: Vec<_> = data
+                    .chunks_mut(chunk_len)
+                    .map(|chunk| scope.spawn(move || f(chunk)))
+                    .collect();
+                threads
+                    .into_iter()
+                    .map(|thread| {
+                        thread
+                            .join()
+                            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                    })
+                    .collect()
+            })
+        })
+    }
+}
+
+impl<R: ScopedRawMutex, T> BlockingMutex<R, MaybeUninit<T>> {
+    /// Locks the raw mutex and writes `val` to the inner data.
+    ///
+    /// Any value previously written to the data is overwritten without being
+    /// dropped. The caller is responsible for tracking whether the data has
+    /// been initialized; see [`UninitMutex`] for a mutex which does so.
+    ///
+    /// [`UninitMutex`]: crate::uninit::UninitMutex
+    pub fn write(&self, val: T) {
+        self.with_lock(|data| {
+            data.write(val);
+        })
+    }
+}
+
+impl<R, T> BlockingMutex<R, MaybeUninit<T>> {
+    /// Converts this mutex into a mutex of the initialized data.
+    ///
+    /// # Safety
+    ///
+    /// The inner data must have been initialized, e.g. using
+    /// [`BlockingMutex::write()`]. See [`MaybeUninit::assume_init`].
+    #[inline]
+    pub unsafe fn assume_init(self) -> BlockingMutex<R, T> {
+        BlockingMutex {
+            raw: self.raw,
+            // SAFETY: the caller guarantees that the data is initialized.
+            data: UnsafeCell::new(unsafe { self.data.into_inner().assume_init() }),
+        }
+    }
+}
+
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+impl<R: ScopedRawMutex, T> BlockingMutex<R, alloc::sync::Arc<T>> {
+    /// Returns a clone of the current [`Arc`](alloc::sync::Arc).
+    ///
+    /// The lock is only held for long enough to clone the `Arc`, and the
+    /// returned value remains valid even if a new value is published using
+    /// [`BlockingMutex::rcu()`].
+    pub fn load(&self) -> alloc::sync::Arc<T> {
+        self.with_lock(|arc| arc.clone())
+    }
+
+    /// Performs a read-copy-update of the protected value.
+    ///
+    /// This loads the current value, calls `f` to build a new value from it,
+    /// and publishes the new value, returning the previous one. Readers that
+    /// have already loaded the previous value using [`BlockingMutex::load()`]
+    /// may continue to use it.
+    ///
+    /// The lock is *not* held while `f` runs. If another writer published a
+    /// new value in the meantime, `f` is called again with the new value, so
+    /// that no updates are lost.
+    pub fn rcu(&self, mut f: impl FnMut(&T) -> T) -> alloc::sync::Arc<T> {
+        let mut current = self.load();
+        loop {
+            let new = alloc::sync::Arc::new(f(&current));
+            let swapped = self.with_lock(|arc| {
+                if alloc::sync::Arc::ptr_eq(arc, &current) {
+                    Ok(core::mem::replace(arc, new))
+                } else {
+                    Err(arc.clone())
+                }
+            });
+            match swapped {
+                Ok(prev) => return prev,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
 #[cfg(feature = "fmt")]
 impl<R, T> core::fmt::Debug for BlockingMutex<R, T>
 where
@@ -190,13 +1008,131 @@ where
         let mut s = f.debug_struct("BlockingMutex");
         s.field("raw", &self.raw);
 
+        // NOTE: check whether the mutex is locked using an `Acquire` load
+        // first, so that formatting a mutex which is held elsewhere (e.g. on
+        // another core) does not contend on its lock state.
+        if self.raw.is_locked_acquire() {
+            return s.field("data", &format_args!("<locked>")).finish();
+        }
         self.try_with_lock(|data| s.field("data", &data).finish())
             .unwrap_or_else(|| s.field("data", &format_args!("<locked>")).finish())
     }
 }
 
+/// Attempts to lock each of the provided mutexes in order, and grants
+/// temporary access to the inner data of the first one which is not already
+/// locked.
+///
+/// `f` is called with the index of the acquired mutex in `locks`, and a
+/// mutable reference to its data. Returns `Some(U)` if any of the mutexes
+/// could be locked, or `None` if all of them were already locked.
+///
+/// This method never blocks, as it uses [`BlockingMutex::try_with_lock()`].
+#[must_use]
+pub fn try_any<R, T, U>(
+    locks: &[&BlockingMutex<R, T>],
+    f: impl FnOnce(usize, &mut T) -> U,
+) -> Option<U>
+where
+    R: ScopedRawMutex,
+    T: ?Sized,
+{
+    let mut f = Some(f);
+    locks.iter().enumerate().find_map(|(i, lock)| {
+        // `f` is only taken if the lock was obtained, after which we stop
+        // iterating, so it is always present here.
+        lock.try_with_lock(|inner| f.take().map(|f| f(i, inner)))
+            .flatten()
+    })
+}
+
+/// A [`ScopedRawMutex`] which detects deadlock, rather than blocking, when it
+/// is locked while already locked.
+///
+/// This is implemented by raw mutexes for which
+/// [`ScopedRawMutex::try_with_lock`] failing means that the lock can never be
+/// acquired by the current context (for example, because it is held by the
+/// same thread, or by code that has been preempted by the current interrupt).
+/// For such mutexes, [`ScopedRawMutex::with_lock`] panics (rather than
+/// blocking) in that case, and [`BlockingMutex::checked_with_lock()`] may be
+/// used to handle the deadlock as an error instead.
+pub trait DetectsDeadlock: ScopedRawMutex {}
+
+/// Error returned by [`BlockingMutex::checked_with_lock()`] when the mutex is
+/// already locked, and could therefore never be acquired.
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "fmt", derive(Debug))]
+pub struct Deadlocked(());
+
+#[cfg(feature = "fmt")]
+impl core::fmt::Display for Deadlocked {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("mutex is already locked, and can never be acquired")
+    }
+}
+
+/// A source of time, used by [`BlockingMutex::try_with_lock_spin_for()`] and
+/// [`BlockingMutex::with_lock_budget()`].
+pub trait Clock {
+    /// Returns the current time, as a [`Duration`] since an arbitrary, fixed
+    /// epoch.
+    ///
+    /// The returned value must never decrease.
+    fn now(&self) -> Duration;
+}
+
+/// A [`std::time::Instant`] may be used as a [`Clock`], returning the time
+/// elapsed since that instant.
+#[cfg(feature = "std")]
+impl Clock for std::time::Instant {
+    #[inline]
+    fn now(&self) -> Duration {
+        self.elapsed()
+    }
+}
+
+/// Formats the address of the protected data.
+///
+/// This does not lock the mutex.
+#[cfg(feature = "fmt")]
+impl<R, T: ?Sized> core::fmt::Pointer for BlockingMutex<R, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Pointer::fmt(&self.data.get(), f)
+    }
+}
+
 // === impl MutexGuard ===
 
+impl<'mutex, R: RawMutex, T: ?Sized> MutexGuard<'mutex, R, T> {
+    /// Unlocks the mutex, consuming the guard.
+    ///
+    /// This is equivalent to dropping the guard, but makes the point at which
+    /// the lock is released explicit in code. As the guard is consumed, the
+    /// mutex cannot be unlocked twice.
+    ///
+    /// This is an associated function, rather than a method, so that it does
+    /// not shadow any method named `unlock` on `T`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mutex::{BlockingMutex, MutexGuard, RawMutex};
+    ///
+    /// fn increment<R: RawMutex>(mutex: &BlockingMutex<R, u32>) {
+    ///     let mut guard = mutex.lock();
+    ///     *guard += 1;
+    ///     MutexGuard::unlock(guard);
+    ///
+    ///     // The lock is no longer held here.
+    ///     assert!(!RawMutex::is_locked(mutex.raw()));
+    /// }
+    /// ```
+    #[inline]
+    pub fn unlock(this: Self) {
+        drop(this);
+    }
+}
+
 impl<R: RawMutex, T: ?Sized> Drop for MutexGuard<'_, R, T> {
     fn drop(&mut self) {
         debug_assert!(
@@ -208,11 +1144,15 @@ impl<R: RawMutex, T: ?Sized> Drop for MutexGuard<'_, R, T> {
         unsafe {
             // SAFETY: a `MutexGuard` is only created when the lock has
             // been acquired, so we are allowed to unlock it.
-            self.lock.raw.unlock();
+            self.lock.raw.unlock_and_wake();
         }
     }
 }
 
+// NOTE: `debug_assert!` does not evaluate its condition when debug assertions
+// are disabled, so the `is_locked` checks in these impls do not add a load to
+// every dereference of a guard in release builds. This is checked by
+// `tests/guard_release.rs`.
 impl<R: RawMutex, T: ?Sized> Deref for MutexGuard<'_, R, T> {
     type Target = T;
 
@@ -282,7 +1222,7 @@ where
     R: RawMutex,
 {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        core::fmt::Debug::fmt(&self.data, f)
+        core::fmt::Debug::fmt(&**self, f)
     }
 }
 
@@ -293,6 +1233,56 @@ where
     R: RawMutex,
 {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        core::fmt::Display::fmt(&self.data, f)
+        core::fmt::Display::fmt(&**self, f)
+    }
+}
+
+/// Formats the address of the protected data.
+#[cfg(feature = "fmt")]
+impl<R, T> core::fmt::Pointer for MutexGuard<'_, R, T>
+where
+    T: ?Sized,
+    R: RawMutex,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Pointer::fmt(&self.lock.data.get(), f)
+    }
+}
+
+// === impl ScopedGuard ===
+
+impl<T: ?Sized> Deref for ScopedGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.data
+    }
+}
+
+impl<T: ?Sized> DerefMut for ScopedGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.data
+    }
+}
+
+#[cfg(feature = "fmt")]
+impl<T> core::fmt::Debug for ScopedGuard<'_, T>
+where
+    T: ?Sized + core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(feature = "fmt")]
+impl<T> core::fmt::Display for ScopedGuard<'_, T>
+where
+    T: ?Sized + core::fmt::Display,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Display::fmt(&**self, f)
     }
 }
@@ -0,0 +1,149 @@
+//! Mutexes which limit the size of the data they protect.
+//!
+//! This module provides [`MaxSizeMutex`], an opt-in guard-rail for keeping
+//! critical sections short. Code which copies data out of (or into) a
+//! critical section holds the lock for as long as the copy takes, so placing
+//! a large value behind a lock which disables interrupts, such as a
+//! [`CriticalSectionRawMutex`], is usually a mistake. A `MaxSizeMutex` turns
+//! the most obvious form of that mistake into a compile error.
+//!
+//! [`CriticalSectionRawMutex`]: crate::raw_impls::cs::CriticalSectionRawMutex
+
+use crate::{BlockingMutex, ConstInit, ScopedRawMutex};
+
+/// A [`BlockingMutex`] which fails to compile if the protected data is larger
+/// than `MAX` bytes.
+///
+/// Creating a `MaxSizeMutex<R, T, MAX>` for a `T` with
+/// [`size_of::<T>()`](core::mem::size_of) greater than `MAX` causes a
+/// compile-time error, reporting that the data is too large for the mutex.
+/// `MAX` is chosen by the user, so the limit is advisory: size is not a
+/// perfect proxy for how long a critical section takes, and a larger limit
+/// (or a plain [`BlockingMutex`]) may be chosen where a large value is
+/// deliberately protected by a lock.
+///
+/// Otherwise, a `MaxSizeMutex` behaves exactly like the [`BlockingMutex`] it
+/// wraps.
+///
+/// # Examples
+///
+/// ```
+/// use mutex::max_size::MaxSizeMutex;
+/// use mutex::raw_impls::cs::CriticalSectionRawMutex;
+///
+/// static STATE: MaxSizeMutex<CriticalSectionRawMutex, [u32; 4], 64> =
+///     MaxSizeMutex::new([0; 4]);
+///
+/// STATE.with_lock(|state| state[0] += 1);
+/// ```
+///
+/// Data which is larger than `MAX` bytes is rejected at compile time:
+///
+/// ```compile_fail,E0080
+/// use mutex::max_size::MaxSizeMutex;
+/// use mutex::raw_impls::cs::CriticalSectionRawMutex;
+///
+/// // error: data protected by a `MaxSizeMutex` is larger than its `MAX` size
+/// static BUFFER: MaxSizeMutex<CriticalSectionRawMutex, [u8; 4096], 64> =
+///     MaxSizeMutex::new([0; 4096]);
+/// ```
+pub struct MaxSizeMutex<R, T, const MAX: usize> {
+    inner: BlockingMutex<R, T>,
+}
+
+// === impl MaxSizeMutex ===
+
+impl<R, T, const MAX: usize> MaxSizeMutex<R, T, MAX> {
+    /// Evaluated whenever a `MaxSizeMutex` is created, so that creating one
+    /// with data which is too large fails to compile.
+    const SIZE_OK: () = assert!(
+        core::mem::size_of::<T>() <= MAX,
+        "data protected by a `MaxSizeMutex` is larger than its `MAX` size; \
+         protect a smaller value, or raise `MAX` if holding the lock while \
+         accessing this much data is intended",
+    );
+}
+
+impl<R: ConstInit, T, const MAX: usize> MaxSizeMutex<R, T, MAX> {
+    /// Creates a new mutex in an unlocked state ready for use.
+    ///
+    /// This fails to compile if `T` is larger than `MAX` bytes.
+    #[inline]
+    pub const fn new(val: T) -> Self {
+        let () = Self::SIZE_OK;
+        Self {
+            inner: BlockingMutex::new(val),
+        }
+    }
+}
+
+impl<R, T, const MAX: usize> MaxSizeMutex<R, T, MAX> {
+    /// Creates a new mutex based on a pre-existing raw mutex.
+    ///
+    /// This fails to compile if `T` is larger than `MAX` bytes.
+    #[inline]
+    pub const fn const_new(raw_mutex: R, val: T) -> Self {
+        let () = Self::SIZE_OK;
+        Self {
+            inner: BlockingMutex::const_new(raw_mutex, val),
+        }
+    }
+
+    /// Returns a reference to the underlying raw mutex.
+    #[inline]
+    pub fn raw(&self) -> &R {
+        self.inner.raw()
+    }
+
+    /// Consumes this mutex, returning the underlying data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+
+    /// Returns a pointer to the inner storage
+    ///
+    /// # Safety
+    ///
+    /// Must NOT be called when the lock is taken
+    pub unsafe fn get_unchecked(&self) -> *mut T {
+        self.inner.get_unchecked()
+    }
+}
+
+impl<R: ScopedRawMutex, T, const MAX: usize> MaxSizeMutex<R, T, MAX> {
+    /// Locks the raw mutex and grants temporary access to the inner data.
+    ///
+    /// Behavior when the lock is already locked is dependent on the behavior
+    /// of the Raw mutex. See [`ScopedRawMutex::with_lock()`]'s documentation for
+    /// more details
+    #[inline]
+    #[track_caller]
+    pub fn with_lock<U>(&self, f: impl FnOnce(&mut T) -> U) -> U {
+        self.inner.with_lock(f)
+    }
+
+    /// Locks the raw mutex and grants temporary access to the inner data.
+    ///
+    /// Returns `Some(U)` if the lock was obtained. Returns `None` if the lock
+    /// was already locked
+    #[must_use]
+    #[inline]
+    #[track_caller]
+    pub fn try_with_lock<U>(&self, f: impl FnOnce(&mut T) -> U) -> Option<U> {
+        self.inner.try_with_lock(f)
+    }
+}
+
+#[cfg(feature = "fmt")]
+impl<R, T, const MAX: usize> core::fmt::Debug for MaxSizeMutex<R, T, MAX>
+where
+    R: ScopedRawMutex + core::fmt::Debug,
+    T: core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("MaxSizeMutex")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
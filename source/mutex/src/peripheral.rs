@@ -0,0 +1,293 @@
+//! Mutexes for sharing peripherals between thread mode and interrupt handlers.
+//!
+//! This module provides [`PeripheralMutex`], which serves the common embedded
+//! pattern of obtaining a peripheral at startup (e.g. from
+//! [`cortex_m::Peripherals::take()`] or a HAL), and then sharing it between
+//! the main thread and interrupt handlers through a `static`.
+//!
+//! Without it, this pattern is usually written by hand as a
+//! `Mutex<RefCell<Option<P>>>`, with an `Option` so that the `static` can be
+//! created before the peripheral exists, and a `RefCell` to obtain a mutable
+//! reference once the mutex is locked. A `PeripheralMutex` replaces both.
+//!
+//! # Choosing a raw mutex
+//!
+//! - If the peripheral is used from interrupt handlers, use a raw mutex which
+//!   masks interrupts while it is held, such as [`CriticalSectionRawMutex`]
+//!   (see [`CsPeripheralMutex`]). Otherwise, an interrupt which fires while
+//!   the main thread holds the lock would deadlock.
+//! - If the peripheral is only shared between tasks running in thread mode,
+//!   on a single-core system, [`ThreadModeRawMutex`] avoids masking
+//!   interrupts (see [`ThreadModePeripheralMutex`]). Locking it from an
+//!   interrupt handler panics.
+//!
+//! # `Send` and `Sync`
+//!
+//! A `PeripheralMutex<R, P>` is [`Sync`], and may therefore be placed in a
+//! `static`, whenever `R` is [`Sync`] and `P` is [`Send`]. The peripheral
+//! itself need not be [`Sync`]: peripheral singletons such as
+//! [`cortex_m::peripheral::SYST`] are [`Send`], so that they may be moved to
+//! the context which uses them, but not [`Sync`], as their registers must not
+//! be accessed from two contexts at once. The mutex ensures that only a
+//! single context accesses the peripheral at a time.
+//!
+//! A type which is not [`Send`], such as one which is tied to the context in
+//! which it was created, cannot be shared using a `PeripheralMutex`:
+//!
+//! ```compile_fail
+//! use mutex::peripheral::CsPeripheralMutex;
+//!
+//! // Raw pointers are neither `Send` nor `Sync`.
+//! struct NotSend(*mut u32);
+//!
+//! static PERIPHERAL: CsPeripheralMutex<NotSend> = CsPeripheralMutex::new();
+//! ```
+//!
+//! [`cortex_m::Peripherals::take()`]:
+//!     https://docs.rs/cortex-m/0.7/cortex_m/peripheral/struct.Peripherals.html#method.take
+//! [`cortex_m::peripheral::SYST`]:
+//!     https://docs.rs/cortex-m/0.7/cortex_m/peripheral/struct.SYST.html
+//! [`CriticalSectionRawMutex`]: crate::raw_impls::cs::CriticalSectionRawMutex
+//! [`ThreadModeRawMutex`]:
+//!     crate::raw_impls::single_core_thread_mode::ThreadModeRawMutex
+
+use crate::{BlockingMutex, ConstInit, ScopedRawMutex};
+
+/// A [`BlockingMutex`] protecting a peripheral which is provided after the
+/// mutex is created.
+///
+/// The mutex is created empty, so that it may be placed in a `static`. Once
+/// the peripheral has been obtained, it is handed to the mutex using
+/// [`init`](Self::init), after which it may be accessed from any context
+/// using [`with_lock`](Self::with_lock). Alternatively,
+/// [`lend`](Self::lend) hands a peripheral to the mutex for the duration of a
+/// closure, and returns it afterwards.
+///
+/// See the [module-level documentation](self) for details on choosing a raw
+/// mutex, and on the [`Send`] and [`Sync`] requirements for the peripheral.
+///
+/// # Examples
+///
+/// Sharing a serial port between the main thread and its interrupt handler:
+///
+/// ```
+/// use mutex::peripheral::CsPeripheralMutex;
+///
+/// // A serial port driver, which would normally be provided by a HAL and
+/// // constructed from the device's `USART1` peripheral.
+/// struct Serial {
+///     # sent: Vec<u8>,
+///     // ...
+/// }
+///
+/// impl Serial {
+///     fn write(&mut self, byte: u8) {
+///         # self.sent.push(byte);
+///         // ...
+///     }
+/// }
+///
+/// static SERIAL: CsPeripheralMutex<Serial> = CsPeripheralMutex::new();
+///
+/// // #[interrupt]
+/// fn USART1() {
+///     // Does nothing if the interrupt fires before `main` has provided the
+///     // serial port.
+///     let _ = SERIAL.with_lock(|serial| serial.write(b'!'));
+/// }
+///
+/// // #[entry]
+/// fn main() {
+///     // let dp = pac::Peripherals::take().unwrap();
+///     // let serial = Serial::new(dp.USART1, ...);
+///     # let serial = Serial { sent: Vec::new() };
+///     if SERIAL.init(serial).is_err() {
+///         unreachable!("serial port is only initialized once");
+///     }
+///
+///     let written = SERIAL.with_lock(|serial| serial.write(b'?'));
+///     assert!(written.is_ok());
+///     # USART1();
+///     # assert_eq!(SERIAL.take().unwrap().sent, b"?!");
+/// }
+/// ```
+pub struct PeripheralMutex<R, P> {
+    inner: BlockingMutex<R, Option<P>>,
+}
+
+/// Error returned when accessing the peripheral in a [`PeripheralMutex`]
+/// which does not currently contain one.
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "fmt", derive(Debug))]
+pub struct NoPeripheral(());
+
+/// A [`PeripheralMutex`] backed by a [`CriticalSectionRawMutex`].
+///
+/// This is the appropriate choice for peripherals which are shared with
+/// interrupt handlers.
+///
+/// This type is [`Send`] and [`Sync`] whenever `P` is [`Send`].
+///
+/// [`CriticalSectionRawMutex`]: crate::raw_impls::cs::CriticalSectionRawMutex
+#[cfg(feature = "impl-critical-section")]
+pub type CsPeripheralMutex<P> = PeripheralMutex<crate::raw_impls::cs::CriticalSectionRawMutex, P>;
+
+/// A [`PeripheralMutex`] backed by a [`ThreadModeRawMutex`].
+///
+/// This is the appropriate choice for peripherals which are only shared
+/// between tasks running in thread mode on a single-core system. Accessing
+/// the peripheral from an interrupt handler panics.
+///
+/// This type is [`Send`] and [`Sync`] whenever `P` is [`Send`].
+///
+/// [`ThreadModeRawMutex`]:
+///     crate::raw_impls::single_core_thread_mode::ThreadModeRawMutex
+#[cfg(all(feature = "impl-unsafe-cortex-m-single-core", cortex_m))]
+pub type ThreadModePeripheralMutex<P> =
+    PeripheralMutex<crate::raw_impls::single_core_thread_mode::ThreadModeRawMutex, P>;
+
+// === impl PeripheralMutex ===
+
+impl<R: ConstInit, P> PeripheralMutex<R, P> {
+    /// Creates a new mutex, which does not yet contain a peripheral, in an
+    /// unlocked state.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            inner: BlockingMutex::new(None),
+        }
+    }
+}
+
+impl<R: ConstInit, P> Default for PeripheralMutex<R, P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R, P> PeripheralMutex<R, P> {
+    /// Creates a new mutex, which does not yet contain a peripheral, based on
+    /// a pre-existing raw mutex.
+    #[inline]
+    pub const fn const_new(raw_mutex: R) -> Self {
+        Self {
+            inner: BlockingMutex::const_new(raw_mutex, None),
+        }
+    }
+
+    /// Returns a reference to the underlying raw mutex.
+    #[inline]
+    pub fn raw(&self) -> &R {
+        self.inner.raw()
+    }
+
+    /// Consumes this mutex, returning the peripheral, if it contains one.
+    #[inline]
+    pub fn into_inner(self) -> Option<P> {
+        self.inner.into_inner()
+    }
+}
+
+impl<R: ScopedRawMutex, P> PeripheralMutex<R, P> {
+    /// Locks the raw mutex and stores `peripheral` in it.
+    ///
+    /// Returns `Err(peripheral)` if the mutex already contains a peripheral,
+    /// which is left in place.
+    pub fn init(&self, peripheral: P) -> Result<(), P> {
+        self.inner.with_lock(|slot| match slot {
+            Some(_) => Err(peripheral),
+            None => {
+                *slot = Some(peripheral);
+                Ok(())
+            }
+        })
+    }
+
+    /// Locks the raw mutex and removes the peripheral from it, returning
+    /// ownership of the peripheral to the caller.
+    ///
+    /// Returns `None` if the mutex does not contain a peripheral.
+    pub fn take(&self) -> Option<P> {
+        self.inner.with_lock(Option::take)
+    }
+
+    /// Stores `peripheral` in the mutex for the duration of `f`, and then
+    /// removes it again, returning it along with the result of `f`.
+    ///
+    /// While `f` runs, other contexts (such as interrupt handlers) may access
+    /// the peripheral using [`with_lock`](Self::with_lock). The lock is *not*
+    /// held while `f` runs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mutex already contains a peripheral, or if the
+    /// peripheral was removed from the mutex (e.g. using
+    /// [`take`](Self::take)) while `f` was running.
+    #[track_caller]
+    pub fn lend<U>(&self, peripheral: P, f: impl FnOnce() -> U) -> (P, U) {
+        if self.init(peripheral).is_err() {
+            panic!("`PeripheralMutex::lend` called while it already contains a peripheral");
+        }
+        let ret = f();
+        let peripheral = self
+            .take()
+            .expect("peripheral lent to a `PeripheralMutex` was taken while it was lent");
+        (peripheral, ret)
+    }
+
+    /// Locks the raw mutex and grants temporary access to the peripheral.
+    ///
+    /// Returns a [`NoPeripheral`] error without calling `f` if the mutex does
+    /// not contain a peripheral.
+    ///
+    /// Behavior when the lock is already locked is dependent on the behavior
+    /// of the Raw mutex. See [`ScopedRawMutex::with_lock()`]'s documentation for
+    /// more details
+    #[inline]
+    #[track_caller]
+    pub fn with_lock<U>(&self, f: impl FnOnce(&mut P) -> U) -> Result<U, NoPeripheral> {
+        self.inner
+            .with_lock(|slot| slot.as_mut().map(f))
+            .ok_or(NoPeripheral(()))
+    }
+
+    /// Locks the raw mutex and grants temporary access to the peripheral.
+    ///
+    /// Returns `None` if the lock was already locked. Returns
+    /// `Some(Err(NoPeripheral))` without calling `f` if the mutex does not
+    /// contain a peripheral.
+    #[must_use]
+    #[inline]
+    #[track_caller]
+    pub fn try_with_lock<U>(&self, f: impl FnOnce(&mut P) -> U) -> Option<Result<U, NoPeripheral>> {
+        self.inner
+            .try_with_lock(|slot| slot.as_mut().map(f).ok_or(NoPeripheral(())))
+    }
+
+    /// Locks the raw mutex and returns whether it contains a peripheral.
+    pub fn has_peripheral(&self) -> bool {
+        self.inner.with_lock(|slot| slot.is_some())
+    }
+}
+
+#[cfg(feature = "fmt")]
+impl<R, P> core::fmt::Debug for PeripheralMutex<R, P>
+where
+    R: ScopedRawMutex + core::fmt::Debug,
+    P: core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PeripheralMutex")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+// === impl NoPeripheral ===
+
+#[cfg(feature = "fmt")]
+impl core::fmt::Display for NoPeripheral {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("the mutex does not contain a peripheral")
+    }
+}
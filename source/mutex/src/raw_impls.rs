@@ -11,54 +11,261 @@
 use core::marker::PhantomData;
 use core::sync::atomic::{AtomicBool, Ordering};
 
-use mutex_traits::{ConstInit, ScopedRawMutex};
+use mutex_traits::{scoped::run_locked, ConstInit, ScopedRawMutex};
+
+use crate::{BlockingMutex, DetectsDeadlock};
+
+// Atomic types with read-modify-write operations, used by the implementations
+// in this module which require them. With the `portable-atomic` feature, these
+// are provided by `portable-atomic`, which can emulate them on targets without
+// native support for them, such as `thumbv6m`.
+#[cfg(all(not(feature = "portable-atomic"), target_has_atomic = "8"))]
+use core::sync::atomic as rmw;
+#[cfg(feature = "portable-atomic")]
+use portable_atomic as rmw;
+
+/// Counts the number of times a mutex has been locked, if the `test-util`
+/// feature is enabled. Otherwise, this is a zero-sized no-op.
+#[cfg_attr(feature = "fmt", derive(Debug))]
+struct LockCount {
+    #[cfg(feature = "test-util")]
+    count: core::sync::atomic::AtomicUsize,
+}
+
+impl LockCount {
+    const fn new() -> Self {
+        Self {
+            #[cfg(feature = "test-util")]
+            count: core::sync::atomic::AtomicUsize::new(0),
+        }
+    }
+
+    /// Increments the count. This must only be called while the mutex is
+    /// held.
+    #[inline(always)]
+    fn increment(&self) {
+        // NOTE: separated load/stores are acceptable as this is only called
+        // while the lock is held, and they work on targets without atomic
+        // read-modify-write operations.
+        #[cfg(feature = "test-util")]
+        self.count
+            .store(self.count.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+    }
+
+    #[cfg(feature = "test-util")]
+    fn get(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+}
+
+/// Called by the implementations in this module when a deadlock is detected.
+///
+/// By default, this panics with the provided message. If the `deadlock-abort`
+/// feature is enabled, this instead aborts immediately, without invoking the
+/// panic handler. If the `deadlock-no-panic` feature is enabled, this aborts
+/// on targets where `deadlock-abort` would, and spins forever on all other
+/// targets, so that this never panics. If the `deadlock-hang-in-release`
+/// feature is enabled and debug assertions are disabled, this spins forever,
+/// taking precedence over the other features.
+#[cold]
+#[inline(never)]
+#[track_caller]
+fn deadlocked(msg: &'static str) -> ! {
+    deadlocked_named(msg, None)
+}
+
+/// Like [`deadlocked`], but includes the name of the mutex in the panic
+/// message, if it has one.
+#[cold]
+#[inline(never)]
+#[track_caller]
+fn deadlocked_named(msg: &'static str, name: Option<&'static str>) -> ! {
+    #[cfg(all(feature = "deadlock-hang-in-release", not(debug_assertions)))]
+    {
+        let _ = (msg, name);
+        // Hang, so that the deadlock is caught by a watchdog, rather than
+        // unwinding through code which may have interrupts disabled.
+        loop {
+            core::hint::spin_loop();
+        }
+    }
+
+    #[cfg(not(all(feature = "deadlock-hang-in-release", not(debug_assertions))))]
+    {
+        #[cfg(all(
+            any(feature = "deadlock-abort", feature = "deadlock-no-panic"),
+            feature = "std"
+        ))]
+        {
+            let _ = (msg, name);
+            std::process::abort()
+        }
+
+        #[cfg(all(
+            any(feature = "deadlock-abort", feature = "deadlock-no-panic"),
+            not(feature = "std"),
+            cortex_m
+        ))]
+        {
+            let _ = (msg, name);
+            // SAFETY: `udf` raises a HardFault (or UsageFault), which does not
+            // return to this code.
+            unsafe { core::arch::asm!("udf #0", options(noreturn, nomem, nostack)) }
+        }
+
+        // On targets where we don't know how to abort without the panic
+        // handler, `deadlock-no-panic` hangs instead, so that no panic message
+        // is formatted.
+        #[cfg(all(feature = "deadlock-no-panic", not(feature = "std"), not(cortex_m)))]
+        {
+            let _ = (msg, name);
+            loop {
+                core::hint::spin_loop();
+            }
+        }
+
+        // Otherwise, fall back to panicking.
+        #[cfg(not(any(
+            all(feature = "deadlock-abort", any(feature = "std", cortex_m)),
+            feature = "deadlock-no-panic"
+        )))]
+        match name {
+            Some(name) => panic!("{msg} on mutex '{name}'"),
+            None => panic!("{msg}"),
+        }
+    }
+}
+
+/// Returns the `VECTACTIVE` field of the Cortex-M `ICSR` register, which is 0
+/// in thread mode, and is otherwise the exception number of the active
+/// exception.
+#[cfg(all(
+    cortex_m,
+    any(
+        feature = "impl-critical-section",
+        feature = "impl-unsafe-cortex-m-single-core"
+    )
+))]
+#[inline]
+fn vectactive() -> u32 {
+    // SAFETY: `ICSR` is a memory-mapped System Control Block register, which
+    // is present on every Cortex-M core. It is always valid for reads, and
+    // reading it has no side effects.
+    let icsr = unsafe { (0xE000ED04 as *const u32).read_volatile() };
+    icsr & 0x1FF
+}
+
+// Compile-time assertion helpers for the `Send`/`Sync` properties of the
+// mutex type aliases in this module.
+fn assert_send<T: Send>() {}
+#[allow(dead_code)] // unused if only non-`Sync` impls are enabled
+fn assert_sync<T: Sync>() {}
 
 #[cfg(feature = "impl-critical-section")]
 pub mod cs {
     //! Critical Section based implementation
 
     use super::*;
+    use crate::atomic::AtomicBoolLike;
 
     /// A mutex that allows borrowing data across executors and interrupts.
     ///
+    /// If the `mutex_unsafe_assume_single_threaded` cfg is set, this does not
+    /// enter a critical section when locking. See the crate's documentation
+    /// for details.
+    ///
+    /// The `A` type parameter selects the type of atomic used to track the
+    /// lock's state. By default, this is [`core::sync::atomic::AtomicBool`],
+    /// but any [`AtomicBoolLike`] type (such as `portable_atomic::AtomicBool`,
+    /// with the `portable-atomic` feature) may be used instead. Mutexes using
+    /// other atomic types are constructed using
+    /// [`with_atomic`](Self::with_atomic).
+    ///
+    /// A mutex may be given a name using [`new_named`](Self::new_named),
+    /// which is included in the panic message if a deadlock is detected.
+    ///
     /// # Safety
     ///
     /// This mutex is safe to share between different executors and interrupts.
     #[cfg_attr(feature = "fmt", derive(Debug))]
-    pub struct CriticalSectionRawMutex {
-        taken: AtomicBool,
+    pub struct CriticalSectionRawMutex<A = AtomicBool> {
+        taken: A,
+        name: Option<&'static str>,
+        count: LockCount,
     }
-    unsafe impl Send for CriticalSectionRawMutex {}
-    unsafe impl Sync for CriticalSectionRawMutex {}
+    unsafe impl<A: AtomicBoolLike> Send for CriticalSectionRawMutex<A> {}
+    unsafe impl<A: AtomicBoolLike> Sync for CriticalSectionRawMutex<A> {}
 
     impl CriticalSectionRawMutex {
         /// Create a new `CriticalSectionRawMutex`.
         pub const fn new() -> Self {
+            Self::with_atomic()
+        }
+
+        /// Create a new `CriticalSectionRawMutex` with the provided name.
+        ///
+        /// If a deadlock is detected when locking this mutex, the name is
+        /// included in the panic message (e.g. "Deadlocked on mutex
+        /// 'spi_bus'"), making it easier to identify which of many mutexes
+        /// deadlocked.
+        pub const fn new_named(name: &'static str) -> Self {
             Self {
                 taken: AtomicBool::new(false),
+                name: Some(name),
+                count: LockCount::new(),
             }
         }
     }
 
-    impl ConstInit for CriticalSectionRawMutex {
-        const INIT: Self = Self::new();
+    impl<A: AtomicBoolLike> CriticalSectionRawMutex<A> {
+        /// Create a new `CriticalSectionRawMutex` using the atomic type `A`.
+        pub const fn with_atomic() -> Self {
+            Self {
+                taken: A::FALSE,
+                name: None,
+                count: LockCount::new(),
+            }
+        }
+
+        /// Returns the name of this mutex, if it was created using
+        /// [`new_named`](CriticalSectionRawMutex::new_named).
+        pub fn name(&self) -> Option<&'static str> {
+            self.name
+        }
+
+        /// Returns the number of times this mutex has been successfully
+        /// locked.
+        #[cfg(feature = "test-util")]
+        pub fn lock_count(&self) -> usize {
+            self.count.get()
+        }
+    }
+
+    impl<A: AtomicBoolLike> ConstInit for CriticalSectionRawMutex<A> {
+        const INIT: Self = Self::with_atomic();
     }
 
-    unsafe impl ScopedRawMutex for CriticalSectionRawMutex {
+    /// A [`BlockingMutex`] backed by a [`CriticalSectionRawMutex`].
+    ///
+    /// This type is [`Send`] and [`Sync`] whenever `T` is [`Send`].
+    pub type CsMutex<T> = BlockingMutex<CriticalSectionRawMutex, T>;
+
+    const _: fn() = || {
+        assert_send::<CsMutex<u32>>();
+        assert_sync::<CsMutex<u32>>();
+    };
+
+    unsafe impl<A: AtomicBoolLike> ScopedRawMutex for CriticalSectionRawMutex<A> {
         #[inline]
-        #[must_use]
         fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
-            critical_section::with(|_| {
-                // NOTE: separated load/stores are acceptable as we are in
-                // a critical section
-                if self.taken.load(Ordering::Relaxed) {
-                    return None;
-                }
-                self.taken.store(true, Ordering::Relaxed);
-                let ret = f();
-                self.taken.store(false, Ordering::Relaxed);
-                Some(ret)
-            })
+            // If the whole program is single-threaded (and has no interrupt
+            // handlers), nothing can preempt us, so the critical section is
+            // unnecessary.
+            #[cfg(mutex_unsafe_assume_single_threaded)]
+            return self.try_with_lock_in_cs(f);
+
+            #[cfg(not(mutex_unsafe_assume_single_threaded))]
+            critical_section::with(|_| self.try_with_lock_in_cs(f))
         }
 
         #[inline]
@@ -66,206 +273,6717 @@ pub mod cs {
             // In a critical section, it is not possible for another holder
             // of this mutex to release, which means we have certainly
             // reached deadlock if the lock was already locked.
-            self.try_with_lock(f).expect("Deadlocked")
+            self.try_with_lock(f)
+                .unwrap_or_else(|| deadlocked_named("Deadlocked", self.name))
         }
 
         fn is_locked(&self) -> bool {
             self.taken.load(Ordering::Relaxed)
         }
+
+        fn is_locked_acquire(&self) -> bool {
+            self.taken.load(Ordering::Acquire)
+        }
+    }
+
+    impl<A: AtomicBoolLike> DetectsDeadlock for CriticalSectionRawMutex<A> {}
+
+    impl CriticalSectionRawMutex {
+        /// Enters a single critical section for the duration of `f`, passing
+        /// it a [`BatchToken`] which may be used to lock this mutex repeatedly
+        /// without entering a new critical section each time.
+        ///
+        /// This amortizes the cost of entering and exiting the critical
+        /// section across many short accesses, such as in a loop. Note that
+        /// the critical section is held for the *entire* duration of `f`,
+        /// rather than just while the mutex is locked.
+        #[inline]
+        pub fn with_batch<U>(&self, f: impl FnOnce(BatchToken<'_>) -> U) -> U {
+            critical_section::with(|_| {
+                f(BatchToken {
+                    mutex: self,
+                    _not_send: PhantomData,
+                })
+            })
+        }
+    }
+
+    impl<A: AtomicBoolLike> CriticalSectionRawMutex<A> {
+        /// Locks the mutex, calling `f()` while it is held, without entering
+        /// a critical section if `is_interrupt_free()` returns `true`.
+        ///
+        /// If `is_interrupt_free()` returns `true`, the mutex is locked using
+        /// plain loads and stores, as a [`LocalRawMutex`] would be, and
+        /// interrupts remain enabled while `f()` runs. Otherwise, this behaves
+        /// exactly like [`ScopedRawMutex::with_lock`]. This allows a caller
+        /// which can cheaply determine that it is not racing with any other
+        /// context (such as one which already has interrupts disabled) to
+        /// skip the cost of entering a critical section, while remaining
+        /// correct when called from any other context.
+        ///
+        /// # Safety
+        ///
+        /// `is_interrupt_free()` may only return `true` if no other execution
+        /// context can run concurrently with the current one until `f()`
+        /// returns. In particular, it must return `false` if there may be
+        /// other threads or CPU cores which lock this mutex. Predicates such
+        /// as "interrupts are currently disabled on a single-core system" are
+        /// correct; "the CPU is in thread mode" is only correct if no
+        /// interrupt handler which may preempt the current context locks
+        /// this mutex.
+        ///
+        /// If an interrupt handler which locks this mutex *does* preempt `f()`
+        /// on a single-core system, it will observe that the mutex is locked
+        /// and detect a deadlock, as it cannot wait for `f()` to release it.
+        ///
+        /// [`LocalRawMutex`]: super::local::LocalRawMutex
+        #[inline]
+        #[track_caller]
+        pub unsafe fn with_lock_prefer_local<R>(
+            &self,
+            is_interrupt_free: impl FnOnce() -> bool,
+            f: impl FnOnce() -> R,
+        ) -> R {
+            if is_interrupt_free() {
+                // NOTE: the caller guarantees that nothing can run
+                // concurrently with us, which is all that entering a critical
+                // section would ensure.
+                self.try_with_lock_in_cs(f)
+                    .unwrap_or_else(|| deadlocked_named("Deadlocked", self.name))
+            } else {
+                self.with_lock(f)
+            }
+        }
+
+        /// Attempts to lock the mutex, calling `f()` while it is held. This
+        /// must only be called while in a critical section.
+        #[inline]
+        pub(super) fn try_with_lock_in_cs<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            run_locked(
+                || {
+                    // NOTE: separated load/stores are acceptable as we are
+                    // in a critical section
+                    if self.taken.load(Ordering::Relaxed) {
+                        return false;
+                    }
+                    self.taken.store(true, Ordering::Relaxed);
+                    self.count.increment();
+                    true
+                },
+                // NOTE: `Release` pairs with the `Acquire` load in
+                // `is_locked_acquire`, which may be called outside of a
+                // critical section (e.g. from another core).
+                || self.taken.store(false, Ordering::Release),
+                f,
+            )
+        }
+    }
+
+    /// A token proving that a critical section is held, which may be used to
+    /// lock a [`CriticalSectionRawMutex`] without entering another critical
+    /// section.
+    ///
+    /// This is created by [`CriticalSectionRawMutex::with_batch`] or
+    /// [`BlockingMutex::with_batch`], and cannot outlive the critical section
+    /// it was created in.
+    #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct BatchToken<'cs> {
+        mutex: &'cs CriticalSectionRawMutex,
+        /// The critical section may not be released from another thread
+        _not_send: PhantomData<*mut ()>,
+    }
+
+    impl BatchToken<'_> {
+        /// Locks the mutex, calling `f()` while it is held.
+        ///
+        /// This behaves like [`ScopedRawMutex::with_lock`], but does not enter
+        /// a new critical section.
+        #[inline]
+        pub fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            self.try_with_lock(f)
+                .unwrap_or_else(|| deadlocked_named("Deadlocked", self.mutex.name))
+        }
+
+        /// Attempts to lock the mutex, calling `f()` while it is held.
+        ///
+        /// This behaves like [`ScopedRawMutex::try_with_lock`], but does not
+        /// enter a new critical section.
+        #[inline]
+        #[must_use]
+        pub fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            self.mutex.try_with_lock_in_cs(f)
+        }
+    }
+
+    /// A batch of accesses to the data protected by a [`CsMutex`], all within
+    /// a single critical section.
+    ///
+    /// This is created by [`BlockingMutex::with_batch`].
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct Batch<'cs, T: ?Sized> {
+        token: BatchToken<'cs>,
+        mutex: &'cs CsMutex<T>,
+    }
+
+    impl<T: ?Sized> Batch<'_, T> {
+        /// Locks the mutex and grants temporary access to the inner data,
+        /// without entering a new critical section.
+        #[inline]
+        pub fn with_lock<U>(&self, f: impl FnOnce(&mut T) -> U) -> U {
+            self.token.with_lock(|| {
+                // SAFETY: the raw mutex proves we have exclusive access to
+                // the inner data
+                f(unsafe { &mut *self.mutex.data.get() })
+            })
+        }
+
+        /// Attempts to lock the mutex and grant temporary access to the inner
+        /// data, without entering a new critical section.
+        ///
+        /// Returns `Some(U)` if the lock was obtained. Returns `None` if the
+        /// lock was already locked
+        #[inline]
+        #[must_use]
+        pub fn try_with_lock<U>(&self, f: impl FnOnce(&mut T) -> U) -> Option<U> {
+            self.token.try_with_lock(|| {
+                // SAFETY: the raw mutex proves we have exclusive access to
+                // the inner data
+                f(unsafe { &mut *self.mutex.data.get() })
+            })
+        }
+    }
+
+    impl<T: ?Sized> BlockingMutex<CriticalSectionRawMutex, T> {
+        /// Enters a single critical section for the duration of `f`, passing
+        /// it a [`Batch`] which may be used to lock this mutex repeatedly
+        /// without entering a new critical section each time.
+        ///
+        /// See [`CriticalSectionRawMutex::with_batch`] for details.
+        #[inline]
+        pub fn with_batch<U>(&self, f: impl FnOnce(Batch<'_, T>) -> U) -> U {
+            self.raw.with_batch(|token| f(Batch { token, mutex: self }))
+        }
+    }
+
+    impl<A: AtomicBoolLike, T: ?Sized> BlockingMutex<CriticalSectionRawMutex<A>, T> {
+        /// Locks the mutex and grants temporary access to the inner data,
+        /// without entering a critical section if `is_interrupt_free()`
+        /// returns `true`.
+        ///
+        /// See [`CriticalSectionRawMutex::with_lock_prefer_local`] for
+        /// details.
+        ///
+        /// # Safety
+        ///
+        /// `is_interrupt_free()` may only return `true` if no other execution
+        /// context can run concurrently with the current one until `f()`
+        /// returns. See [`CriticalSectionRawMutex::with_lock_prefer_local`]
+        /// for details.
+        #[inline]
+        #[track_caller]
+        pub unsafe fn with_lock_prefer_local<U>(
+            &self,
+            is_interrupt_free: impl FnOnce() -> bool,
+            f: impl FnOnce(&mut T) -> U,
+        ) -> U {
+            // SAFETY: the caller is responsible for upholding the safety
+            // requirements of `with_lock_prefer_local`.
+            unsafe {
+                self.raw.with_lock_prefer_local(is_interrupt_free, || {
+                    // SAFETY: the raw mutex proves we have exclusive access
+                    // to the inner data
+                    f(&mut *self.data.get())
+                })
+            }
+        }
     }
 }
 
 // ================
 
-pub mod local {
-    //! Locally usable based implementation
+#[cfg(feature = "impl-critical-section")]
+pub mod dma {
+    //! Critical Section based implementation with memory barriers suitable for
+    //! sharing buffers with DMA peripherals
+
+    use super::cs::CriticalSectionRawMutex;
     use super::*;
+    use core::sync::atomic::fence;
 
-    /// A mutex that allows borrowing data in local context.
+    /// A [`CriticalSectionRawMutex`] which issues memory barriers when the lock
+    /// is acquired and released, so that accesses to DMA buffers within the
+    /// critical section are coherent with a DMA peripheral.
     ///
-    /// This acts similar to a RefCell, with scoped access patterns, though
-    /// without being able to borrow the data twice.
+    /// A `CriticalSectionRawMutex` only orders memory accesses with respect to
+    /// other CPU contexts that take the same lock. A DMA peripheral does not
+    /// take the lock, so a buffer written inside the critical section may not
+    /// yet be visible to the peripheral when it is told to start a transfer
+    /// after the lock is released. This mutex issues the following barriers:
+    ///
+    /// - After acquiring the lock, and before calling the closure: an
+    ///   [`Acquire`] fence, followed by a `DMB` instruction on Cortex-M. This
+    ///   ensures that reads of the buffer within the closure observe any
+    ///   writes completed by the peripheral before the lock was acquired.
+    /// - After the closure returns (or panics), and before releasing the lock:
+    ///   a [`Release`] fence, preceded by a `DMB` instruction on Cortex-M.
+    ///   This ensures that writes to the buffer within the closure complete
+    ///   before any subsequent write (such as to a peripheral register which
+    ///   starts the transfer).
+    ///
+    /// The fences prevent the compiler from reordering accesses to the buffer
+    /// across the barrier. The `DMB` (data memory barrier, full system) is
+    /// required in addition because the peripheral is an observer outside of
+    /// the CPU's memory model. On other targets, only the fences are issued.
+    ///
+    /// Note that memory barriers do not perform cache maintenance. On systems
+    /// with a data cache, the buffer must additionally be cleaned or
+    /// invalidated (or placed in non-cacheable memory).
+    ///
+    /// [`Acquire`]: Ordering::Acquire
+    /// [`Release`]: Ordering::Release
     #[cfg_attr(feature = "fmt", derive(Debug))]
-    pub struct LocalRawMutex {
-        taken: AtomicBool,
-        /// Prevent this from being sync or send
-        _phantom: PhantomData<*mut ()>,
+    pub struct DmaCoherentRawMutex {
+        inner: CriticalSectionRawMutex,
     }
 
-    impl LocalRawMutex {
-        /// Create a new `LocalRawMutex`.
+    impl DmaCoherentRawMutex {
+        /// Create a new `DmaCoherentRawMutex`.
         pub const fn new() -> Self {
             Self {
-                taken: AtomicBool::new(false),
-                _phantom: PhantomData,
+                inner: CriticalSectionRawMutex::new(),
             }
         }
-    }
 
-    unsafe impl Send for LocalRawMutex {}
+        /// Returns the number of times this mutex has been successfully
+        /// locked.
+        #[cfg(feature = "test-util")]
+        pub fn lock_count(&self) -> usize {
+            self.inner.lock_count()
+        }
+    }
 
-    impl ConstInit for LocalRawMutex {
+    impl ConstInit for DmaCoherentRawMutex {
         const INIT: Self = Self::new();
     }
 
-    unsafe impl ScopedRawMutex for LocalRawMutex {
+    /// A [`BlockingMutex`] backed by a [`DmaCoherentRawMutex`].
+    pub type DmaMutex<T> = BlockingMutex<DmaCoherentRawMutex, T>;
+
+    const _: fn() = || {
+        assert_send::<DmaMutex<u32>>();
+        assert_sync::<DmaMutex<u32>>();
+    };
+
+    unsafe impl ScopedRawMutex for DmaCoherentRawMutex {
         #[inline]
-        #[must_use]
         fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
-            // NOTE: separated load/stores are acceptable as we are !Send and !Sync,
-            // meaning that we can only be accessed within a single thread
-            if self.taken.load(Ordering::Relaxed) {
-                return None;
-            }
-            self.taken.store(true, Ordering::Relaxed);
-            let ret = f();
-            self.taken.store(false, Ordering::Relaxed);
-            Some(ret)
+            self.inner.try_with_lock(|| {
+                fence(Ordering::Acquire);
+                dmb();
+                // Using a drop guard ensures that the release barrier is
+                // issued before the lock is released, even if `f()` panics.
+                let _barrier = ReleaseBarrier;
+                f()
+            })
         }
 
         #[inline]
         fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
-            // In a local-only mutex, it is not possible for another holder
+            // In a critical section, it is not possible for another holder
             // of this mutex to release, which means we have certainly
             // reached deadlock if the lock was already locked.
-            self.try_with_lock(f).expect("Deadlocked")
+            self.try_with_lock(f)
+                .unwrap_or_else(|| deadlocked("Deadlocked"))
         }
 
         fn is_locked(&self) -> bool {
-            self.taken.load(Ordering::Relaxed)
+            self.inner.is_locked()
+        }
+
+        fn is_locked_acquire(&self) -> bool {
+            self.inner.is_locked_acquire()
+        }
+    }
+
+    impl DetectsDeadlock for DmaCoherentRawMutex {}
+
+    /// Drop guard which issues the release barrier.
+    struct ReleaseBarrier;
+
+    impl Drop for ReleaseBarrier {
+        #[inline(always)]
+        fn drop(&mut self) {
+            dmb();
+            fence(Ordering::Release);
         }
     }
+
+    #[inline(always)]
+    fn dmb() {
+        #[cfg(cortex_m)]
+        // SAFETY: `dmb` has no effects other than ordering memory accesses.
+        unsafe {
+            core::arch::asm!("dmb sy", options(nostack, preserves_flags))
+        };
+    }
 }
 
 // ================
 
-#[cfg(all(feature = "impl-unsafe-cortex-m-single-core", cortex_m))]
-pub mod single_core_thread_mode {
-    //! A single-core safe implementation that does not require a critical section
+#[cfg(feature = "impl-critical-section")]
+pub mod pool {
+    //! Critical Section based implementation for guarding the free lists of
+    //! memory pools
+    //!
+    //! Fixed-size block pools, such as those provided by `heapless::pool`,
+    //! hand out blocks from a statically allocated region, and keep track of
+    //! unused blocks using a free list. Allocating or freeing a block only
+    //! requires pushing or popping a single entry of the free list, which is
+    //! a handful of instructions, and so is well suited to being protected by
+    //! a critical section.
+    //!
+    //! # Examples
+    //!
+    //! A pool of four blocks, whose free list is stored as an array of indices
+    //! of the next free block:
+    //!
+    //! ```
+    //! use mutex::BlockingMutex;
+    //! use mutex::raw_impls::pool::PoolRawMutex;
+    //!
+    //! const BLOCKS: usize = 4;
+    //!
+    //! struct FreeList {
+    //!     /// The index of the first free block, or `BLOCKS` if there is none.
+    //!     head: usize,
+    //!     /// For each free block, the index of the next free block.
+    //!     next: [usize; BLOCKS],
+    //! }
+    //!
+    //! static FREE_LIST: BlockingMutex<PoolRawMutex, FreeList> =
+    //!     BlockingMutex::new(FreeList { head: 0, next: [1, 2, 3, 4] });
+    //!
+    //! /// Returns the index of a free block, if there is one.
+    //! fn alloc() -> Option<usize> {
+    //!     FREE_LIST.with_lock(|list| {
+    //!         let block = list.head;
+    //!         if block == BLOCKS {
+    //!             return None;
+    //!         }
+    //!         list.head = list.next[block];
+    //!         Some(block)
+    //!     })
+    //! }
+    //!
+    //! /// Returns a block previously returned by `alloc` to the pool.
+    //! fn free(block: usize) {
+    //!     FREE_LIST.with_lock(|list| {
+    //!         list.next[block] = list.head;
+    //!         list.head = block;
+    //!     })
+    //! }
+    //! ```
 
+    use super::cs::CriticalSectionRawMutex;
     use super::*;
+    use core::time::Duration;
 
-    /// A "mutex" that only allows borrowing from thread mode.
-    ///
-    /// # Safety
-    ///
-    /// **This Mutex is only safe on single-core systems.**
+    /// A [`CriticalSectionRawMutex`] intended for guarding the free list of a
+    /// memory pool.
     ///
-    /// On multi-core systems, a `ThreadModeRawMutex` **is not sufficient** to ensure exclusive access.
+    /// As interrupts are disabled while a pool's free list is locked, the
+    /// closure passed to [`with_lock`](ScopedRawMutex::with_lock) should only
+    /// push or pop free list entries, and must not perform any other work
+    /// (such as initializing the contents of a block). To help enforce this,
+    /// a `PoolRawMutex` may be created with a time budget using
+    /// [`PoolRawMutex::with_budget`]. In builds with debug assertions enabled,
+    /// the time spent with the lock held is then measured, and a panic occurs
+    /// after the lock is released if it exceeded the budget. In builds with
+    /// debug assertions disabled, the budget is not checked, and this type
+    /// behaves exactly like a [`CriticalSectionRawMutex`].
     #[cfg_attr(feature = "fmt", derive(Debug))]
-    pub struct ThreadModeRawMutex {
-        taken: AtomicBool,
+    pub struct PoolRawMutex {
+        inner: CriticalSectionRawMutex,
+        #[cfg(debug_assertions)]
+        budget: Option<Budget>,
     }
 
-    unsafe impl Send for ThreadModeRawMutex {}
-    unsafe impl Sync for ThreadModeRawMutex {}
+    /// A time budget, and the function used to read the current time.
+    #[cfg(debug_assertions)]
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    struct Budget {
+        max: Duration,
+        now: fn() -> Duration,
+    }
 
-    impl ThreadModeRawMutex {
-        /// Create a new `ThreadModeRawMutex`.
+    impl PoolRawMutex {
+        /// Create a new `PoolRawMutex`, without a time budget.
         pub const fn new() -> Self {
             Self {
-                taken: AtomicBool::new(false),
+                inner: CriticalSectionRawMutex::new(),
+                #[cfg(debug_assertions)]
+                budget: None,
+            }
+        }
+
+        /// Create a new `PoolRawMutex` which, in builds with debug assertions
+        /// enabled, panics if the lock is held for longer than `budget`.
+        ///
+        /// `now` is called to read the current time when the lock is acquired
+        /// and again before it is released. It returns the time as a
+        /// [`Duration`] since an arbitrary, fixed epoch, and must be cheap
+        /// and callable with interrupts disabled, such as a function reading
+        /// a hardware timer.
+        pub const fn with_budget(budget: Duration, now: fn() -> Duration) -> Self {
+            #[cfg(not(debug_assertions))]
+            let _ = (budget, now);
+            Self {
+                inner: CriticalSectionRawMutex::new(),
+                #[cfg(debug_assertions)]
+                budget: Some(Budget { max: budget, now }),
             }
         }
+
+        /// Returns the number of times this mutex has been successfully
+        /// locked.
+        #[cfg(feature = "test-util")]
+        pub fn lock_count(&self) -> usize {
+            self.inner.lock_count()
+        }
     }
 
-    impl ConstInit for ThreadModeRawMutex {
+    impl ConstInit for PoolRawMutex {
         const INIT: Self = Self::new();
     }
 
-    unsafe impl ScopedRawMutex for ThreadModeRawMutex {
+    unsafe impl ScopedRawMutex for PoolRawMutex {
         #[inline]
-        #[must_use]
+        #[track_caller]
         fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
-            if !in_thread_mode() {
-                return None;
+            #[cfg(debug_assertions)]
+            if let Some(Budget { max, now }) = self.budget {
+                let (ret, elapsed) = self.inner.try_with_lock(|| {
+                    let start = now();
+                    let ret = f();
+                    (ret, now().saturating_sub(start))
+                })?;
+                // Check the budget after the lock has been released, rather
+                // than panicking with interrupts disabled.
+                assert!(
+                    elapsed <= max,
+                    "pool lock was held for {elapsed:?}, exceeding its budget of {max:?}",
+                );
+                return Some(ret);
             }
-            // NOTE: separated load/stores are acceptable as we checked we are only
-            // accessed from a single thread (checked above)
-            assert!(self.taken.load(Ordering::Relaxed));
-            self.taken.store(true, Ordering::Relaxed);
-            let ret = f();
-            self.taken.store(false, Ordering::Relaxed);
-            Some(ret)
+
+            self.inner.try_with_lock(f)
         }
 
         #[inline]
+        #[track_caller]
         fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
-            // In a thread-mode only mutex, it is not possible for another holder
+            // In a critical section, it is not possible for another holder
             // of this mutex to release, which means we have certainly
             // reached deadlock if the lock was already locked.
-            self.try_lock(f)
-                .expect("Deadlocked or attempted to access outside of thread mode")
+            self.try_with_lock(f)
+                .unwrap_or_else(|| deadlocked("Deadlocked"))
         }
 
         fn is_locked(&self) -> bool {
-            self.taken.load(Ordering::Relaxed)
+            self.inner.is_locked()
         }
-    }
-
-    impl Drop for ThreadModeRawMutex {
-        fn drop(&mut self) {
-            // Only allow dropping from thread mode. Dropping calls drop on the inner `T`, so
-            // `drop` needs the same guarantees as `lock`. `ThreadModeMutex<T>` is Send even if
-            // T isn't, so without this check a user could create a ThreadModeMutex in thread mode,
-            // send it to interrupt context and drop it there, which would "send" a T even if T is not Send.
-            assert!(
-                in_thread_mode(),
-                "ThreadModeMutex can only be dropped from thread mode."
-            );
 
-            // Drop of the inner `T` happens after this.
+        fn is_locked_acquire(&self) -> bool {
+            self.inner.is_locked_acquire()
         }
     }
 
-    fn in_thread_mode() -> bool {
-        // ICSR.VECTACTIVE == 0
-        return unsafe { (0xE000ED04 as *const u32).read_volatile() } & 0x1FF == 0;
-    }
+    impl DetectsDeadlock for PoolRawMutex {}
 }
 
-#[cfg(feature = "impl-lock_api-0_4")]
-pub mod lock_api_0_4 {
-    //! [`lock_api`](https://crates.io/crates/lock_api) v0.4 [`RawMutex`]
-    //! implementation.
+// ================
 
-    use ::lock_api_0_4 as lock_api;
-    use mutex_traits::{ConstInit, RawMutex};
+#[cfg(all(feature = "cs-cycle-count", cortex_m, any(armv7m, armv8m_main)))]
+pub mod cs_cycle_count {
+    //! Critical Section based implementation which measures the time spent
+    //! with interrupts disabled
 
-    /// [`lock_api`](https://crates.io/crates/lock_api) v0.4 [`RawMutex`]
-    /// implementation.
-    #[cfg_attr(feature = "fmt", derive(Debug))]
-    pub struct LockApiRawMutex<T>(T);
+    use super::cs::CriticalSectionRawMutex;
+    use super::*;
+    use core::cell::Cell;
+    use cortex_m::peripheral::DWT;
 
-    impl<T: lock_api::RawMutex> ConstInit for LockApiRawMutex<T> {
-        const INIT: Self = LockApiRawMutex(T::INIT);
+    /// Total number of cycles spent in critical sections entered by a
+    /// [`CycleCountingCsRawMutex`].
+    ///
+    /// This is a `u64` protected by a critical section, rather than an
+    /// `AtomicU64`, as Cortex-M targets do not support 64-bit atomics.
+    static TOTAL_CS_CYCLES: critical_section::Mutex<Cell<u64>> =
+        critical_section::Mutex::new(Cell::new(0));
+
+    /// Returns the total number of cycles which have been spent in critical
+    /// sections entered by any [`CycleCountingCsRawMutex`].
+    pub fn total_cs_cycles() -> u64 {
+        critical_section::with(|cs| TOTAL_CS_CYCLES.borrow(cs).get())
     }
 
-    unsafe impl<T: lock_api::RawMutex> RawMutex for LockApiRawMutex<T> {
-        type GuardMarker = <T as lock_api::RawMutex>::GuardMarker;
+    /// Resets the count returned by [`total_cs_cycles`] to zero.
+    pub fn reset_total_cs_cycles() {
+        critical_section::with(|cs| TOTAL_CS_CYCLES.borrow(cs).set(0))
+    }
 
-        #[inline]
-        #[track_caller]
-        fn lock(&self) {
-            self.0.lock();
+    /// A [`CriticalSectionRawMutex`] which measures the number of cycles spent
+    /// in each critical section it enters, using the DWT cycle counter.
+    ///
+    /// The cycle counter is sampled when entering and exiting the critical
+    /// section, and the elapsed cycles are accumulated across all
+    /// `CycleCountingCsRawMutex`es. The total may be read using
+    /// [`total_cs_cycles`]. As a `CriticalSectionRawMutex` disables interrupts
+    /// for the entire duration of the closure, this approximates the total
+    /// time interrupts have been disabled by these mutexes, which is useful
+    /// when validating interrupt latency budgets.
+    ///
+    /// The cycle counter must be enabled (e.g. using
+    /// [`DCB::enable_trace`] and [`DWT::enable_cycle_counter`]) for this to
+    /// measure anything. Note that critical sections entered while another
+    /// critical section is already held are counted in full, so nested
+    /// locking will overestimate the time interrupts were disabled.
+    ///
+    /// [`DCB::enable_trace`]:
+    ///     https://docs.rs/cortex-m/0.7/cortex_m/peripheral/struct.DCB.html#method.enable_trace
+    /// [`DWT::enable_cycle_counter`]:
+    ///     https://docs.rs/cortex-m/0.7/cortex_m/peripheral/struct.DWT.html#method.enable_cycle_counter
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct CycleCountingCsRawMutex {
+        inner: CriticalSectionRawMutex,
+    }
+
+    impl CycleCountingCsRawMutex {
+        /// Create a new `CycleCountingCsRawMutex`.
+        pub const fn new() -> Self {
+            Self {
+                inner: CriticalSectionRawMutex::new(),
+            }
         }
 
-        #[inline]
-        #[track_caller]
-        fn try_lock(&self) -> bool {
-            self.0.try_lock()
+        /// Returns the number of times this mutex has been successfully
+        /// locked.
+        #[cfg(feature = "test-util")]
+        pub fn lock_count(&self) -> usize {
+            self.inner.lock_count()
         }
+    }
+
+    impl ConstInit for CycleCountingCsRawMutex {
+        const INIT: Self = Self::new();
+    }
 
+    unsafe impl ScopedRawMutex for CycleCountingCsRawMutex {
         #[inline]
-        #[track_caller]
-        unsafe fn unlock(&self) {
-            self.0.unlock()
+        fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            critical_section::with(|cs| {
+                // Using a drop guard ensures that the cycles are counted even
+                // if `f()` panics.
+                let _sample = Sample {
+                    start: DWT::cycle_count(),
+                    cs,
+                };
+                self.inner.try_with_lock_in_cs(f)
+            })
         }
 
         #[inline]
-        #[track_caller]
-        fn is_locked(&self) -> bool {
-            self.0.is_locked()
+        fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            // In a critical section, it is not possible for another holder
+            // of this mutex to release, which means we have certainly
+            // reached deadlock if the lock was already locked.
+            self.try_with_lock(f)
+                .unwrap_or_else(|| deadlocked("Deadlocked"))
+        }
+
+        fn is_locked(&self) -> bool {
+            self.inner.is_locked()
+        }
+
+        fn is_locked_acquire(&self) -> bool {
+            self.inner.is_locked_acquire()
+        }
+    }
+
+    impl DetectsDeadlock for CycleCountingCsRawMutex {}
+
+    /// Drop guard which adds the cycles elapsed since `start` to the total.
+    struct Sample<'cs> {
+        start: u32,
+        cs: critical_section::CriticalSection<'cs>,
+    }
+
+    impl Drop for Sample<'_> {
+        #[inline]
+        fn drop(&mut self) {
+            let elapsed = DWT::cycle_count().wrapping_sub(self.start);
+            let total = TOTAL_CS_CYCLES.borrow(self.cs);
+            total.set(total.get() + u64::from(elapsed));
+        }
+    }
+}
+
+// ================
+
+#[cfg(feature = "impl-critical-section")]
+pub mod cs_raw {
+    //! Critical Section based implementation with separate lock and unlock
+    //! operations
+
+    use super::*;
+    use core::cell::UnsafeCell;
+    use critical_section::RestoreState;
+
+    /// A mutex that allows borrowing data across executors and interrupts,
+    /// which may be locked and unlocked by separate calls.
+    ///
+    /// Unlike [`CriticalSectionRawMutex`], which only enters a critical section
+    /// for the duration of a closure, this mutex may also be locked using
+    /// [`lock`](Self::lock), which enters a critical section using
+    /// [`critical_section::acquire`], and unlocked using
+    /// [`unlock`](Self::unlock), which exits it using
+    /// [`critical_section::release`]. The [`RestoreState`] token returned by
+    /// `acquire` is stored inside the mutex while it is locked.
+    ///
+    /// This mutex also implements [`ScopedRawMutex`], which locks and unlocks
+    /// it around a closure, and may be used with [`BlockingMutex`] as usual.
+    /// It does not implement [`RawMutex`](mutex_traits::RawMutex): a
+    /// [`MutexGuard`](crate::MutexGuard) may be dropped in any order, or
+    /// leaked, which would exit the critical section out of order, or never
+    /// exit it at all.
+    ///
+    /// # Nesting
+    ///
+    /// Critical sections must be strictly nested: each one must be exited
+    /// before the critical section which was entered before it. As this mutex
+    /// holds a critical section from [`lock`](Self::lock) until
+    /// [`unlock`](Self::unlock), those calls must be nested with any other
+    /// critical sections, including those held by other
+    /// `CriticalSectionFullRawMutex`es, [`critical_section::with`], and
+    /// [`CriticalSectionRawMutex`]. That is:
+    ///
+    /// - If a critical section is entered while this mutex is locked, it must
+    ///   be exited before this mutex is unlocked. In particular, when several
+    ///   `CriticalSectionFullRawMutex`es are held at the same time, they must
+    ///   be unlocked in the reverse order in which they were locked.
+    /// - If this mutex is locked while a critical section is held, it must be
+    ///   unlocked before that critical section is exited. For example, this
+    ///   mutex must not be locked inside a closure passed to
+    ///   [`critical_section::with`] and unlocked after the closure returns.
+    ///
+    /// Exiting critical sections out of order is undefined behavior, so
+    /// [`lock`](Self::lock), [`try_lock`](Self::try_lock), and
+    /// [`unlock`](Self::unlock) are `unsafe`, and their callers must uphold
+    /// this. Locking the mutex using [`ScopedRawMutex`] is always correctly
+    /// nested, and is safe.
+    ///
+    /// # Lock and unlock callbacks
+    ///
+    /// As this mutex may be locked and unlocked by separate calls, rather
+    /// than for the duration of a closure, it may back the `lock` and
+    /// `unlock` callbacks registered with C libraries which protect their
+    /// internal state with a user-provided lock:
+    ///
+    /// ```
+    /// use mutex::raw_impls::cs_raw::CriticalSectionFullRawMutex;
+    ///
+    /// static LIB_LOCK: CriticalSectionFullRawMutex = CriticalSectionFullRawMutex::new();
+    ///
+    /// /// Registered with the C library as its `lock` callback.
+    /// extern "C" fn lib_lock() {
+    ///     // SAFETY: the library calls `unlock` after `lock`, in the same
+    ///     // context, and does not enter or exit other critical sections
+    ///     // while its lock is held.
+    ///     unsafe { LIB_LOCK.lock() }
+    /// }
+    ///
+    /// /// Registered with the C library as its `unlock` callback.
+    /// extern "C" fn lib_unlock() {
+    ///     // SAFETY: the library only calls `unlock` after `lock`.
+    ///     unsafe { LIB_LOCK.unlock() }
+    /// }
+    /// ```
+    ///
+    /// The `unsafe` blocks in such callbacks rely on the C library's locking
+    /// protocol, which must guarantee that:
+    ///
+    /// - every call to `unlock` follows a call to `lock`, on the same thread
+    ///   or in the same interrupt handler;
+    /// - `lock` is not called again before `unlock` (the library does not
+    ///   lock recursively), as this panics, which aborts when unwinding out of
+    ///   an `extern "C"` function; and
+    /// - any critical section entered between `lock` and `unlock`, e.g. by
+    ///   Rust code called back by the library, is exited before `unlock`.
+    ///
+    /// As interrupts cannot preempt the critical section held between `lock`
+    /// and `unlock`, calls made by interrupt handlers are always nested
+    /// within any calls which they interrupted.
+    ///
+    /// # Safety
+    ///
+    /// This mutex is safe to share between different executors and interrupts.
+    ///
+    /// [`CriticalSectionRawMutex`]: super::cs::CriticalSectionRawMutex
+    pub struct CriticalSectionFullRawMutex {
+        taken: AtomicBool,
+        count: LockCount,
+        /// The restore state returned by `critical_section::acquire` when this
+        /// mutex was locked. Only accessed while the critical section is held.
+        restore: UnsafeCell<RestoreState>,
+    }
+    unsafe impl Send for CriticalSectionFullRawMutex {}
+    unsafe impl Sync for CriticalSectionFullRawMutex {}
+
+    impl CriticalSectionFullRawMutex {
+        /// Create a new `CriticalSectionFullRawMutex`.
+        pub const fn new() -> Self {
+            Self {
+                taken: AtomicBool::new(false),
+                count: LockCount::new(),
+                restore: UnsafeCell::new(RestoreState::invalid()),
+            }
+        }
+
+        /// Returns the number of times this mutex has been successfully
+        /// locked.
+        #[cfg(feature = "test-util")]
+        pub fn lock_count(&self) -> usize {
+            self.count.get()
+        }
+
+        /// Locks the mutex, entering a critical section which is held until
+        /// the mutex is unlocked using [`unlock`](Self::unlock).
+        ///
+        /// # Panics
+        ///
+        /// If the mutex is already locked. As the critical section is held
+        /// while the mutex is locked, another holder of this mutex cannot
+        /// unlock it, so this would deadlock.
+        ///
+        /// # Safety
+        ///
+        /// The mutex must be unlocked by the same execution context, and the
+        /// critical section it holds must be nested with all others, as
+        /// described in the [type-level documentation](Self#nesting).
+        #[inline]
+        #[track_caller]
+        pub unsafe fn lock(&self) {
+            // In a critical section, it is not possible for another holder
+            // of this mutex to release, which means we have certainly
+            // reached deadlock if the lock was already locked.
+            if !self.try_lock() {
+                deadlocked("Deadlocked");
+            }
+        }
+
+        /// Attempts to lock the mutex, entering a critical section which is
+        /// held until the mutex is unlocked using [`unlock`](Self::unlock).
+        ///
+        /// Returns `false` without entering a critical section if the mutex
+        /// is already locked.
+        ///
+        /// # Safety
+        ///
+        /// If this returns `true`, the mutex must be unlocked by the same
+        /// execution context, and the critical section it holds must be nested
+        /// with all others, as described in the
+        /// [type-level documentation](Self#nesting).
+        #[inline]
+        pub unsafe fn try_lock(&self) -> bool {
+            // SAFETY: the critical section is either released below if the
+            // mutex is already taken, or in `unlock` otherwise, which the
+            // caller guarantees is correctly nested.
+            let restore = critical_section::acquire();
+            // NOTE: separated load/stores are acceptable as we are in
+            // a critical section
+            if self.taken.load(Ordering::Relaxed) {
+                // SAFETY: `restore` was returned by the `acquire` above, and
+                // no other critical section has been entered since.
+                critical_section::release(restore);
+                return false;
+            }
+            self.taken.store(true, Ordering::Relaxed);
+            self.count.increment();
+            // SAFETY: we hold the critical section and have just taken the
+            // lock, so nothing else may access `self.restore`.
+            self.restore.get().write(restore);
+            true
+        }
+
+        /// Unlocks the mutex, exiting the critical section entered when it
+        /// was locked.
+        ///
+        /// # Safety
+        ///
+        /// The mutex must have been locked by a call to [`lock`](Self::lock)
+        /// or [`try_lock`](Self::try_lock) in the same execution context, and
+        /// every critical section entered since then must have been exited,
+        /// as described in the [type-level documentation](Self#nesting).
+        #[inline]
+        pub unsafe fn unlock(&self) {
+            // SAFETY: the caller guarantees that the lock is held, so we are
+            // still in the critical section entered in `try_lock`.
+            let restore = self.restore.get().read();
+            // NOTE: `Release` pairs with the `Acquire` load in
+            // `is_locked_acquire`, which may be called outside of a critical
+            // section.
+            self.taken.store(false, Ordering::Release);
+            critical_section::release(restore);
+        }
+    }
+
+    impl ConstInit for CriticalSectionFullRawMutex {
+        const INIT: Self = Self::new();
+    }
+
+    unsafe impl ScopedRawMutex for CriticalSectionFullRawMutex {
+        #[inline]
+        fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            run_locked(
+                // SAFETY: the lock is released by `run_locked` before it
+                // returns (or unwinds), so the critical section is nested
+                // with any others entered or exited by `f`.
+                || unsafe { self.try_lock() },
+                || unsafe { self.unlock() },
+                f,
+            )
+        }
+
+        #[inline]
+        #[track_caller]
+        fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            // In a critical section, it is not possible for another holder
+            // of this mutex to release, which means we have certainly
+            // reached deadlock if the lock was already locked.
+            self.try_with_lock(f)
+                .unwrap_or_else(|| deadlocked("Deadlocked"))
+        }
+
+        #[inline]
+        fn is_locked(&self) -> bool {
+            self.taken.load(Ordering::Relaxed)
+        }
+
+        #[inline]
+        fn is_locked_acquire(&self) -> bool {
+            self.taken.load(Ordering::Acquire)
+        }
+    }
+
+    impl DetectsDeadlock for CriticalSectionFullRawMutex {}
+
+    #[cfg(feature = "fmt")]
+    impl core::fmt::Debug for CriticalSectionFullRawMutex {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            f.debug_struct("CriticalSectionFullRawMutex")
+                .field("taken", &self.taken)
+                .finish_non_exhaustive()
+        }
+    }
+}
+
+// ================
+
+#[cfg(feature = "impl-critical-section")]
+pub mod global_logger {
+    //! A lock for implementing [`defmt`]'s global logger
+    //!
+    //! [`defmt`]: https://docs.rs/defmt/
+
+    use super::cs_raw::CriticalSectionFullRawMutex;
+    use super::*;
+
+    /// A mutex intended for implementing the `acquire` and `release` methods
+    /// of a [`defmt::Logger`].
+    ///
+    /// A global logger may be used from any context, including interrupts, so
+    /// this mutex is backed by a [`CriticalSectionFullRawMutex`]: the
+    /// critical section is entered in [`acquire`], and only exited in
+    /// [`release`]. As `defmt` requires that the logger is not acquired
+    /// reentrantly, [`acquire`] panics if the lock is already held.
+    ///
+    /// As the critical section is held between calls, they must be nested
+    /// with any other critical sections, as described for
+    /// [`CriticalSectionFullRawMutex`]. `defmt` calls `release` after each
+    /// call to `acquire`, once the log frame has been written, so this holds
+    /// as long as the logger's `write` and `flush` methods do not themselves
+    /// exit the critical section.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// use mutex::raw_impls::global_logger::GlobalLoggerRawMutex;
+    ///
+    /// static LOCK: GlobalLoggerRawMutex = GlobalLoggerRawMutex::new();
+    /// static mut ENCODER: defmt::Encoder = defmt::Encoder::new();
+    ///
+    /// #[defmt::global_logger]
+    /// struct Logger;
+    ///
+    /// unsafe impl defmt::Logger for Logger {
+    ///     fn acquire() {
+    ///         // SAFETY: `defmt` calls `release` after `acquire`, in the same
+    ///         // context.
+    ///         unsafe { LOCK.acquire() };
+    ///         // SAFETY: the encoder is only accessed while `LOCK` is held.
+    ///         unsafe { (*core::ptr::addr_of_mut!(ENCODER)).start_frame(do_write) }
+    ///     }
+    ///
+    ///     unsafe fn flush() {}
+    ///
+    ///     unsafe fn release() {
+    ///         (*core::ptr::addr_of_mut!(ENCODER)).end_frame(do_write);
+    ///         LOCK.release();
+    ///     }
+    ///
+    ///     unsafe fn write(bytes: &[u8]) {
+    ///         (*core::ptr::addr_of_mut!(ENCODER)).write(bytes, do_write);
+    ///     }
+    /// }
+    ///
+    /// fn do_write(bytes: &[u8]) {
+    ///     // write `bytes` to the RTT channel...
+    /// }
+    /// ```
+    ///
+    /// [`defmt::Logger`]: https://docs.rs/defmt/latest/defmt/trait.Logger.html
+    /// [`acquire`]: GlobalLoggerRawMutex::acquire
+    /// [`release`]: GlobalLoggerRawMutex::release
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct GlobalLoggerRawMutex {
+        raw: CriticalSectionFullRawMutex,
+    }
+
+    impl GlobalLoggerRawMutex {
+        /// Create a new `GlobalLoggerRawMutex`.
+        pub const fn new() -> Self {
+            Self {
+                raw: CriticalSectionFullRawMutex::new(),
+            }
+        }
+
+        /// Acquires the lock, entering a critical section.
+        ///
+        /// This is intended to be called from `defmt::Logger::acquire`.
+        ///
+        /// # Panics
+        ///
+        /// If the lock is already held, as this indicates that the logger was
+        /// acquired reentrantly.
+        ///
+        /// # Safety
+        ///
+        /// The lock must be released using [`release`](Self::release) in the
+        /// same context, and the critical section it holds must be nested
+        /// with all others, as described for [`CriticalSectionFullRawMutex`].
+        #[inline]
+        #[track_caller]
+        pub unsafe fn acquire(&self) {
+            if !self.raw.try_lock() {
+                deadlocked("defmt logger taken reentrantly");
+            }
+        }
+
+        /// Releases the lock, exiting the critical section entered by
+        /// [`acquire`](Self::acquire).
+        ///
+        /// This is intended to be called from `defmt::Logger::release`.
+        ///
+        /// # Safety
+        ///
+        /// This method may only be called after a call to
+        /// [`acquire`](Self::acquire), in the same context, once every
+        /// critical section entered since then has been exited.
+        #[inline]
+        pub unsafe fn release(&self) {
+            self.raw.unlock()
+        }
+    }
+
+    impl ConstInit for GlobalLoggerRawMutex {
+        const INIT: Self = Self::new();
+    }
+}
+
+// ================
+
+#[cfg(feature = "impl-cortex-m-interrupt")]
+pub mod cortex_m_interrupt {
+    //! [`cortex_m::interrupt::free`] based implementation
+    //!
+    //! [`cortex_m::interrupt::free`]:
+    //!     https://docs.rs/cortex-m/0.7/cortex_m/interrupt/fn.free.html
+
+    use super::*;
+
+    /// A mutex that allows borrowing data across executors and interrupts,
+    /// using [`cortex_m::interrupt::free`] directly rather than the
+    /// `critical-section` crate.
+    ///
+    /// This behaves identically to [`CriticalSectionRawMutex`], and is intended
+    /// for projects which do not use `critical-section`.
+    ///
+    /// # Safety
+    ///
+    /// This mutex is safe to share between different executors and interrupts
+    /// **on single-core systems**. Disabling interrupts does not prevent
+    /// concurrent access from other cores.
+    ///
+    /// [`cortex_m::interrupt::free`]:
+    ///     https://docs.rs/cortex-m/0.7/cortex_m/interrupt/fn.free.html
+    /// [`CriticalSectionRawMutex`]: super::cs::CriticalSectionRawMutex
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct CortexMInterruptFreeRawMutex {
+        taken: AtomicBool,
+        count: LockCount,
+    }
+    unsafe impl Send for CortexMInterruptFreeRawMutex {}
+    unsafe impl Sync for CortexMInterruptFreeRawMutex {}
+
+    impl CortexMInterruptFreeRawMutex {
+        /// Create a new `CortexMInterruptFreeRawMutex`.
+        pub const fn new() -> Self {
+            Self {
+                taken: AtomicBool::new(false),
+                count: LockCount::new(),
+            }
+        }
+
+        /// Returns the number of times this mutex has been successfully
+        /// locked.
+        #[cfg(feature = "test-util")]
+        pub fn lock_count(&self) -> usize {
+            self.count.get()
+        }
+    }
+
+    impl ConstInit for CortexMInterruptFreeRawMutex {
+        const INIT: Self = Self::new();
+    }
+
+    unsafe impl ScopedRawMutex for CortexMInterruptFreeRawMutex {
+        #[inline]
+        fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            cortex_m::interrupt::free(|_| {
+                run_locked(
+                    || {
+                        // NOTE: separated load/stores are acceptable as
+                        // interrupts are disabled
+                        if self.taken.load(Ordering::Relaxed) {
+                            return false;
+                        }
+                        self.taken.store(true, Ordering::Relaxed);
+                        self.count.increment();
+                        true
+                    },
+                    // NOTE: `Release` pairs with the `Acquire` load in
+                    // `is_locked_acquire`, which may be called with
+                    // interrupts enabled.
+                    || self.taken.store(false, Ordering::Release),
+                    f,
+                )
+            })
+        }
+
+        #[inline]
+        fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            // With interrupts disabled, it is not possible for another holder
+            // of this mutex to release, which means we have certainly
+            // reached deadlock if the lock was already locked.
+            self.try_with_lock(f)
+                .unwrap_or_else(|| deadlocked("Deadlocked"))
+        }
+
+        fn is_locked(&self) -> bool {
+            self.taken.load(Ordering::Relaxed)
+        }
+
+        fn is_locked_acquire(&self) -> bool {
+            self.taken.load(Ordering::Acquire)
+        }
+    }
+
+    impl DetectsDeadlock for CortexMInterruptFreeRawMutex {}
+}
+
+// ================
+
+#[cfg(all(
+    feature = "impl-riscv-interrupt",
+    any(target_arch = "riscv32", target_arch = "riscv64")
+))]
+pub mod riscv_interrupt {
+    //! RISC-V `mstatus.MIE` based implementation
+
+    use super::*;
+    use core::arch::asm;
+
+    /// The machine interrupt enable (`MIE`) bit in `mstatus`.
+    const MSTATUS_MIE: usize = 1 << 3;
+
+    /// A mutex that allows borrowing data across executors and interrupts on
+    /// RISC-V, by clearing the machine interrupt enable bit (`mstatus.MIE`)
+    /// while the lock is held, rather than using the `critical-section`
+    /// crate.
+    ///
+    /// This behaves identically to [`CriticalSectionRawMutex`], and is intended
+    /// for bare-metal projects running in machine mode which do not use
+    /// `critical-section`.
+    ///
+    /// # Nesting
+    ///
+    /// Each lock saves the previous value of `mstatus.MIE` when it clears it,
+    /// and restores that value when it is released, even if the closure
+    /// panics. A lock taken while another is held (or in an interrupt
+    /// handler, where interrupts are already disabled) therefore leaves
+    /// interrupts disabled when it is released, and only the outermost lock
+    /// re-enables them.
+    ///
+    /// # Safety
+    ///
+    /// This mutex is safe to share between different executors and interrupts
+    /// **on single-hart systems**. Clearing `mstatus.MIE` only masks
+    /// interrupts on the current hart, and does not prevent concurrent access
+    /// from other harts.
+    ///
+    /// Additionally, it must only be locked from machine mode, as `mstatus`
+    /// is not accessible from lower privilege levels.
+    ///
+    /// [`CriticalSectionRawMutex`]: super::cs::CriticalSectionRawMutex
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct RiscvInterruptFreeRawMutex {
+        taken: AtomicBool,
+        count: LockCount,
+    }
+    unsafe impl Send for RiscvInterruptFreeRawMutex {}
+    unsafe impl Sync for RiscvInterruptFreeRawMutex {}
+
+    impl RiscvInterruptFreeRawMutex {
+        /// Create a new `RiscvInterruptFreeRawMutex`.
+        pub const fn new() -> Self {
+            Self {
+                taken: AtomicBool::new(false),
+                count: LockCount::new(),
+            }
+        }
+
+        /// Returns the number of times this mutex has been successfully
+        /// locked.
+        #[cfg(feature = "test-util")]
+        pub fn lock_count(&self) -> usize {
+            self.count.get()
+        }
+    }
+
+    impl ConstInit for RiscvInterruptFreeRawMutex {
+        const INIT: Self = Self::new();
+    }
+
+    unsafe impl ScopedRawMutex for RiscvInterruptFreeRawMutex {
+        #[inline]
+        fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            let _restore = InterruptsDisabled::acquire();
+            run_locked(
+                || {
+                    // NOTE: separated load/stores are acceptable as
+                    // interrupts are disabled
+                    if self.taken.load(Ordering::Relaxed) {
+                        return false;
+                    }
+                    self.taken.store(true, Ordering::Relaxed);
+                    self.count.increment();
+                    true
+                },
+                // NOTE: `Release` pairs with the `Acquire` load in
+                // `is_locked_acquire`, which may be called with interrupts
+                // enabled.
+                || self.taken.store(false, Ordering::Release),
+                f,
+            )
+        }
+
+        #[inline]
+        fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            // With interrupts disabled, it is not possible for another holder
+            // of this mutex to release, which means we have certainly
+            // reached deadlock if the lock was already locked.
+            self.try_with_lock(f)
+                .unwrap_or_else(|| deadlocked("Deadlocked"))
+        }
+
+        fn is_locked(&self) -> bool {
+            self.taken.load(Ordering::Relaxed)
+        }
+
+        fn is_locked_acquire(&self) -> bool {
+            self.taken.load(Ordering::Acquire)
+        }
+    }
+
+    impl DetectsDeadlock for RiscvInterruptFreeRawMutex {}
+
+    /// Clears `mstatus.MIE` when acquired, and restores its previous value
+    /// when dropped.
+    struct InterruptsDisabled {
+        was_enabled: bool,
+    }
+
+    impl InterruptsDisabled {
+        #[inline(always)]
+        fn acquire() -> Self {
+            let mstatus: usize;
+            // SAFETY: clearing `mstatus.MIE` only masks interrupts, and the
+            // previous value is restored when the guard is dropped.
+            unsafe {
+                asm!(
+                    "csrrci {mstatus}, mstatus, {mie}",
+                    mstatus = out(reg) mstatus,
+                    mie = const MSTATUS_MIE,
+                    options(nostack, preserves_flags),
+                );
+            }
+            Self {
+                was_enabled: mstatus & MSTATUS_MIE != 0,
+            }
+        }
+    }
+
+    impl Drop for InterruptsDisabled {
+        #[inline(always)]
+        fn drop(&mut self) {
+            if self.was_enabled {
+                // SAFETY: interrupts were enabled when this guard was
+                // acquired, so re-enabling them restores the previous state.
+                unsafe {
+                    asm!(
+                        "csrsi mstatus, {mie}",
+                        mie = const MSTATUS_MIE,
+                        options(nostack, preserves_flags),
+                    );
+                }
+            }
+        }
+    }
+}
+
+// ================
+
+pub mod local {
+    //! Locally usable based implementation
+    use super::*;
+
+    /// A mutex that allows borrowing data in local context.
+    ///
+    /// This acts similar to a RefCell, with scoped access patterns, though
+    /// without being able to borrow the data twice.
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct LocalRawMutex {
+        taken: AtomicBool,
+        count: LockCount,
+        /// Prevent this from being sync or send
+        _phantom: PhantomData<*mut ()>,
+    }
+
+    impl LocalRawMutex {
+        /// Create a new `LocalRawMutex`.
+        pub const fn new() -> Self {
+            Self {
+                taken: AtomicBool::new(false),
+                count: LockCount::new(),
+                _phantom: PhantomData,
+            }
+        }
+
+        /// Returns the number of times this mutex has been successfully
+        /// locked.
+        #[cfg(feature = "test-util")]
+        pub fn lock_count(&self) -> usize {
+            self.count.get()
+        }
+    }
+
+    unsafe impl Send for LocalRawMutex {}
+
+    impl ConstInit for LocalRawMutex {
+        const INIT: Self = Self::new();
+    }
+
+    /// A [`BlockingMutex`] backed by a [`LocalRawMutex`].
+    ///
+    /// This type is [`Send`] whenever `T` is [`Send`], but it is never
+    /// [`Sync`].
+    pub type LocalMutex<T> = BlockingMutex<LocalRawMutex, T>;
+
+    const _: fn() = || {
+        assert_send::<LocalMutex<u32>>();
+    };
+
+    unsafe impl ScopedRawMutex for LocalRawMutex {
+        #[inline]
+        fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            run_locked(
+                || {
+                    // NOTE: separated load/stores are acceptable as we are !Sync,
+                    // meaning that we can only be accessed within a single thread
+                    if self.taken.load(Ordering::Relaxed) {
+                        return false;
+                    }
+                    self.taken.store(true, Ordering::Relaxed);
+                    self.count.increment();
+                    true
+                },
+                || self.taken.store(false, Ordering::Relaxed),
+                f,
+            )
+        }
+
+        #[inline]
+        fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            // In a local-only mutex, it is not possible for another holder
+            // of this mutex to release, which means we have certainly
+            // reached deadlock if the lock was already locked.
+            self.try_with_lock(f)
+                .unwrap_or_else(|| deadlocked("Deadlocked"))
+        }
+
+        fn is_locked(&self) -> bool {
+            self.taken.load(Ordering::Relaxed)
+        }
+
+        /// As this mutex is `!Sync`, it can only be locked by the current
+        /// thread, so it is owned by the current context whenever it is
+        /// locked.
+        fn is_owned(&self) -> bool {
+            self.is_locked()
+        }
+    }
+
+    impl DetectsDeadlock for LocalRawMutex {}
+
+    impl<T: ?Sized> BlockingMutex<LocalRawMutex, T> {
+        /// Grants temporary access to the inner data, assuming that the mutex
+        /// is not locked, without checking or updating the lock state.
+        ///
+        /// This is a fast path for code which has already proven, by its
+        /// structure, that it never re-enters the mutex: in builds with debug
+        /// assertions disabled, it reduces the lock to a plain mutable borrow
+        /// of the data, with no loads or stores of the lock state.
+        ///
+        /// In builds with debug assertions enabled, the mutex is locked as
+        /// usual for the duration of `f`, and this panics if it was already
+        /// locked, so that violations of the safety requirements below are
+        /// caught in testing.
+        ///
+        /// # Safety
+        ///
+        /// The caller must ensure that the mutex is not locked when this is
+        /// called, and that it is not locked (using this or any other method)
+        /// while `f` runs. As the lock state is not updated, a nested call to
+        /// [`with_lock`](BlockingMutex::with_lock) from within `f` will *not*
+        /// detect the re-entry, and will create a second `&mut T` aliasing the
+        /// one passed to `f`, which is undefined behavior.
+        #[inline]
+        #[track_caller]
+        pub unsafe fn with_lock_assume_free<U>(&self, f: impl FnOnce(&mut T) -> U) -> U {
+            #[cfg(debug_assertions)]
+            {
+                match self.try_with_lock(f) {
+                    Some(ret) => ret,
+                    None => panic!(
+                        "`with_lock_assume_free` called while the mutex was \
+                         locked; this is undefined behavior in release builds"
+                    ),
+                }
+            }
+
+            #[cfg(not(debug_assertions))]
+            {
+                // SAFETY: the caller guarantees that the mutex is not locked,
+                // and will not be locked while `f` runs, so no other
+                // reference to the data exists. As `LocalRawMutex` is
+                // `!Sync`, no other thread can access it either.
+                f(unsafe { &mut *self.data.get() })
+            }
+        }
+    }
+}
+
+// ================
+
+#[cfg(feature = "impl-wasm")]
+pub mod wasm {
+    //! Single-threaded WebAssembly implementation
+
+    use super::*;
+    use core::cell::Cell;
+
+    /// A mutex for single-threaded WebAssembly targets.
+    ///
+    /// This behaves like a [`LocalRawMutex`], panicking if it is locked
+    /// reentrantly, but it is [`Sync`], so that a `BlockingMutex` using it may
+    /// be placed in a `static`.
+    ///
+    /// # Safety
+    ///
+    /// This mutex is only [`Sync`] when compiling for `wasm32` *without* the
+    /// `atomics` target feature. Without that feature, WebAssembly code cannot
+    /// be multi-threaded, so there is no concurrency to guard against. When
+    /// compiling for any other target, or with the `atomics` target feature
+    /// enabled, this mutex is not [`Sync`].
+    ///
+    /// [`LocalRawMutex`]: super::local::LocalRawMutex
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct WasmRawMutex {
+        taken: Cell<bool>,
+        count: LockCount,
+    }
+
+    // SAFETY: without the `atomics` target feature, wasm has no threads.
+    #[cfg(all(target_arch = "wasm32", not(target_feature = "atomics")))]
+    unsafe impl Sync for WasmRawMutex {}
+
+    impl WasmRawMutex {
+        /// Create a new `WasmRawMutex`.
+        pub const fn new() -> Self {
+            Self {
+                taken: Cell::new(false),
+                count: LockCount::new(),
+            }
+        }
+
+        /// Returns the number of times this mutex has been successfully
+        /// locked.
+        #[cfg(feature = "test-util")]
+        pub fn lock_count(&self) -> usize {
+            self.count.get()
+        }
+    }
+
+    impl ConstInit for WasmRawMutex {
+        const INIT: Self = Self::new();
+    }
+
+    unsafe impl ScopedRawMutex for WasmRawMutex {
+        #[inline]
+        fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            run_locked(
+                || {
+                    if self.taken.replace(true) {
+                        return false;
+                    }
+                    self.count.increment();
+                    true
+                },
+                || self.taken.set(false),
+                f,
+            )
+        }
+
+        #[inline]
+        fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            // On a single thread, it is not possible for another holder
+            // of this mutex to release, which means we have certainly
+            // reached deadlock if the lock was already locked.
+            self.try_with_lock(f)
+                .unwrap_or_else(|| deadlocked("Deadlocked"))
+        }
+
+        fn is_locked(&self) -> bool {
+            self.taken.get()
+        }
+
+        /// As there is only a single thread, this mutex is owned by the
+        /// current context whenever it is locked.
+        fn is_owned(&self) -> bool {
+            self.is_locked()
+        }
+    }
+
+    impl DetectsDeadlock for WasmRawMutex {}
+}
+
+// ================
+
+#[cfg(all(feature = "impl-unsafe-cortex-m-single-core", cortex_m))]
+pub mod single_core_thread_mode {
+    //! A single-core safe implementation that does not require a critical section
+
+    use super::*;
+
+    /// A "mutex" that only allows borrowing from thread mode.
+    ///
+    /// # Safety
+    ///
+    /// **This Mutex is only safe on single-core systems.**
+    ///
+    /// On multi-core systems, a `ThreadModeRawMutex` **is not sufficient** to ensure exclusive access.
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct ThreadModeRawMutex {
+        taken: AtomicBool,
+        count: LockCount,
+    }
+
+    unsafe impl Send for ThreadModeRawMutex {}
+    unsafe impl Sync for ThreadModeRawMutex {}
+
+    impl ThreadModeRawMutex {
+        /// Create a new `ThreadModeRawMutex`.
+        pub const fn new() -> Self {
+            Self {
+                taken: AtomicBool::new(false),
+                count: LockCount::new(),
+            }
+        }
+
+        /// Returns the number of times this mutex has been successfully
+        /// locked.
+        #[cfg(feature = "test-util")]
+        pub fn lock_count(&self) -> usize {
+            self.count.get()
+        }
+    }
+
+    impl ConstInit for ThreadModeRawMutex {
+        const INIT: Self = Self::new();
+    }
+
+    /// A [`BlockingMutex`] backed by a [`ThreadModeRawMutex`].
+    ///
+    /// This type is [`Send`] and [`Sync`] whenever `T` is [`Send`].
+    pub type ThreadModeMutex<T> = BlockingMutex<ThreadModeRawMutex, T>;
+
+    const _: fn() = || {
+        assert_send::<ThreadModeMutex<u32>>();
+        assert_sync::<ThreadModeMutex<u32>>();
+    };
+
+    unsafe impl ScopedRawMutex for ThreadModeRawMutex {
+        #[inline]
+        fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            run_locked(
+                || {
+                    if !in_thread_mode() {
+                        return false;
+                    }
+                    // NOTE: separated load/stores are acceptable as we checked we are only
+                    // accessed from a single thread (checked above)
+                    if self.taken.load(Ordering::Relaxed) {
+                        return false;
+                    }
+                    self.taken.store(true, Ordering::Relaxed);
+                    self.count.increment();
+                    true
+                },
+                // NOTE: `Release` pairs with the `Acquire` load in
+                // `is_locked_acquire`, which may be called from outside of
+                // thread mode.
+                || self.taken.store(false, Ordering::Release),
+                f,
+            )
+        }
+
+        #[inline]
+        fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            // In a thread-mode only mutex, it is not possible for another holder
+            // of this mutex to release, which means we have certainly
+            // reached deadlock if the lock was already locked.
+            self.try_with_lock(f).unwrap_or_else(|| {
+                deadlocked("Deadlocked or attempted to access outside of thread mode")
+            })
+        }
+
+        fn is_locked(&self) -> bool {
+            self.taken.load(Ordering::Relaxed)
+        }
+
+        fn is_locked_acquire(&self) -> bool {
+            self.taken.load(Ordering::Acquire)
+        }
+
+        /// This mutex can only be locked from thread mode, so it is owned by
+        /// the current context if it is locked and we are in thread mode.
+        fn is_owned(&self) -> bool {
+            in_thread_mode() && self.is_locked()
+        }
+    }
+
+    impl DetectsDeadlock for ThreadModeRawMutex {}
+
+    impl Drop for ThreadModeRawMutex {
+        fn drop(&mut self) {
+            // Only allow dropping from thread mode. Dropping calls drop on the inner `T`, so
+            // `drop` needs the same guarantees as `lock`. `ThreadModeMutex<T>` is Send even if
+            // T isn't, so without this check a user could create a ThreadModeMutex in thread mode,
+            // send it to interrupt context and drop it there, which would "send" a T even if T is not Send.
+            assert!(
+                in_thread_mode(),
+                "ThreadModeMutex can only be dropped from thread mode."
+            );
+
+            // Drop of the inner `T` happens after this.
+        }
+    }
+
+    fn in_thread_mode() -> bool {
+        vectactive() == 0
+    }
+}
+
+// ================
+
+#[cfg(all(
+    feature = "impl-critical-section",
+    feature = "impl-unsafe-cortex-m-single-core",
+    cortex_m
+))]
+pub mod nested_cs {
+    //! A single-core Critical Section based implementation which does not
+    //! re-enter the critical section when locks are nested
+
+    use super::cs::CriticalSectionRawMutex;
+    use super::*;
+    use core::sync::atomic::AtomicUsize;
+
+    /// The number of `NestedCsRawMutex`es currently locked. This is only
+    /// modified while in a critical section.
+    static DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+    /// A [`CriticalSectionRawMutex`] which only enters a critical section if
+    /// no other `NestedCsRawMutex` is already locked.
+    ///
+    /// A `CriticalSectionRawMutex` enters a new critical section each time it
+    /// is locked, even when it is locked within the closure passed to another
+    /// mutex's [`with_lock`] (and so is already in a critical section). When
+    /// many mutexes are locked in nested helper functions, the cost of
+    /// repeatedly entering and exiting the critical section can add up.
+    ///
+    /// Instead, `NestedCsRawMutex`es share a global count of the number of
+    /// `NestedCsRawMutex`es which are currently locked. A critical section is
+    /// only entered when this count is zero; nested locks of other
+    /// `NestedCsRawMutex`es reuse the outermost critical section, and only
+    /// update the count and the mutex's own state.
+    ///
+    /// Only `NestedCsRawMutex`es are counted: a `NestedCsRawMutex` locked
+    /// within a critical section entered by other means (such as by a
+    /// [`CriticalSectionRawMutex`]) enters a nested critical section, as
+    /// usual.
+    ///
+    /// # Safety
+    ///
+    /// **This Mutex is only safe on single-core systems.**
+    ///
+    /// The count is only modified while in a critical section, so updates to
+    /// it are never interrupted. On a single-core system, interrupts are
+    /// disabled for as long as the count is non-zero, so if the current
+    /// context observes a non-zero count, it must be the context which
+    /// entered the critical section, and it may safely skip entering
+    /// it again.
+    ///
+    /// On multi-core systems, the count is shared between all cores, so a
+    /// core may observe a non-zero count while *another* core is in the
+    /// critical section, and skip entering it. A `NestedCsRawMutex` **is not
+    /// sufficient** to ensure exclusive access on multi-core systems.
+    ///
+    /// [`with_lock`]: ScopedRawMutex::with_lock
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct NestedCsRawMutex {
+        inner: CriticalSectionRawMutex,
+    }
+
+    impl NestedCsRawMutex {
+        /// Create a new `NestedCsRawMutex`.
+        pub const fn new() -> Self {
+            Self {
+                inner: CriticalSectionRawMutex::new(),
+            }
+        }
+
+        /// Returns the number of times this mutex has been successfully
+        /// locked.
+        #[cfg(feature = "test-util")]
+        pub fn lock_count(&self) -> usize {
+            self.inner.lock_count()
+        }
+    }
+
+    impl ConstInit for NestedCsRawMutex {
+        const INIT: Self = Self::new();
+    }
+
+    /// A [`BlockingMutex`] backed by a [`NestedCsRawMutex`].
+    ///
+    /// This type is [`Send`] and [`Sync`] whenever `T` is [`Send`].
+    pub type NestedCsMutex<T> = BlockingMutex<NestedCsRawMutex, T>;
+
+    const _: fn() = || {
+        assert_send::<NestedCsMutex<u32>>();
+        assert_sync::<NestedCsMutex<u32>>();
+    };
+
+    unsafe impl ScopedRawMutex for NestedCsRawMutex {
+        #[inline]
+        fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            if DEPTH.load(Ordering::Relaxed) > 0 {
+                // We are on a single core, and interrupts are disabled while
+                // the count is non-zero, so the current context must already
+                // be in the critical section.
+                return self.try_with_lock_nested(f);
+            }
+            critical_section::with(|_| self.try_with_lock_nested(f))
+        }
+
+        #[inline]
+        fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            // In a critical section, it is not possible for another holder
+            // of this mutex to release, which means we have certainly
+            // reached deadlock if the lock was already locked.
+            self.try_with_lock(f)
+                .unwrap_or_else(|| deadlocked("Deadlocked"))
+        }
+
+        fn is_locked(&self) -> bool {
+            self.inner.is_locked()
+        }
+
+        fn is_locked_acquire(&self) -> bool {
+            self.inner.is_locked_acquire()
+        }
+    }
+
+    impl DetectsDeadlock for NestedCsRawMutex {}
+
+    impl NestedCsRawMutex {
+        /// Attempts to lock the mutex, incrementing the count while it is
+        /// held. This must only be called while in a critical section.
+        #[inline]
+        fn try_with_lock_nested<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            self.inner.try_with_lock_in_cs(|| {
+                // NOTE: separated load/stores are acceptable as we are in a
+                // critical section
+                let depth = DEPTH.load(Ordering::Relaxed);
+                DEPTH.store(depth + 1, Ordering::Relaxed);
+                // Using a drop guard ensures that the count is restored when
+                // the lock is released, even if `f()` panics.
+                let _restore = RestoreDepth(depth);
+                f()
+            })
+        }
+    }
+
+    /// Drop guard which restores the count to its previous value.
+    struct RestoreDepth(usize);
+
+    impl Drop for RestoreDepth {
+        #[inline(always)]
+        fn drop(&mut self) {
+            DEPTH.store(self.0, Ordering::Relaxed);
+        }
+    }
+}
+
+// ================
+
+#[cfg(all(feature = "impl-critical-section", cortex_m))]
+pub mod max_priority {
+    //! Critical Section based implementation which may only be locked at or
+    //! below a maximum interrupt priority
+
+    use super::cs::CriticalSectionRawMutex;
+    use super::*;
+
+    /// A [`CriticalSectionRawMutex`] which may only be locked from thread
+    /// mode, or from exceptions whose priority is no higher than `MAX_PRIO`.
+    ///
+    /// This is useful for data which must only be accessed below a certain
+    /// interrupt priority. It is a stronger check than that performed by the
+    /// [`ThreadModeRawMutex`], which only distinguishes between thread mode
+    /// and any exception. If the mutex is locked from an exception with a
+    /// higher priority than `MAX_PRIO`, [`try_with_lock`] returns `None`, and
+    /// [`with_lock`] panics.
+    ///
+    /// # Priorities
+    ///
+    /// On Cortex-M, a *numerically lower* priority value is a *higher*
+    /// priority. `MAX_PRIO` is a raw priority value, as written to the NVIC's
+    /// priority registers: as only the most significant bits of each priority
+    /// register are implemented, it must be shifted accordingly. For example,
+    /// on a device which implements 3 priority bits, logical priority 5 is
+    /// represented as `5 << 5`. The mutex may be locked from an exception
+    /// whose priority value is *greater than or equal to* `MAX_PRIO`.
+    ///
+    /// The current priority is determined by reading the number of the active
+    /// exception from the `ICSR` register, and then reading that exception's
+    /// priority from the `SHPR` registers (for system exceptions) or the
+    /// NVIC's `IPR` registers (for interrupts). NMI and HardFault have fixed
+    /// priorities higher than any configurable priority, and may never lock
+    /// the mutex. Note that this is the priority of the active exception, and
+    /// does not take into account priority boosting using the `PRIMASK` or
+    /// `BASEPRI` registers.
+    ///
+    /// Whether one exception preempts another is determined only by the
+    /// *group* priority bits of the priority values, as configured by the
+    /// `PRIGROUP` field of the `AIRCR` register; the remaining subpriority
+    /// bits are ignored. The priority grouping is read from `AIRCR` when the
+    /// mutex is locked, and only the group priority bits of `MAX_PRIO` and
+    /// the current priority are compared. The priority grouping should
+    /// therefore be configured before the mutex is first locked, and not
+    /// changed afterwards.
+    ///
+    /// [`ThreadModeRawMutex`]: super::single_core_thread_mode::ThreadModeRawMutex
+    /// [`try_with_lock`]: ScopedRawMutex::try_with_lock
+    /// [`with_lock`]: ScopedRawMutex::with_lock
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct MaxPriorityRawMutex<const MAX_PRIO: u8> {
+        inner: CriticalSectionRawMutex,
+    }
+
+    impl<const MAX_PRIO: u8> MaxPriorityRawMutex<MAX_PRIO> {
+        /// Create a new `MaxPriorityRawMutex`.
+        pub const fn new() -> Self {
+            Self {
+                inner: CriticalSectionRawMutex::new(),
+            }
+        }
+
+        /// Returns the number of times this mutex has been successfully
+        /// locked.
+        #[cfg(feature = "test-util")]
+        pub fn lock_count(&self) -> usize {
+            self.inner.lock_count()
+        }
+    }
+
+    impl<const MAX_PRIO: u8> ConstInit for MaxPriorityRawMutex<MAX_PRIO> {
+        const INIT: Self = Self::new();
+    }
+
+    /// A [`BlockingMutex`] backed by a [`MaxPriorityRawMutex`].
+    ///
+    /// This type is [`Send`] and [`Sync`] whenever `T` is [`Send`].
+    pub type MaxPriorityMutex<const MAX_PRIO: u8, T> =
+        BlockingMutex<MaxPriorityRawMutex<MAX_PRIO>, T>;
+
+    const _: fn() = || {
+        assert_send::<MaxPriorityMutex<0x80, u32>>();
+        assert_sync::<MaxPriorityMutex<0x80, u32>>();
+    };
+
+    unsafe impl<const MAX_PRIO: u8> ScopedRawMutex for MaxPriorityRawMutex<MAX_PRIO> {
+        #[inline]
+        fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            if !priority_at_most(MAX_PRIO) {
+                return None;
+            }
+            self.inner.try_with_lock(f)
+        }
+
+        #[inline]
+        fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            // In a critical section, it is not possible for another holder
+            // of this mutex to release, and the current priority will not
+            // change before we return, which means we have certainly reached
+            // deadlock if the lock could not be acquired.
+            self.try_with_lock(f).unwrap_or_else(|| {
+                deadlocked("Deadlocked or attempted to access above the maximum priority")
+            })
+        }
+
+        fn is_locked(&self) -> bool {
+            self.inner.is_locked()
+        }
+
+        fn is_locked_acquire(&self) -> bool {
+            self.inner.is_locked_acquire()
+        }
+    }
+
+    impl<const MAX_PRIO: u8> DetectsDeadlock for MaxPriorityRawMutex<MAX_PRIO> {}
+
+    /// Returns `true` if the current context is thread mode, or an exception
+    /// whose group priority is no higher than that of `max`.
+    fn priority_at_most(max: u8) -> bool {
+        let prio = match vectactive() as usize {
+            // Thread mode
+            0 => return true,
+            // Reset, NMI, and HardFault have fixed negative priorities
+            1..=3 => return false,
+            // SHPR1-3
+            n @ 4..=15 => read_priority(0xE000ED18, n - 4),
+            // NVIC_IPR0-n
+            n => read_priority(0xE000E400, n - 16),
+        };
+
+        // AIRCR.PRIGROUP: bits [PRIGROUP:0] of a priority value are the
+        // subpriority, which does not affect preemption.
+        let aircr = unsafe { (0xE000ED0C as *const u32).read_volatile() };
+        let prigroup = (aircr >> 8) & 0b111;
+        let group_mask = (0xFF_u32 << (prigroup + 1)) as u8;
+
+        prio & group_mask >= max & group_mask
+    }
+
+    /// Reads the `n`th byte of an array of priority registers starting at
+    /// `base`.
+    fn read_priority(base: usize, n: usize) -> u8 {
+        // NOTE: ARMv6-M only permits word accesses to these registers.
+        let word = unsafe { ((base + (n & !0b11)) as *const u32).read_volatile() };
+        (word >> ((n & 0b11) * 8)) as u8
+    }
+}
+
+// ================
+
+pub mod context {
+    //! Mutexes which may only be locked from a particular execution context
+    //!
+    //! This generalizes [`ThreadModeRawMutex`] to arbitrary execution
+    //! contexts, such as the interrupt in which an async executor runs (e.g.
+    //! an `embassy_executor::InterruptExecutor`). Tasks spawned on the same
+    //! executor never preempt each other, so data shared only between them
+    //! may be protected by a [`ContextRawMutex`] without disabling interrupts.
+    //!
+    //! [`ThreadModeRawMutex`]:
+    //!     super::single_core_thread_mode::ThreadModeRawMutex
+
+    use super::*;
+
+    /// An execution context, in which code does not run concurrently with
+    /// other code running in the same context.
+    ///
+    /// # Safety
+    ///
+    /// All code for which [`is_current`](Self::is_current) returns `true` must
+    /// run on a single core, and must never run concurrently with other code
+    /// for which it returns `true`. It may only *preempt* such code if it runs
+    /// to completion before the preempted code resumes, as an interrupt
+    /// handler does. For example, this holds for Cortex-M thread mode on a
+    /// single-core system, for a particular interrupt handler, as an
+    /// interrupt cannot preempt itself, or for thread mode together with a
+    /// particular interrupt handler.
+    pub unsafe trait ExecutionContext {
+        /// Returns `true` if the caller is running in this context.
+        fn is_current() -> bool;
+    }
+
+    /// A mutex which may only be locked from the execution context `C`.
+    ///
+    /// Attempting to lock the mutex from any other context fails, causing
+    /// [`with_lock`](ScopedRawMutex::with_lock) to panic. As the code in a
+    /// single context never preempts itself, no critical section is required.
+    ///
+    /// # Examples
+    ///
+    /// Using a mocked context, which is only current while a flag is set:
+    ///
+    /// ```
+    /// use core::sync::atomic::{AtomicBool, Ordering};
+    /// use mutex::BlockingMutex;
+    /// use mutex::raw_impls::context::{ContextRawMutex, ExecutionContext};
+    ///
+    /// static IN_EXECUTOR: AtomicBool = AtomicBool::new(false);
+    ///
+    /// struct Executor;
+    ///
+    /// // SAFETY: this example is single-threaded.
+    /// unsafe impl ExecutionContext for Executor {
+    ///     fn is_current() -> bool {
+    ///         IN_EXECUTOR.load(Ordering::Relaxed)
+    ///     }
+    /// }
+    ///
+    /// static DATA: BlockingMutex<ContextRawMutex<Executor>, u32> = BlockingMutex::new(0);
+    ///
+    /// // Outside of the executor, the mutex cannot be locked.
+    /// assert_eq!(DATA.try_with_lock(|data| *data), None);
+    ///
+    /// IN_EXECUTOR.store(true, Ordering::Relaxed);
+    /// DATA.with_lock(|data| *data += 1);
+    /// assert_eq!(DATA.try_with_lock(|data| *data), Some(1));
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// This relies on the guarantees provided by the [`ExecutionContext`]
+    /// implementation. For the contexts provided by this crate, **this mutex is
+    /// only safe on single-core systems.**
+    pub struct ContextRawMutex<C: ExecutionContext> {
+        taken: AtomicBool,
+        count: LockCount,
+        _context: PhantomData<fn() -> C>,
+    }
+
+    unsafe impl<C: ExecutionContext> Send for ContextRawMutex<C> {}
+    unsafe impl<C: ExecutionContext> Sync for ContextRawMutex<C> {}
+
+    impl<C: ExecutionContext> ContextRawMutex<C> {
+        /// Create a new `ContextRawMutex`.
+        pub const fn new() -> Self {
+            Self {
+                taken: AtomicBool::new(false),
+                count: LockCount::new(),
+                _context: PhantomData,
+            }
+        }
+
+        /// Returns the number of times this mutex has been successfully
+        /// locked.
+        #[cfg(feature = "test-util")]
+        pub fn lock_count(&self) -> usize {
+            self.count.get()
+        }
+    }
+
+    impl<C: ExecutionContext> ConstInit for ContextRawMutex<C> {
+        const INIT: Self = Self::new();
+    }
+
+    unsafe impl<C: ExecutionContext> ScopedRawMutex for ContextRawMutex<C> {
+        #[inline]
+        fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            run_locked(
+                || {
+                    if !C::is_current() {
+                        return false;
+                    }
+                    // NOTE: separated load/stores are acceptable as we checked
+                    // we are only accessed from a single context (checked
+                    // above)
+                    if self.taken.load(Ordering::Relaxed) {
+                        return false;
+                    }
+                    self.taken.store(true, Ordering::Relaxed);
+                    self.count.increment();
+                    true
+                },
+                // NOTE: `Release` pairs with the `Acquire` load in
+                // `is_locked_acquire`, which may be called from outside of the
+                // context.
+                || self.taken.store(false, Ordering::Release),
+                f,
+            )
+        }
+
+        #[inline]
+        fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            // Code in the context can only be preempted by other code in the
+            // same context which runs to completion, so it is not possible
+            // for another holder of this mutex to release, which means we
+            // have certainly reached deadlock if the lock was already locked.
+            self.try_with_lock(f).unwrap_or_else(|| {
+                deadlocked("Deadlocked or attempted to access outside of the execution context")
+            })
+        }
+
+        fn is_locked(&self) -> bool {
+            self.taken.load(Ordering::Relaxed)
+        }
+
+        fn is_locked_acquire(&self) -> bool {
+            self.taken.load(Ordering::Acquire)
+        }
+
+        /// This mutex can only be locked from its context, so it is owned by
+        /// the current context if it is locked and we are in that context.
+        fn is_owned(&self) -> bool {
+            C::is_current() && self.is_locked()
+        }
+    }
+
+    impl<C: ExecutionContext> DetectsDeadlock for ContextRawMutex<C> {}
+
+    impl<C: ExecutionContext> Drop for ContextRawMutex<C> {
+        fn drop(&mut self) {
+            // See the `Drop` impl for `ThreadModeRawMutex`: dropping drops the
+            // inner `T`, so it requires the same guarantees as locking.
+            assert!(
+                C::is_current(),
+                "ContextRawMutex can only be dropped from its execution context."
+            );
+        }
+    }
+
+    #[cfg(feature = "fmt")]
+    impl<C: ExecutionContext> core::fmt::Debug for ContextRawMutex<C> {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            f.debug_struct("ContextRawMutex")
+                .field("context", &core::any::type_name::<C>())
+                .field("taken", &self.taken)
+                .finish_non_exhaustive()
+        }
+    }
+
+    /// Cortex-M thread mode, the context in which `main` runs.
+    #[cfg(all(feature = "impl-unsafe-cortex-m-single-core", cortex_m))]
+    #[derive(Debug)]
+    pub struct ThreadMode;
+
+    // SAFETY: thread mode cannot preempt itself, and the
+    // `impl-unsafe-cortex-m-single-core` feature asserts that there is only a
+    // single core.
+    #[cfg(all(feature = "impl-unsafe-cortex-m-single-core", cortex_m))]
+    unsafe impl ExecutionContext for ThreadMode {
+        #[inline]
+        fn is_current() -> bool {
+            vectactive() == 0
+        }
+    }
+
+    /// The Cortex-M exception handler with exception number `N`.
+    ///
+    /// For device interrupts, `N` is the IRQ number plus 16. For example, this
+    /// may be used to share data between the tasks of an executor which runs
+    /// in a particular interrupt handler.
+    #[cfg(all(feature = "impl-unsafe-cortex-m-single-core", cortex_m))]
+    #[derive(Debug)]
+    pub struct Exception<const N: u16>;
+
+    // SAFETY: an exception handler cannot preempt itself, and the
+    // `impl-unsafe-cortex-m-single-core` feature asserts that there is only a
+    // single core.
+    #[cfg(all(feature = "impl-unsafe-cortex-m-single-core", cortex_m))]
+    unsafe impl<const N: u16> ExecutionContext for Exception<N> {
+        #[inline]
+        fn is_current() -> bool {
+            vectactive() == u32::from(N)
+        }
+    }
+
+    /// Cortex-M thread mode, *or* the exception handler with exception number
+    /// `N`.
+    ///
+    /// This is used by [`ThreadAndExceptionRawMutex`] to share data strictly
+    /// between thread mode and one designated exception handler (such as
+    /// PendSV), while excluding all other exceptions.
+    #[cfg(all(feature = "impl-unsafe-cortex-m-single-core", cortex_m))]
+    #[derive(Debug)]
+    pub struct ThreadModeOrException<const N: u16>;
+
+    // SAFETY: the exception handler may preempt thread mode, but it always
+    // runs to completion before thread mode resumes, and thread mode cannot
+    // preempt it. The `impl-unsafe-cortex-m-single-core` feature asserts that
+    // there is only a single core.
+    #[cfg(all(feature = "impl-unsafe-cortex-m-single-core", cortex_m))]
+    unsafe impl<const N: u16> ExecutionContext for ThreadModeOrException<N> {
+        #[inline]
+        fn is_current() -> bool {
+            let vect = vectactive();
+            vect == 0 || vect == u32::from(N)
+        }
+    }
+
+    /// A mutex which may only be locked from thread mode, or from the exception
+    /// handler with exception number `VECT`.
+    ///
+    /// This is determined by reading the `VECTACTIVE` field of the `ICSR`
+    /// register, which is 0 in thread mode, and is otherwise the number of
+    /// the active exception (for device interrupts, the IRQ number plus 16).
+    /// Locking the mutex from any other exception handler fails, causing
+    /// [`with_lock`](ScopedRawMutex::with_lock) to panic.
+    ///
+    /// # Preemption
+    ///
+    /// No critical section is entered, so the exception handler may preempt
+    /// thread mode while thread mode holds the lock. The handler then cannot
+    /// acquire the lock, as thread mode cannot release it until the handler
+    /// returns, and so [`with_lock`](ScopedRawMutex::with_lock) panics, as it
+    /// would on a deadlock. This is only sound, and deadlock-free, under the
+    /// following assumptions:
+    ///
+    /// - **The system is single-core.** The exception handler must never run
+    ///   concurrently with thread mode, only preempt it.
+    /// - The exception handler does not run while thread mode holds the lock.
+    ///   For example, thread mode may only lock the mutex while the exception
+    ///   is masked (e.g. using `BASEPRI`), or the exception may only be
+    ///   triggered by thread mode itself (e.g. by pending PendSV) outside of
+    ///   the critical section. Alternatively, the exception handler may use
+    ///   [`try_with_lock`](ScopedRawMutex::try_with_lock) and handle the case
+    ///   where the lock is held by thread mode.
+    #[cfg(all(feature = "impl-unsafe-cortex-m-single-core", cortex_m))]
+    pub type ThreadAndExceptionRawMutex<const VECT: u16> =
+        ContextRawMutex<ThreadModeOrException<VECT>>;
+}
+
+// ================
+
+#[cfg(all(feature = "impl-ldrex", cortex_m, any(armv7m, armv8m)))]
+pub mod ldrex {
+    //! A spinlock implemented directly using the Cortex-M exclusive monitor
+    //!
+    //! # Exclusive monitor and context switches
+    //!
+    //! A failed or abandoned `LDREX` leaves the local exclusive monitor in the
+    //! exclusive-access state. [`LdrexRawMutex`] always executes `CLREX` when
+    //! it observes that the lock is already held, and retries when a `STREX`
+    //! fails (e.g. because an interrupt cleared the monitor between the
+    //! `LDREX` and `STREX`).
+    //!
+    //! On Cortex-M, the local monitor is cleared by exception entry and
+    //! return, so context switches performed in an exception handler (such as
+    //! `PendSV`) need not do anything further. Any code which switches between
+    //! execution contexts *without* an exception entry or return must execute
+    //! `CLREX` itself, otherwise a `STREX` in the new context may succeed
+    //! based on an `LDREX` performed in the old one.
+
+    use super::*;
+    use core::arch::asm;
+    use core::sync::atomic::{fence, AtomicU32};
+    use mutex_traits::RawMutex;
+
+    const UNLOCKED: u32 = 0;
+    const LOCKED: u32 = 1;
+
+    /// A spinlock using the `LDREX`/`STREX` exclusive access instructions
+    /// directly.
+    ///
+    /// # Deadlocks
+    ///
+    /// [`RawMutex::lock`] spins until the lock is released. If the lock is held
+    /// by a context that cannot run until the current one completes (e.g.
+    /// thread mode code interrupted by an interrupt handler that attempts to
+    /// lock the same mutex), this will deadlock. In such cases, use
+    /// [`RawMutex::try_lock`] instead, or use a critical-section based mutex.
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct LdrexRawMutex {
+        state: AtomicU32,
+        count: LockCount,
+    }
+
+    impl LdrexRawMutex {
+        /// Create a new `LdrexRawMutex`.
+        pub const fn new() -> Self {
+            Self {
+                state: AtomicU32::new(UNLOCKED),
+                count: LockCount::new(),
+            }
+        }
+
+        /// Returns the number of times this mutex has been successfully
+        /// locked.
+        #[cfg(feature = "test-util")]
+        pub fn lock_count(&self) -> usize {
+            self.count.get()
+        }
+    }
+
+    impl ConstInit for LdrexRawMutex {
+        const INIT: Self = Self::new();
+    }
+
+    unsafe impl RawMutex for LdrexRawMutex {
+        type GuardMarker = ();
+
+        #[inline]
+        fn lock(&self) {
+            while !self.try_lock() {
+                core::hint::spin_loop();
+            }
+        }
+
+        #[inline]
+        fn try_lock(&self) -> bool {
+            let ptr = self.state.as_ptr();
+            loop {
+                // SAFETY: `ptr` points to the lock state, which is valid for
+                // the lifetime of `self`.
+                let state = unsafe { ldrex(ptr) };
+                if state != UNLOCKED {
+                    // Clear the monitor, so that the abandoned `LDREX` cannot
+                    // pair with an unrelated `STREX` later on.
+                    clrex();
+                    return false;
+                }
+
+                // SAFETY: as above.
+                if unsafe { strex(LOCKED, ptr) } {
+                    fence(Ordering::Acquire);
+                    self.count.increment();
+                    return true;
+                }
+                // The exclusive store failed, because the monitor was cleared
+                // since the `LDREX` (e.g. by an interrupt). Try again.
+            }
+        }
+
+        #[inline]
+        unsafe fn unlock(&self) {
+            self.state.store(UNLOCKED, Ordering::Release);
+        }
+
+        #[inline]
+        fn is_locked(&self) -> bool {
+            self.state.load(Ordering::Relaxed) != UNLOCKED
+        }
+
+        #[inline]
+        fn is_locked_acquire(&self) -> bool {
+            self.state.load(Ordering::Acquire) != UNLOCKED
+        }
+    }
+
+    /// Performs an exclusive load of the word at `ptr`.
+    #[inline(always)]
+    unsafe fn ldrex(ptr: *mut u32) -> u32 {
+        let value: u32;
+        asm!(
+            "ldrex {value}, [{ptr}]",
+            ptr = in(reg) ptr,
+            value = out(reg) value,
+            options(nostack, preserves_flags),
+        );
+        value
+    }
+
+    /// Performs an exclusive store of `value` to the word at `ptr`, returning
+    /// `true` if the store succeeded.
+    #[inline(always)]
+    unsafe fn strex(value: u32, ptr: *mut u32) -> bool {
+        let failed: u32;
+        asm!(
+            "strex {failed}, {value}, [{ptr}]",
+            ptr = in(reg) ptr,
+            value = in(reg) value,
+            failed = out(reg) failed,
+            options(nostack, preserves_flags),
+        );
+        failed == 0
+    }
+
+    /// Clears the local exclusive monitor.
+    #[inline(always)]
+    fn clrex() {
+        unsafe { asm!("clrex", options(nostack, preserves_flags)) };
+    }
+}
+
+// ================
+
+// Requires atomic compare-and-swap, which is emulated by `portable-atomic` on
+// ARMv6-M.
+#[cfg(all(
+    feature = "impl-wfe",
+    cortex_m,
+    any(target_has_atomic = "8", feature = "portable-atomic")
+))]
+pub mod wfe {
+    //! A low-power spinlock which waits for events using `WFE`/`SEV`
+    //!
+    //! # Event register
+    //!
+    //! Each core has a single-bit event register. `WFE` ("wait for event")
+    //! clears the event register and returns immediately if it was set, and
+    //! otherwise puts the core into a low-power state until an event occurs.
+    //! `SEV` ("send event") sets the event register on every core, waking any
+    //! core waiting in `WFE`.
+    //!
+    //! [`WfeRawMutex`] executes `SEV` after releasing the lock. As the event
+    //! register latches the event, a waiter which observes the lock as held
+    //! and then executes `WFE` *after* the lock has been released and `SEV`
+    //! executed returns immediately, rather than missing the wakeup.
+    //!
+    //! `WFE` may also return for other reasons, such as an interrupt, or a
+    //! `SEV` executed by unrelated code. Such a spurious wakeup simply retries
+    //! acquiring the lock, and waits again if it is still held.
+
+    use super::rmw::AtomicBool;
+    use super::*;
+    use core::arch::asm;
+    use mutex_traits::RawMutex;
+
+    /// A spinlock which puts waiting cores into a low-power state using `WFE`,
+    /// and wakes them using `SEV` when the lock is released.
+    ///
+    /// This is intended for multi-core Cortex-M systems, where it uses less
+    /// power than a busy-waiting spinlock while a core waits for another core
+    /// to release the lock. See the [module-level documentation](self) for
+    /// details on event-based waiting.
+    ///
+    /// # Deadlocks
+    ///
+    /// [`RawMutex::lock`] waits until the lock is released. If the lock is held
+    /// by a context that cannot run until the current one completes (e.g.
+    /// thread mode code interrupted by an interrupt handler that attempts to
+    /// lock the same mutex), this will deadlock. In such cases, use
+    /// [`RawMutex::try_lock`] instead, or use a critical-section based mutex.
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct WfeRawMutex {
+        locked: AtomicBool,
+        count: LockCount,
+    }
+
+    /// A [`BlockingMutex`] backed by a [`WfeRawMutex`].
+    ///
+    /// This type is [`Send`] and [`Sync`] whenever `T` is [`Send`].
+    pub type WfeMutex<T> = BlockingMutex<WfeRawMutex, T>;
+
+    const _: fn() = || {
+        assert_send::<WfeMutex<u32>>();
+        assert_sync::<WfeMutex<u32>>();
+    };
+
+    impl WfeRawMutex {
+        /// Create a new `WfeRawMutex`.
+        pub const fn new() -> Self {
+            Self {
+                locked: AtomicBool::new(false),
+                count: LockCount::new(),
+            }
+        }
+
+        /// Returns the number of times this mutex has been successfully
+        /// locked.
+        #[cfg(feature = "test-util")]
+        pub fn lock_count(&self) -> usize {
+            self.count.get()
+        }
+    }
+
+    impl ConstInit for WfeRawMutex {
+        const INIT: Self = Self::new();
+    }
+
+    unsafe impl RawMutex for WfeRawMutex {
+        type GuardMarker = ();
+
+        #[inline]
+        fn lock(&self) {
+            while !self.try_lock() {
+                // If the lock was released (and `SEV` executed) since the
+                // failed attempt, the event register is set, and this
+                // returns immediately.
+                wfe();
+            }
+        }
+
+        #[inline]
+        fn try_lock(&self) -> bool {
+            let acquired = self
+                .locked
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok();
+            if acquired {
+                self.count.increment();
+            }
+            acquired
+        }
+
+        #[inline]
+        unsafe fn unlock(&self) {
+            self.locked.store(false, Ordering::Release);
+            sev();
+        }
+
+        #[inline]
+        fn is_locked(&self) -> bool {
+            self.locked.load(Ordering::Relaxed)
+        }
+
+        #[inline]
+        fn is_locked_acquire(&self) -> bool {
+            self.locked.load(Ordering::Acquire)
+        }
+    }
+
+    /// Waits for an event.
+    #[inline(always)]
+    fn wfe() {
+        unsafe { asm!("wfe", options(nostack, preserves_flags)) };
+    }
+
+    /// Sends an event to all cores, after ensuring that all prior memory
+    /// accesses (i.e. the store releasing the lock) have completed, so that
+    /// woken cores observe the lock as released.
+    #[inline(always)]
+    fn sev() {
+        unsafe { asm!("dsb", "sev", options(nostack, preserves_flags)) };
+    }
+}
+
+// ================
+
+#[cfg(feature = "std")]
+pub mod std_sync {
+    //! [`std::sync`]-based implementation
+
+    use super::LockCount;
+    use crate::BlockingMutex;
+    use mutex_traits::{ConstInit, RawMutex, ScopedRawMutex};
+    use std::sync::{Condvar, Mutex, MutexGuard, PoisonError, TryLockError};
+
+    /// A [`RawMutex`] which blocks the current thread using the primitives in
+    /// [`std::sync`].
+    ///
+    /// Threads waiting to acquire the lock are parked on a [`Condvar`], rather
+    /// than spinning. This is intended for use on hosted targets, such as when
+    /// running tests, where a `critical-section` implementation may not be
+    /// desirable.
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct StdRawMutex {
+        taken: Mutex<bool>,
+        unlocked: Condvar,
+        count: LockCount,
+    }
+
+    impl StdRawMutex {
+        /// Create a new `StdRawMutex`.
+        pub const fn new() -> Self {
+            Self {
+                taken: Mutex::new(false),
+                unlocked: Condvar::new(),
+                count: LockCount::new(),
+            }
+        }
+
+        /// Returns the number of times this mutex has been successfully
+        /// locked.
+        #[cfg(feature = "test-util")]
+        pub fn lock_count(&self) -> usize {
+            self.count.get()
+        }
+    }
+
+    impl ConstInit for StdRawMutex {
+        const INIT: Self = Self::new();
+    }
+
+    unsafe impl RawMutex for StdRawMutex {
+        // The lock state is not tied to the thread which acquired it, so it may
+        // be released from any thread.
+        type GuardMarker = ();
+
+        fn lock(&self) {
+            // NOTE: the inner `Mutex` is never held while user code runs, so
+            // it may only be poisoned by a panic within this module. It is
+            // always left in a consistent state, so poisoning is ignored.
+            let mut taken = self.taken.lock().unwrap_or_else(PoisonError::into_inner);
+            while *taken {
+                taken = self
+                    .unlocked
+                    .wait(taken)
+                    .unwrap_or_else(PoisonError::into_inner);
+            }
+            *taken = true;
+            self.count.increment();
+        }
+
+        fn try_lock(&self) -> bool {
+            let mut taken = self.taken.lock().unwrap_or_else(PoisonError::into_inner);
+            if *taken {
+                return false;
+            }
+            *taken = true;
+            self.count.increment();
+            true
+        }
+
+        unsafe fn unlock(&self) {
+            *self.taken.lock().unwrap_or_else(PoisonError::into_inner) = false;
+            self.unlocked.notify_one();
+        }
+
+        fn is_locked(&self) -> bool {
+            *self.taken.lock().unwrap_or_else(PoisonError::into_inner)
+        }
+    }
+
+    /// A [`ScopedRawMutex`] which holds a [`std::sync::Mutex`] while the
+    /// closure passed to [`with_lock`] runs.
+    ///
+    /// Unlike [`StdRawMutex`], the inner [`Mutex`] is held while user code
+    /// runs, so a panic inside the critical section poisons it. As
+    /// `BlockingMutex` does not implement poisoning, this poison would
+    /// otherwise be lost, and other threads would go on to observe data
+    /// which may have been left in an inconsistent state by the panic.
+    ///
+    /// # Poisoning
+    ///
+    /// By default, a mutex created with [`StdScopedRawMutex::new`] (or
+    /// [`ConstInit::INIT`]) panics with the message "underlying std Mutex is
+    /// poisoned" when it is locked after a previous critical section
+    /// panicked, including from [`try_with_lock`]. Poison is never treated
+    /// as the mutex being locked.
+    ///
+    /// A mutex created with [`StdScopedRawMutex::ignore_poison`] instead
+    /// recovers from poisoning, and locks the mutex as normal, in the same
+    /// way as [`PoisonError::into_inner`]. This is appropriate when the
+    /// protected data is always left in a consistent state, even if a
+    /// critical section panics.
+    ///
+    /// [`with_lock`]: ScopedRawMutex::with_lock
+    /// [`try_with_lock`]: ScopedRawMutex::try_with_lock
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct StdScopedRawMutex {
+        inner: Mutex<()>,
+        ignore_poison: bool,
+        count: LockCount,
+    }
+
+    impl StdScopedRawMutex {
+        /// Create a new `StdScopedRawMutex`, which panics if it is locked
+        /// after being poisoned.
+        pub const fn new() -> Self {
+            Self {
+                inner: Mutex::new(()),
+                ignore_poison: false,
+                count: LockCount::new(),
+            }
+        }
+
+        /// Create a new `StdScopedRawMutex`, which ignores poisoning and
+        /// locks the mutex as normal after a critical section has panicked.
+        pub const fn ignore_poison() -> Self {
+            Self {
+                inner: Mutex::new(()),
+                ignore_poison: true,
+                count: LockCount::new(),
+            }
+        }
+
+        /// Returns `true` if a critical section has panicked while holding
+        /// this mutex.
+        ///
+        /// This returns `true` regardless of whether the mutex ignores
+        /// poisoning.
+        pub fn is_poisoned(&self) -> bool {
+            self.inner.is_poisoned()
+        }
+
+        /// Returns the number of times this mutex has been successfully
+        /// locked.
+        #[cfg(feature = "test-util")]
+        pub fn lock_count(&self) -> usize {
+            self.count.get()
+        }
+
+        #[track_caller]
+        fn recover<'a>(&self, err: PoisonError<MutexGuard<'a, ()>>) -> MutexGuard<'a, ()> {
+            if !self.ignore_poison {
+                panic!("underlying std Mutex is poisoned");
+            }
+            err.into_inner()
+        }
+    }
+
+    impl ConstInit for StdScopedRawMutex {
+        const INIT: Self = Self::new();
+    }
+
+    unsafe impl ScopedRawMutex for StdScopedRawMutex {
+        #[track_caller]
+        fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            let guard = match self.inner.try_lock() {
+                Ok(guard) => guard,
+                Err(TryLockError::Poisoned(err)) => self.recover(err),
+                Err(TryLockError::WouldBlock) => return None,
+            };
+            self.count.increment();
+            let ret = f();
+            // Release the lock before returning the value, so that it is not
+            // dropped while the lock is held.
+            drop(guard);
+            Some(ret)
+        }
+
+        #[track_caller]
+        fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            let guard = self.inner.lock().unwrap_or_else(|err| self.recover(err));
+            self.count.increment();
+            let ret = f();
+            drop(guard);
+            ret
+        }
+
+        fn is_locked(&self) -> bool {
+            matches!(self.inner.try_lock(), Err(TryLockError::WouldBlock))
+        }
+    }
+
+    /// Converts a [`std::sync::Mutex`] into a `BlockingMutex`, to ease
+    /// incremental migration from code using [`std::sync::Mutex`].
+    ///
+    /// If the source mutex was poisoned, the data is recovered, and the
+    /// returned `BlockingMutex` is not poisoned (as `BlockingMutex` does not
+    /// implement poisoning).
+    impl<T> From<Mutex<T>> for BlockingMutex<StdRawMutex, T> {
+        fn from(mutex: Mutex<T>) -> Self {
+            Self::new(mutex.into_inner().unwrap_or_else(PoisonError::into_inner))
+        }
+    }
+
+    impl<T> BlockingMutex<StdRawMutex, T> {
+        /// Converts this mutex into a [`std::sync::Mutex`] protecting the
+        /// same data.
+        ///
+        /// The returned mutex is unlocked and not poisoned.
+        pub fn into_std(self) -> Mutex<T> {
+            Mutex::new(self.into_inner())
+        }
+    }
+}
+
+// ================
+
+#[cfg(all(feature = "linux-futex", target_os = "linux"))]
+pub mod futex {
+    //! Linux `futex`-based implementation
+
+    use super::*;
+    use core::sync::atomic::AtomicU32;
+    use mutex_traits::RawMutex;
+
+    /// The mutex is unlocked.
+    const UNLOCKED: u32 = 0;
+    /// The mutex is locked, and no threads are waiting for it.
+    const LOCKED: u32 = 1;
+    /// The mutex is locked, and threads may be waiting for it.
+    const CONTENDED: u32 = 2;
+
+    /// A [`RawMutex`] which puts waiting threads to sleep using the Linux
+    /// `futex` system call.
+    ///
+    /// The state of the mutex is stored in an [`AtomicU32`], which is either
+    /// unlocked, locked, or locked with (possibly) waiting threads. Locking
+    /// an unlocked mutex, and unlocking a mutex with no waiters, only require
+    /// a single atomic operation, and do not make a system call. Threads
+    /// which must wait for the lock mark it as contended, and sleep using
+    /// `FUTEX_WAIT`; unlocking a contended mutex wakes one of them using
+    /// `FUTEX_WAKE`.
+    ///
+    /// Unlike [`std::sync::Mutex`], this mutex does not implement poisoning,
+    /// and does not require the `std` feature.
+    ///
+    /// [`std::sync::Mutex`]: https://doc.rust-lang.org/stable/std/sync/struct.Mutex.html
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct FutexRawMutex {
+        state: AtomicU32,
+        count: LockCount,
+    }
+
+    impl FutexRawMutex {
+        /// Create a new `FutexRawMutex`.
+        pub const fn new() -> Self {
+            Self {
+                state: AtomicU32::new(UNLOCKED),
+                count: LockCount::new(),
+            }
+        }
+
+        /// Returns the number of times this mutex has been successfully
+        /// locked.
+        #[cfg(feature = "test-util")]
+        pub fn lock_count(&self) -> usize {
+            self.count.get()
+        }
+
+        #[cold]
+        fn lock_contended(&self) {
+            // Mark the mutex as contended, so that the thread holding it
+            // wakes us when it is unlocked. If it was unlocked in the
+            // meantime, we now hold it (and will wake a thread when unlocking
+            // it, which may be unnecessary, but is harmless).
+            while self.state.swap(CONTENDED, Ordering::Acquire) != UNLOCKED {
+                self.wait(CONTENDED);
+            }
+        }
+
+        /// Sleeps until woken, if the state is still `expected`.
+        fn wait(&self, expected: u32) {
+            // SAFETY: `FUTEX_WAIT` only reads the futex word, which is a
+            // valid `u32` for as long as `self` is borrowed.
+            unsafe {
+                libc::syscall(
+                    libc::SYS_futex,
+                    self.state.as_ptr(),
+                    libc::FUTEX_WAIT | libc::FUTEX_PRIVATE_FLAG,
+                    expected,
+                    core::ptr::null::<libc::timespec>(),
+                );
+            }
+        }
+
+        /// Wakes one thread sleeping in `wait`.
+        fn wake_one(&self) {
+            // SAFETY: `FUTEX_WAKE` does not access the futex word.
+            unsafe {
+                libc::syscall(
+                    libc::SYS_futex,
+                    self.state.as_ptr(),
+                    libc::FUTEX_WAKE | libc::FUTEX_PRIVATE_FLAG,
+                    1,
+                );
+            }
+        }
+    }
+
+    impl ConstInit for FutexRawMutex {
+        const INIT: Self = Self::new();
+    }
+
+    /// A [`BlockingMutex`] backed by a [`FutexRawMutex`].
+    ///
+    /// This type is [`Send`] and [`Sync`] whenever `T` is [`Send`].
+    pub type FutexMutex<T> = BlockingMutex<FutexRawMutex, T>;
+
+    const _: fn() = || {
+        assert_send::<FutexMutex<u32>>();
+        assert_sync::<FutexMutex<u32>>();
+    };
+
+    unsafe impl RawMutex for FutexRawMutex {
+        type GuardMarker = ();
+
+        #[inline]
+        fn lock(&self) {
+            if !self.try_lock() {
+                self.lock_contended();
+                self.count.increment();
+            }
+        }
+
+        #[inline]
+        fn try_lock(&self) -> bool {
+            let locked = self
+                .state
+                .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok();
+            if locked {
+                self.count.increment();
+            }
+            locked
+        }
+
+        #[inline]
+        unsafe fn unlock(&self) {
+            if self.state.swap(UNLOCKED, Ordering::Release) == CONTENDED {
+                self.wake_one();
+            }
+        }
+
+        #[inline]
+        fn is_locked(&self) -> bool {
+            self.state.load(Ordering::Relaxed) != UNLOCKED
+        }
+
+        #[inline]
+        fn is_locked_acquire(&self) -> bool {
+            self.state.load(Ordering::Acquire) != UNLOCKED
+        }
+    }
+}
+
+// ================
+
+#[cfg(feature = "impl-rtic")]
+pub mod rtic {
+    //! [RTIC](https://rtic.rs) resource based implementation
+
+    use super::*;
+    use core::cell::RefCell;
+
+    /// A [`ScopedRawMutex`] which locks an RTIC shared resource.
+    ///
+    /// RTIC shared resources are accessed through proxies implementing
+    /// [`rtic_core::Mutex`], whose `lock` method raises the current priority
+    /// to the resource's ceiling for the duration of a closure. This adapter
+    /// maps [`ScopedRawMutex::with_lock`] onto that `lock` method, so that an
+    /// RTIC resource may be used with code written against this crate's
+    /// traits. As the ceiling is computed by RTIC at compile time, no
+    /// additional configuration is required.
+    ///
+    /// Typically, the wrapped resource has type `()`, and is used only to
+    /// provide the priority ceiling for data held in a `BlockingMutex`. The
+    /// resource's data may also be accessed using
+    /// [`with_resource`](Self::with_resource).
+    ///
+    /// As RTIC resource proxies are local to a task, this type is not
+    /// [`Sync`]. Locking it reentrantly (e.g. from within the closure passed to
+    /// `with_lock`) is a deadlock, and will panic.
+    ///
+    /// [`rtic_core::Mutex`]: https://docs.rs/rtic-core/1/rtic_core/trait.Mutex.html
+    pub struct RticRawMutex<M> {
+        resource: RefCell<M>,
+        count: LockCount,
+    }
+
+    impl<M: rtic_core::Mutex> RticRawMutex<M> {
+        /// Create a new `RticRawMutex` wrapping the provided resource proxy.
+        pub const fn new(resource: M) -> Self {
+            Self {
+                resource: RefCell::new(resource),
+                count: LockCount::new(),
+            }
+        }
+
+        /// Locks the resource, and calls `f` with a mutable reference to its
+        /// data.
+        ///
+        /// Returns `None` if the resource is already locked through this
+        /// `RticRawMutex`.
+        pub fn with_resource<U>(&self, f: impl FnOnce(&mut M::T) -> U) -> Option<U> {
+            let mut resource = self.resource.try_borrow_mut().ok()?;
+            self.count.increment();
+            Some(resource.lock(f))
+        }
+
+        /// Consumes the `RticRawMutex`, returning the wrapped resource proxy.
+        pub fn into_inner(self) -> M {
+            self.resource.into_inner()
+        }
+
+        /// Returns the number of times this mutex has been successfully
+        /// locked.
+        #[cfg(feature = "test-util")]
+        pub fn lock_count(&self) -> usize {
+            self.count.get()
+        }
+    }
+
+    unsafe impl<M: rtic_core::Mutex> ScopedRawMutex for RticRawMutex<M> {
+        #[inline]
+        fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            self.with_resource(|_| f())
+        }
+
+        #[inline]
+        fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            // RTIC resources may only be locked once per task, so if this
+            // resource is already borrowed, we have certainly reached
+            // deadlock.
+            self.try_with_lock(f)
+                .unwrap_or_else(|| deadlocked("Deadlocked"))
+        }
+
+        fn is_locked(&self) -> bool {
+            self.resource.try_borrow_mut().is_err()
+        }
+
+        /// As this mutex is `!Sync`, it can only be locked by the current
+        /// task, so it is owned by the current context whenever it is locked.
+        fn is_owned(&self) -> bool {
+            self.is_locked()
+        }
+    }
+
+    impl<M: rtic_core::Mutex> DetectsDeadlock for RticRawMutex<M> {}
+
+    /// Allows a `BlockingMutex` to be passed to code written against RTIC's
+    /// [`rtic_core::Mutex`] trait.
+    ///
+    /// [`rtic_core::Mutex`]: https://docs.rs/rtic-core/1/rtic_core/trait.Mutex.html
+    impl<R: ScopedRawMutex, T> rtic_core::Mutex for &BlockingMutex<R, T> {
+        type T = T;
+
+        fn lock<U>(&mut self, f: impl FnOnce(&mut T) -> U) -> U {
+            self.with_lock(f)
+        }
+    }
+
+    #[cfg(feature = "fmt")]
+    impl<M> core::fmt::Debug for RticRawMutex<M> {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            f.debug_struct("RticRawMutex")
+                .field("locked", &self.resource.try_borrow_mut().is_err())
+                .finish_non_exhaustive()
+        }
+    }
+}
+
+// ================
+
+#[cfg(feature = "impl-lock_api-0_4")]
+pub mod lock_api_0_4 {
+    //! [`lock_api`](https://crates.io/crates/lock_api) v0.4 [`RawMutex`]
+    //! implementation.
+
+    use super::LockCount;
+    use ::lock_api_0_4 as lock_api;
+    use mutex_traits::{ConstInit, RawMutex};
+
+    /// [`lock_api`](https://crates.io/crates/lock_api) v0.4 [`RawMutex`]
+    /// implementation.
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct LockApiRawMutex<T>(T, LockCount);
+
+    impl<T: lock_api::RawMutex> ConstInit for LockApiRawMutex<T> {
+        const INIT: Self = LockApiRawMutex::new(T::INIT);
+    }
+
+    impl<T> LockApiRawMutex<T> {
+        /// Create a new `LockApiRawMutex` wrapping the provided
+        /// [`lock_api::RawMutex`].
+        ///
+        /// This is a `const fn`, so that a `BlockingMutex` using a
+        /// `LockApiRawMutex` may be constructed in a `static` using
+        /// [`BlockingMutex::const_new`](crate::BlockingMutex::const_new).
+        pub const fn new(inner: T) -> Self {
+            LockApiRawMutex(inner, LockCount::new())
+        }
+
+        /// Returns the number of times this mutex has been successfully
+        /// locked.
+        #[cfg(feature = "test-util")]
+        pub fn lock_count(&self) -> usize {
+            self.1.get()
+        }
+    }
+
+    unsafe impl<T: lock_api::RawMutex> RawMutex for LockApiRawMutex<T> {
+        type GuardMarker = <T as lock_api::RawMutex>::GuardMarker;
+
+        #[inline]
+        #[track_caller]
+        fn lock(&self) {
+            self.0.lock();
+            self.1.increment();
+        }
+
+        #[inline]
+        #[track_caller]
+        fn try_lock(&self) -> bool {
+            if self.0.try_lock() {
+                self.1.increment();
+                true
+            } else {
+                false
+            }
+        }
+
+        #[inline]
+        #[track_caller]
+        unsafe fn unlock(&self) {
+            self.0.unlock()
+        }
+
+        #[inline]
+        #[track_caller]
+        fn is_locked(&self) -> bool {
+            self.0.is_locked()
+        }
+    }
+}
+
+// ================
+
+// Requires atomic compare-and-swap, as the child may be taken without holding
+// the parent lock.
+#[cfg(any(target_has_atomic = "8", feature = "portable-atomic"))]
+pub mod hierarchical {
+    //! Child locks which defer to a coarse-grained parent lock
+
+    use super::rmw::AtomicBool;
+    use super::*;
+
+    /// A child mutex which defers to a parent [`ScopedRawMutex`].
+    ///
+    /// Locking a `HierarchicalRawMutex` normally locks its parent for the
+    /// duration of the critical section. However, when the parent is already
+    /// held through [`HierarchicalRawMutex::with_parent_lock`], locking the
+    /// child does not lock the parent again, and only marks the child itself
+    /// as taken. This avoids redundant locking in hot paths where the parent
+    /// lock is known to be held.
+    ///
+    /// The child always tracks whether it is taken, so it is never possible to
+    /// obtain two overlapping locks on the same child, even while the parent is
+    /// held.
+    ///
+    /// # Constraints
+    ///
+    /// Whether the parent is held is recorded in the child, and the child has
+    /// no way of knowing *which* context holds the parent. While the parent is
+    /// held through [`with_parent_lock`], *any* context locking the child will
+    /// skip locking the parent. Therefore, [`with_parent_lock`] should only be
+    /// used when no other context may lock the child until it returns. For
+    /// example, this is the case if the parent is a [`CriticalSectionRawMutex`]
+    /// on a single-core system, or if the child is only ever used from a
+    /// single thread.
+    ///
+    /// Violating this constraint does not permit concurrent access to the
+    /// child's data, but it does permit the child to be locked by one context
+    /// while another holds the parent.
+    ///
+    /// [`with_parent_lock`]: HierarchicalRawMutex::with_parent_lock
+    /// [`CriticalSectionRawMutex`]: super::cs::CriticalSectionRawMutex
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct HierarchicalRawMutex<'p, P> {
+        parent: &'p P,
+        parent_held: AtomicBool,
+        taken: AtomicBool,
+        count: LockCount,
+    }
+
+    impl<'p, P: ScopedRawMutex> HierarchicalRawMutex<'p, P> {
+        /// Create a new `HierarchicalRawMutex` with the provided parent.
+        pub const fn new(parent: &'p P) -> Self {
+            Self {
+                parent,
+                parent_held: AtomicBool::new(false),
+                taken: AtomicBool::new(false),
+                count: LockCount::new(),
+            }
+        }
+
+        /// Returns the number of times this mutex has been successfully
+        /// locked.
+        #[cfg(feature = "test-util")]
+        pub fn lock_count(&self) -> usize {
+            self.count.get()
+        }
+
+        /// Returns a reference to this mutex's parent.
+        pub fn parent(&self) -> &'p P {
+            self.parent
+        }
+
+        /// Locks the parent mutex, and calls `f()` while it is held.
+        ///
+        /// While `f()` runs, locking this child mutex will not lock the parent
+        /// again. See the [type-level documentation](Self#constraints) for the
+        /// constraints on using this method.
+        ///
+        /// Behavior when the parent is already locked is dependent on the
+        /// behavior of the parent's [`ScopedRawMutex::with_lock`].
+        pub fn with_parent_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            self.parent.with_lock(|| {
+                run_locked(
+                    || !self.parent_held.swap(true, Ordering::Acquire),
+                    || self.parent_held.store(false, Ordering::Release),
+                    f,
+                )
+                .unwrap_or_else(|| deadlocked("Deadlocked"))
+            })
+        }
+
+        fn take(&self) -> bool {
+            let taken = self
+                .taken
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok();
+            if taken {
+                self.count.increment();
+            }
+            taken
+        }
+
+        fn release(&self) {
+            self.taken.store(false, Ordering::Release);
+        }
+    }
+
+    unsafe impl<P: ScopedRawMutex> ScopedRawMutex for HierarchicalRawMutex<'_, P> {
+        #[inline]
+        fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            if self.parent_held.load(Ordering::Acquire) {
+                return run_locked(|| self.take(), || self.release(), f);
+            }
+
+            self.parent
+                .try_with_lock(|| run_locked(|| self.take(), || self.release(), f))
+                .flatten()
+        }
+
+        #[inline]
+        fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            // Whether or not the parent is held, the child can only be taken
+            // if it is re-entered from within its own critical section, which
+            // means we have certainly reached deadlock.
+            if self.parent_held.load(Ordering::Acquire) {
+                return run_locked(|| self.take(), || self.release(), f)
+                    .unwrap_or_else(|| deadlocked("Deadlocked"));
+            }
+
+            self.parent.with_lock(|| {
+                run_locked(|| self.take(), || self.release(), f)
+                    .unwrap_or_else(|| deadlocked("Deadlocked"))
+            })
+        }
+
+        fn is_locked(&self) -> bool {
+            self.taken.load(Ordering::Relaxed)
+        }
+
+        fn is_locked_acquire(&self) -> bool {
+            self.taken.load(Ordering::Acquire)
+        }
+    }
+}
+
+// ================
+
+// Requires atomic compare-and-swap, as the lock is taken without entering a
+// critical section.
+#[cfg(any(target_has_atomic = "ptr", feature = "portable-atomic"))]
+pub mod donating {
+    //! Software priority donation for cooperative schedulers
+
+    use super::rmw::{AtomicBool, AtomicU8, AtomicUsize};
+    use super::*;
+    use core::num::NonZeroUsize;
+
+    /// The interface between a [`DonatingRawMutex`] and a scheduler which
+    /// implements priority donation in software.
+    ///
+    /// Tasks are identified by a non-zero `usize`, such as the address of the
+    /// task's control block. Priorities are `u8`s, where a *higher* value is
+    /// a higher priority.
+    pub trait Priority {
+        /// Returns the identifier of the currently running task.
+        fn current_task() -> NonZeroUsize;
+
+        /// Returns the priority of the currently running task.
+        fn current() -> u8;
+
+        /// Raises the effective priority of `to_task` to at least `prio`,
+        /// because a task with priority `prio` is waiting for a lock it holds.
+        fn donate(to_task: NonZeroUsize, prio: u8);
+
+        /// Returns `task` to its own priority, after it has released a lock
+        /// for which it received a donation.
+        ///
+        /// If `task` still holds other locks for which it has received
+        /// donations, the scheduler should keep the highest of those.
+        fn restore(task: NonZeroUsize);
+
+        /// Yields to the scheduler while waiting for a lock, so that the task
+        /// holding it may run.
+        fn yield_now();
+    }
+
+    /// A [`ScopedRawMutex`] which donates the priority of waiting tasks to
+    /// the task holding the lock.
+    ///
+    /// This is a software counterpart to priority-ceiling locks which use
+    /// hardware interrupt priorities (such as `BASEPRI` on Cortex-M), for
+    /// cooperative schedulers without hardware priority support. When a task
+    /// blocks in [`with_lock`](ScopedRawMutex::with_lock) on a lock held by a
+    /// lower-priority task, it calls [`Priority::donate`] to raise the
+    /// holder's priority to its own, and then [`Priority::yield_now`] until
+    /// the lock is released. This prevents a medium-priority task from
+    /// starving the holder, and therefore the waiting high-priority task
+    /// (priority inversion). When the holder releases the lock, it calls
+    /// [`Priority::restore`] if it received a donation.
+    ///
+    /// Each waiting task donates at most once per holder, and only if its
+    /// priority is higher than that of the holder and of any previous
+    /// donation.
+    ///
+    /// # Multi-core schedulers
+    ///
+    /// Donations are made without holding any lock. On a single-core
+    /// cooperative scheduler, where a waiting task and the holder never run
+    /// concurrently, a donation is always made to the current holder, and
+    /// always restored when it releases the lock. If tasks may run
+    /// concurrently, a waiter may donate to a task which has just released
+    /// the lock, after that task checked for donations. The scheduler must
+    /// then tolerate a donation to a task which does not hold the lock, e.g.
+    /// by resetting each task's priority when it next blocks or yields.
+    ///
+    /// # Deadlocks
+    ///
+    /// [`with_lock`](ScopedRawMutex::with_lock) panics if the current task
+    /// already holds the lock.
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct DonatingRawMutex<P: Priority> {
+        /// The identifier of the task holding the lock, or 0 if unlocked.
+        holder: AtomicUsize,
+        /// The highest of the holder's priority and any donated priority.
+        ceiling: AtomicU8,
+        donated: AtomicBool,
+        count: LockCount,
+        _priority: PhantomData<fn() -> P>,
+    }
+
+    const UNLOCKED: usize = 0;
+
+    impl<P: Priority> DonatingRawMutex<P> {
+        /// Create a new `DonatingRawMutex`.
+        pub const fn new() -> Self {
+            Self {
+                holder: AtomicUsize::new(UNLOCKED),
+                ceiling: AtomicU8::new(0),
+                donated: AtomicBool::new(false),
+                count: LockCount::new(),
+                _priority: PhantomData,
+            }
+        }
+
+        /// Returns the number of times this mutex has been successfully
+        /// locked.
+        #[cfg(feature = "test-util")]
+        pub fn lock_count(&self) -> usize {
+            self.count.get()
+        }
+
+        /// Returns the identifier of the task currently holding the lock, or
+        /// `None` if it is unlocked.
+        pub fn holder(&self) -> Option<NonZeroUsize> {
+            NonZeroUsize::new(self.holder.load(Ordering::Acquire))
+        }
+
+        fn take(&self, task: NonZeroUsize) -> bool {
+            let taken = self
+                .holder
+                .compare_exchange(UNLOCKED, task.get(), Ordering::Acquire, Ordering::Relaxed)
+                .is_ok();
+            if taken {
+                self.ceiling.store(P::current(), Ordering::Relaxed);
+                self.count.increment();
+            }
+            taken
+        }
+
+        fn release(&self) {
+            let donated = self.donated.swap(false, Ordering::Relaxed);
+            let holder = self.holder.swap(UNLOCKED, Ordering::Release);
+            if donated {
+                if let Some(holder) = NonZeroUsize::new(holder) {
+                    P::restore(holder);
+                }
+            }
+        }
+
+        /// Donates the current task's priority to the holder of the lock, if
+        /// it is higher than the holder's current ceiling.
+        fn donate_to_holder(&self) {
+            let Some(holder) = self.holder() else {
+                return;
+            };
+            let prio = P::current();
+            if self.ceiling.fetch_max(prio, Ordering::Relaxed) < prio {
+                self.donated.store(true, Ordering::Relaxed);
+                P::donate(holder, prio);
+            }
+        }
+    }
+
+    impl<P: Priority> ConstInit for DonatingRawMutex<P> {
+        const INIT: Self = Self::new();
+    }
+
+    unsafe impl<P: Priority> ScopedRawMutex for DonatingRawMutex<P> {
+        #[inline]
+        fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            run_locked(|| self.take(P::current_task()), || self.release(), f)
+        }
+
+        #[inline]
+        fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            let task = P::current_task();
+            run_locked(
+                || loop {
+                    if self.take(task) {
+                        return true;
+                    }
+                    // If the current task holds the lock, it will never be
+                    // released.
+                    if self.holder.load(Ordering::Relaxed) == task.get() {
+                        return false;
+                    }
+                    self.donate_to_holder();
+                    P::yield_now();
+                },
+                || self.release(),
+                f,
+            )
+            .unwrap_or_else(|| deadlocked("Deadlocked"))
+        }
+
+        fn is_locked(&self) -> bool {
+            self.holder.load(Ordering::Relaxed) != UNLOCKED
+        }
+
+        fn is_locked_acquire(&self) -> bool {
+            self.holder.load(Ordering::Acquire) != UNLOCKED
+        }
+
+        fn is_owned(&self) -> bool {
+            self.holder.load(Ordering::Acquire) == P::current_task().get()
+        }
+    }
+}
+
+// ================
+
+// Requires atomic compare-and-swap, to serialize writers.
+#[cfg(any(target_has_atomic = "ptr", feature = "portable-atomic"))]
+pub mod seqlock {
+    //! Sequence lock for read-mostly `Copy` data
+
+    use super::rmw::{fence, AtomicUsize};
+    use core::cell::UnsafeCell;
+    use core::mem::MaybeUninit;
+    use core::ptr;
+    use core::sync::atomic::Ordering;
+
+    /// A sequence lock, providing wait-free reads of small [`Copy`] data.
+    ///
+    /// This is intended for data which is published by a single writer, such
+    /// as an interrupt handler, and read by several readers. Unlike a mutex,
+    /// readers never block the writer: instead, a reader which observes a
+    /// concurrent write simply retries its read. As such, a `SeqLock` is not a
+    /// [`ScopedRawMutex`](crate::ScopedRawMutex), and has its own interface.
+    ///
+    /// `T` must be [`Copy`], as a reader may copy out a partially-written
+    /// value, which is discarded when the read is retried. `T` should also be
+    /// small, as a large `T` increases the likelihood of reads being retried.
+    ///
+    /// A read which races with a write copies the data into a
+    /// [`MaybeUninit<T>`], which is only treated as a `T` once the sequence
+    /// counter shows that no write occurred, so a torn value is never
+    /// produced. However, the copy itself is a non-atomic access which races
+    /// with the writer's non-atomic store. This is a data race under the Rust
+    /// memory model, as in every sequence lock which does not copy its data
+    /// using atomic operations, and may be reported as such by tools like
+    /// Miri and ThreadSanitizer. Both accesses are volatile, so the compiler
+    /// does not assume anything about the value read, and in practice the
+    /// racing copy is discarded before it is used.
+    ///
+    /// # Ordering
+    ///
+    /// The lock is implemented using a sequence counter, which is odd while a
+    /// write is in progress:
+    ///
+    /// - [`write`](Self::write) increments the counter, issues a
+    ///   [`Release`](Ordering::Release) fence, writes the data, and then
+    ///   increments the counter again with [`Release`](Ordering::Release)
+    ///   ordering.
+    /// - [`read`](Self::read) loads the counter with
+    ///   [`Acquire`](Ordering::Acquire) ordering, reads the data, issues an
+    ///   [`Acquire`](Ordering::Acquire) fence, and then loads the counter
+    ///   again. The read is retried if the counter was odd, or if it changed.
+    pub struct SeqLock<T> {
+        seq: AtomicUsize,
+        data: UnsafeCell<T>,
+    }
+
+    unsafe impl<T: Copy + Send> Sync for SeqLock<T> {}
+
+    impl<T: Copy> SeqLock<T> {
+        /// Create a new `SeqLock` containing `val`.
+        pub const fn new(val: T) -> Self {
+            Self {
+                seq: AtomicUsize::new(0),
+                data: UnsafeCell::new(val),
+            }
+        }
+
+        /// Reads the current value.
+        ///
+        /// If a write is in progress, or a write occurs while reading, the
+        /// read is retried.
+        pub fn read(&self) -> T {
+            loop {
+                let seq1 = self.seq.load(Ordering::Acquire);
+                if seq1 & 1 != 0 {
+                    core::hint::spin_loop();
+                    continue;
+                }
+                // SAFETY: the pointer is valid for reads. The value is read
+                // as a `MaybeUninit<T>`, so a torn read is not yet treated as
+                // a `T`; see the type-level documentation for the race with a
+                // concurrent write.
+                let val = unsafe { ptr::read_volatile(self.data.get().cast::<MaybeUninit<T>>()) };
+                fence(Ordering::Acquire);
+                let seq2 = self.seq.load(Ordering::Relaxed);
+                if seq1 == seq2 {
+                    // SAFETY: the sequence number was even and unchanged, so
+                    // no write overlapped the read, and `val` is a complete
+                    // copy of the value stored by the last write.
+                    return unsafe { val.assume_init() };
+                }
+            }
+        }
+
+        /// Writes `val`, waiting for any other write in progress to complete.
+        ///
+        /// Note that, on a single-core system, calling this method from an
+        /// interrupt handler which has preempted another write will never
+        /// complete. In that case, [`try_write`](Self::try_write) should be
+        /// used instead.
+        pub fn write(&self, val: T) {
+            while !self.try_write(val) {
+                core::hint::spin_loop();
+            }
+        }
+
+        /// Attempts to write `val`, returning `false` without writing if
+        /// another write is in progress.
+        pub fn try_write(&self, val: T) -> bool {
+            let seq = self.seq.load(Ordering::Relaxed);
+            if seq & 1 != 0
+                || self
+                    .seq
+                    .compare_exchange(
+                        seq,
+                        seq.wrapping_add(1),
+                        Ordering::Relaxed,
+                        Ordering::Relaxed,
+                    )
+                    .is_err()
+            {
+                return false;
+            }
+            fence(Ordering::Release);
+            // SAFETY: the odd sequence number excludes other writers, and
+            // readers discard any value read while it is odd.
+            unsafe { ptr::write_volatile(self.data.get(), val) };
+            self.seq.store(seq.wrapping_add(2), Ordering::Release);
+            true
+        }
+
+        /// Returns a mutable reference to the contained value.
+        ///
+        /// As this requires a `&mut SeqLock`, no synchronization is required.
+        pub fn get_mut(&mut self) -> &mut T {
+            self.data.get_mut()
+        }
+
+        /// Consumes the `SeqLock`, returning the contained value.
+        pub fn into_inner(self) -> T {
+            self.data.into_inner()
+        }
+    }
+
+    #[cfg(feature = "fmt")]
+    impl<T: Copy + core::fmt::Debug> core::fmt::Debug for SeqLock<T> {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            f.debug_struct("SeqLock")
+                .field("data", &self.read())
+                .finish()
+        }
+    }
+}
+
+// ================
+
+pub mod tiered {
+    //! A combinator which falls back to a second lock when contended
+
+    use super::*;
+
+    /// A [`ScopedRawMutex`] which uses a fast lock when it is uncontended,
+    /// and a slow lock to serialize contending lockers.
+    ///
+    /// # Semantics
+    ///
+    /// The data is *always* protected by the `Fast` lock: the closure passed
+    /// to [`with_lock`](ScopedRawMutex::with_lock) only runs while `Fast` is
+    /// held. The `Slow` lock never grants access to the data on its own, as
+    /// that would allow one context to access the data while holding `Fast`
+    /// and another while holding `Slow`. Instead:
+    ///
+    /// - If `Fast` is free, it is acquired and the closure is run, without
+    ///   touching `Slow`.
+    /// - If `Fast` is contended, `Slow` is acquired (using its blocking
+    ///   [`with_lock`](ScopedRawMutex::with_lock)), and, while holding `Slow`,
+    ///   `Fast` is repeatedly tried until it is acquired. This ensures that at
+    ///   most one contending context spins on `Fast` at a time, while the
+    ///   others wait on `Slow`, which may be better suited to waiting (e.g. by
+    ///   parking the thread).
+    ///
+    /// [`try_with_lock`](ScopedRawMutex::try_with_lock) only tries `Fast`,
+    /// and fails if it is contended.
+    ///
+    /// Note that if `Fast` is held by a context which cannot run while the
+    /// current context holds `Slow` (for example, if `Slow` disables
+    /// interrupts, and `Fast` is held by a preempted context on the same
+    /// core), contention results in a deadlock.
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct TieredRawMutex<Fast, Slow> {
+        fast: Fast,
+        slow: Slow,
+    }
+
+    impl<Fast, Slow> TieredRawMutex<Fast, Slow> {
+        /// Create a new `TieredRawMutex` from the provided fast and slow
+        /// locks.
+        pub const fn new(fast: Fast, slow: Slow) -> Self {
+            Self { fast, slow }
+        }
+
+        /// Returns a reference to the fast lock.
+        pub fn fast(&self) -> &Fast {
+            &self.fast
+        }
+
+        /// Returns a reference to the slow lock.
+        pub fn slow(&self) -> &Slow {
+            &self.slow
+        }
+    }
+
+    impl<Fast: ConstInit, Slow: ConstInit> ConstInit for TieredRawMutex<Fast, Slow> {
+        const INIT: Self = Self::new(Fast::INIT, Slow::INIT);
+    }
+
+    unsafe impl<Fast, Slow> ScopedRawMutex for TieredRawMutex<Fast, Slow>
+    where
+        Fast: ScopedRawMutex,
+        Slow: ScopedRawMutex,
+    {
+        #[inline]
+        #[track_caller]
+        fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            self.fast.try_with_lock(f)
+        }
+
+        #[inline]
+        #[track_caller]
+        fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            let mut f = Some(f);
+            // `f` is only taken if `fast` was acquired, after which we
+            // return, so it is always present here.
+            if let Some(ret) = self.fast.try_with_lock(|| f.take().map(|f| f())).flatten() {
+                return ret;
+            }
+
+            self.slow.with_lock(|| loop {
+                if let Some(ret) = self.fast.try_with_lock(|| f.take().map(|f| f())).flatten() {
+                    return ret;
+                }
+                core::hint::spin_loop();
+            })
+        }
+
+        #[inline]
+        fn is_locked(&self) -> bool {
+            self.fast.is_locked()
+        }
+
+        #[inline]
+        fn is_locked_acquire(&self) -> bool {
+            self.fast.is_locked_acquire()
+        }
+
+        #[inline]
+        fn is_owned(&self) -> bool {
+            self.fast.is_owned()
+        }
+    }
+}
+
+// ================
+
+pub mod pair {
+    //! A combinator which acquires two locks as a single logical lock
+
+    use super::*;
+
+    /// A [`ScopedRawMutex`] which acquires two locks, `A` and `B`, and holds
+    /// both for the duration of the critical section.
+    ///
+    /// This allows data which is split across two fine-grained locks (each of
+    /// which may also be locked individually) to be accessed together, as if
+    /// it were protected by a single coarse lock.
+    ///
+    /// The locks are always acquired in the same order: `A` and then `B`.
+    /// They are released in the reverse order. Acquiring locks in a
+    /// consistent order prevents deadlocks between contexts which each hold
+    /// one of the locks while waiting for the other, provided that all code
+    /// which locks both `A` and `B` does so in this order (for example, by
+    /// always using this type to do so).
+    ///
+    /// [`try_with_lock`](ScopedRawMutex::try_with_lock) tries `A`, and then
+    /// tries `B` while holding `A`. If `B` cannot be acquired, `A` is released
+    /// before returning `None`, so that a failed attempt never holds either
+    /// lock.
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct PairRawMutex<A, B> {
+        a: A,
+        b: B,
+    }
+
+    impl<A, B> PairRawMutex<A, B> {
+        /// Create a new `PairRawMutex` from the provided locks, which are
+        /// acquired in the order `a`, `b`.
+        pub const fn new(a: A, b: B) -> Self {
+            Self { a, b }
+        }
+
+        /// Returns a reference to the first lock.
+        pub fn first(&self) -> &A {
+            &self.a
+        }
+
+        /// Returns a reference to the second lock.
+        pub fn second(&self) -> &B {
+            &self.b
+        }
+    }
+
+    impl<A: ConstInit, B: ConstInit> ConstInit for PairRawMutex<A, B> {
+        const INIT: Self = Self::new(A::INIT, B::INIT);
+    }
+
+    unsafe impl<A: ScopedRawMutex, B: ScopedRawMutex> ScopedRawMutex for PairRawMutex<A, B> {
+        #[inline]
+        #[track_caller]
+        fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            // If `B` cannot be acquired, the closure passed to `A` returns
+            // `None`, releasing `A`.
+            self.a.try_with_lock(|| self.b.try_with_lock(f)).flatten()
+        }
+
+        #[inline]
+        #[track_caller]
+        fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            self.a.with_lock(|| self.b.with_lock(f))
+        }
+
+        fn is_locked(&self) -> bool {
+            self.a.is_locked() && self.b.is_locked()
+        }
+
+        fn is_locked_acquire(&self) -> bool {
+            self.a.is_locked_acquire() && self.b.is_locked_acquire()
+        }
+
+        fn is_owned(&self) -> bool {
+            self.a.is_owned() && self.b.is_owned()
+        }
+    }
+
+    impl<A: DetectsDeadlock, B: DetectsDeadlock> DetectsDeadlock for PairRawMutex<A, B> {}
+}
+
+// ================
+
+// Requires atomic compare-and-swap, as the lock is taken without entering a
+// critical section.
+#[cfg(any(target_has_atomic = "8", feature = "portable-atomic"))]
+pub mod spin {
+    //! Spinlock with randomized exponential backoff
+
+    use super::rmw::AtomicBool;
+    use super::*;
+    use mutex_traits::RawMutex;
+
+    /// A spinlock which waits for a random, exponentially increasing number
+    /// of iterations between attempts to acquire the lock.
+    ///
+    /// When several cores contend for a spinlock, retrying at fixed intervals
+    /// can cause them to retry in lockstep, repeatedly colliding on the lock
+    /// (and the cache line containing it), so that none of them make progress.
+    /// To avoid this, after each failed attempt, [`RawMutex::lock`] waits for
+    /// a random number of iterations, chosen from a range which doubles after
+    /// each failure (up to a maximum of 1024 iterations), spreading contending
+    /// cores' retries apart.
+    ///
+    /// The random numbers are generated by a xorshift generator, which takes
+    /// only a few instructions per number and requires no global state. It is
+    /// seeded each time the lock is contended, from the address of the mutex
+    /// and of a local variable on the current stack. As each core (or thread)
+    /// has its own stack, contending cores use different seeds.
+    ///
+    /// Waiting contexts spin, rather than blocking, so this mutex should only
+    /// be used on multi-core systems, and only when the lock is held briefly.
+    /// On a single-core system, a context which is preempted while holding
+    /// the lock will cause any context which preempts it and attempts to lock
+    /// the mutex to spin forever.
+    ///
+    /// # Memory ordering
+    ///
+    /// By default, the lock is acquired with [`Ordering::Acquire`] and released
+    /// with [`Ordering::Release`]. The `O` type parameter selects different
+    /// orderings; see [`LockOrdering`] for details.
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct RandomizedSpinRawMutex<O = AcquireRelease> {
+        locked: AtomicBool,
+        count: LockCount,
+        _ordering: PhantomData<fn() -> O>,
+    }
+
+    /// The memory orderings used by a [`RandomizedSpinRawMutex`] to acquire
+    /// and release the lock.
+    ///
+    /// This is intended for code which layers its own synchronization on top
+    /// of the mutex, such as lock-free algorithms which also access data
+    /// outside of the critical section, and which must reason precisely about
+    /// the orderings the lock provides. For example,
+    /// [`SequentiallyConsistent`] places every acquisition and release of the
+    /// lock in the single total order of all `SeqCst` operations.
+    ///
+    /// # Safety
+    ///
+    /// The orderings determine whether writes made by one critical section
+    /// are visible to the next. To uphold the guarantees of [`RawMutex`],
+    /// implementations must ensure that:
+    ///
+    /// - [`ACQUIRE`](Self::ACQUIRE) is [`Ordering::Acquire`],
+    ///   [`Ordering::AcqRel`], or [`Ordering::SeqCst`],
+    /// - [`RELEASE`](Self::RELEASE) is [`Ordering::Release`] or
+    ///   [`Ordering::SeqCst`], and
+    /// - [`FAILURE`](Self::FAILURE) is [`Ordering::Relaxed`],
+    ///   [`Ordering::Acquire`], or [`Ordering::SeqCst`].
+    ///
+    /// These orderings may therefore only be *strengthened* from the default
+    /// of [`AcquireRelease`]. Using [`Ordering::Relaxed`] to acquire or
+    /// release the lock would allow a critical section to observe the
+    /// protected data before the previous critical section finished writing
+    /// it, which is a data race, and therefore undefined behavior. Using
+    /// `Release` or `AcqRel` as the failure ordering causes a panic.
+    pub unsafe trait LockOrdering {
+        /// The ordering of a successful compare-and-swap which acquires the
+        /// lock.
+        const ACQUIRE: Ordering;
+
+        /// The ordering of a failed compare-and-swap, when the lock was
+        /// already held.
+        const FAILURE: Ordering;
+
+        /// The ordering of the store which releases the lock.
+        const RELEASE: Ordering;
+    }
+
+    /// The default [`LockOrdering`], which acquires the lock with
+    /// [`Ordering::Acquire`] and releases it with [`Ordering::Release`].
+    #[derive(Copy, Clone)]
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct AcquireRelease;
+
+    /// A [`LockOrdering`] which acquires and releases the lock with
+    /// [`Ordering::SeqCst`].
+    #[derive(Copy, Clone)]
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct SequentiallyConsistent;
+
+    unsafe impl LockOrdering for AcquireRelease {
+        const ACQUIRE: Ordering = Ordering::Acquire;
+        const FAILURE: Ordering = Ordering::Relaxed;
+        const RELEASE: Ordering = Ordering::Release;
+    }
+
+    unsafe impl LockOrdering for SequentiallyConsistent {
+        const ACQUIRE: Ordering = Ordering::SeqCst;
+        const FAILURE: Ordering = Ordering::SeqCst;
+        const RELEASE: Ordering = Ordering::SeqCst;
+    }
+
+    impl RandomizedSpinRawMutex {
+        /// Create a new `RandomizedSpinRawMutex`.
+        pub const fn new() -> Self {
+            Self::with_ordering()
+        }
+    }
+
+    impl<O: LockOrdering> RandomizedSpinRawMutex<O> {
+        /// Create a new `RandomizedSpinRawMutex` using the memory orderings
+        /// `O`.
+        pub const fn with_ordering() -> Self {
+            Self {
+                locked: AtomicBool::new(false),
+                count: LockCount::new(),
+                _ordering: PhantomData,
+            }
+        }
+
+        /// Returns the number of times this mutex has been successfully
+        /// locked.
+        #[cfg(feature = "test-util")]
+        pub fn lock_count(&self) -> usize {
+            self.count.get()
+        }
+    }
+
+    impl<O: LockOrdering> ConstInit for RandomizedSpinRawMutex<O> {
+        const INIT: Self = Self::with_ordering();
+    }
+
+    impl<O: LockOrdering> RandomizedSpinRawMutex<O> {
+        /// Acquires the lock, returning the number of failed attempts to
+        /// acquire it.
+        #[inline]
+        pub(super) fn lock_counting_retries(&self) -> usize {
+            if self.try_lock() {
+                return 0;
+            }
+
+            let mut retries = 1;
+            let mut backoff = Backoff::new(self);
+            loop {
+                // Wait until the lock appears to be free before attempting
+                // to take it, so that waiting contexts only read the lock's
+                // cache line.
+                while self.locked.load(Ordering::Relaxed) {
+                    core::hint::spin_loop();
+                }
+                if self.try_lock() {
+                    return retries;
+                }
+                retries += 1;
+                backoff.spin();
+            }
+        }
+    }
+
+    /// A [`BlockingMutex`] backed by a [`RandomizedSpinRawMutex`].
+    ///
+    /// This type is [`Send`] and [`Sync`] whenever `T` is [`Send`].
+    pub type RandomizedSpinMutex<T> = BlockingMutex<RandomizedSpinRawMutex, T>;
+
+    const _: fn() = || {
+        assert_send::<RandomizedSpinMutex<u32>>();
+        assert_sync::<RandomizedSpinMutex<u32>>();
+    };
+
+    unsafe impl<O: LockOrdering> RawMutex for RandomizedSpinRawMutex<O> {
+        type GuardMarker = ();
+
+        #[inline]
+        fn lock(&self) {
+            self.lock_counting_retries();
+        }
+
+        #[inline]
+        fn try_lock(&self) -> bool {
+            let locked = self
+                .locked
+                .compare_exchange(false, true, O::ACQUIRE, O::FAILURE)
+                .is_ok();
+            if locked {
+                self.count.increment();
+            }
+            locked
+        }
+
+        #[inline]
+        unsafe fn unlock(&self) {
+            self.locked.store(false, O::RELEASE);
+        }
+
+        #[inline]
+        fn is_locked(&self) -> bool {
+            self.locked.load(Ordering::Relaxed)
+        }
+
+        #[inline]
+        fn is_locked_acquire(&self) -> bool {
+            self.locked.load(Ordering::Acquire)
+        }
+    }
+
+    /// The state of a context waiting for a [`RandomizedSpinRawMutex`].
+    struct Backoff {
+        /// The state of the xorshift generator, which is never zero.
+        rng: u32,
+        /// The upper bound (exclusive) of the number of iterations to wait
+        /// for. This is always a power of two.
+        limit: u32,
+    }
+
+    impl Backoff {
+        const MAX_LIMIT: u32 = 1 << 10;
+
+        #[inline]
+        fn new<O>(mutex: &RandomizedSpinRawMutex<O>) -> Self {
+            let local = 0u8;
+            let seed = (core::ptr::addr_of!(local) as usize) ^ (mutex as *const _ as usize);
+            // Spread the low bits of the addresses, which are likely to be
+            // similar, across the whole word, and ensure that the seed is
+            // non-zero, as xorshift gets stuck at zero.
+            Self {
+                rng: (seed as u32).wrapping_mul(0x9E37_79B9) | 1,
+                limit: 2,
+            }
+        }
+
+        #[inline]
+        fn spin(&mut self) {
+            // xorshift32
+            self.rng ^= self.rng << 13;
+            self.rng ^= self.rng >> 17;
+            self.rng ^= self.rng << 5;
+
+            for _ in 0..(self.rng & (self.limit - 1)) {
+                core::hint::spin_loop();
+            }
+            if self.limit < Self::MAX_LIMIT {
+                self.limit <<= 1;
+            }
+        }
+    }
+}
+
+// ================
+
+// Requires atomic compare-and-swap, as the lock is taken without entering a
+// critical section.
+#[cfg(any(target_has_atomic = "ptr", feature = "portable-atomic"))]
+pub mod handoff {
+    //! A queued spinlock which can hand the lock off to a specific waiter
+    //!
+    //! # Waiter tokens
+    //!
+    //! A context which intends to wait for a [`HandoffRawMutex`] registers
+    //! itself using [`HandoffRawMutex::register`], which returns a [`Waiter`].
+    //! The [`WaiterToken`] returned by [`Waiter::token`] identifies that
+    //! waiter, and may be passed to other contexts (such as a scheduler
+    //! which decides which waiter should run next).
+    //!
+    //! A token is *live* from the call to `register` until the `Waiter`
+    //! acquires the lock (using [`Waiter::lock`]) or is dropped. After that,
+    //! the token is *stale*, and refers to no waiter. Each registration
+    //! receives a new token, so a stale token never refers to a different
+    //! waiter (unless the `usize` counter from which tokens are allocated
+    //! wraps around).
+    //!
+    //! # Handoff
+    //!
+    //! When the lock is released using [`RawMutex::unlock`], it is handed off
+    //! to one of the registered waiters, if there are any, selected according
+    //! to the mutex's [`WakePolicy`]. Otherwise, it is simply released. By
+    //! default, the waiter which registered first is selected, in FIFO order.
+    //!
+    //! [`HandoffRawMutex::unlock_to`] (or [`MutexGuard::unlock_to`]) instead
+    //! hands the lock off to the waiter identified by a token, regardless of
+    //! its position in the queue. This allows custom wakeup policies to be
+    //! layered on top of the mutex. If the token is stale, `unlock_to`
+    //! behaves like `unlock`.
+    //!
+    //! Once the lock has been handed off to a waiter, no other context may
+    //! acquire it until that waiter does. If the waiter is dropped instead,
+    //! the lock is passed on as if it had been released with `unlock`. If the
+    //! waiter stops being live concurrently with the handoff, so that the
+    //! lock is handed to a stale token, the next context to attempt to lock
+    //! the mutex acquires it.
+
+    use super::rmw::{AtomicU8, AtomicUsize};
+    use super::*;
+    use crate::MutexGuard;
+    use core::num::NonZeroUsize;
+    use mutex_traits::RawMutex;
+
+    /// The lock is not held, and has not been handed off.
+    const UNLOCKED: usize = 0;
+    /// The lock is held.
+    const LOCKED: usize = 1;
+    /// A waiter slot which is not in use.
+    const FREE: usize = 0;
+
+    /// A spinlock with a queue of up to `N` registered waiters, which may
+    /// hand the lock off to a specific waiter.
+    ///
+    /// Any context may lock the mutex using [`RawMutex::lock`] without
+    /// registering, in which case it competes for the lock only when it is
+    /// released with no registered waiters. See the [module-level
+    /// documentation](self) for details on registration and handoff.
+    ///
+    /// The [`WakePolicy`] which selects the waiter to hand the lock off to is
+    /// chosen when the mutex is created, using
+    /// [`with_policy`](Self::with_policy), so that mutexes of the same type
+    /// may be configured differently. The policy is only consulted when the
+    /// lock is released while waiters are registered.
+    ///
+    /// Waiting contexts spin, rather than blocking, so this mutex should only
+    /// be used on multi-core systems, and only when the lock is held briefly.
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct HandoffRawMutex<const N: usize> {
+        /// `UNLOCKED`, `LOCKED`, or the token of the waiter to which the lock
+        /// has been handed off.
+        state: AtomicUsize,
+        next_token: AtomicUsize,
+        /// The tokens of the registered waiters, or `FREE`.
+        waiters: [AtomicUsize; N],
+        /// The priorities of the registered waiters, used by
+        /// `WakePolicy::Priority`.
+        priorities: [AtomicU8; N],
+        policy: WakePolicy,
+        count: LockCount,
+    }
+
+    /// Selects which registered waiter a [`HandoffRawMutex`] hands the lock
+    /// off to when it is released.
+    #[derive(Copy, Clone, PartialEq, Eq, Default)]
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub enum WakePolicy {
+        /// Hand off to the waiter which registered first.
+        ///
+        /// This is fair: every waiter eventually acquires the lock.
+        #[default]
+        Fifo,
+        /// Hand off to the waiter which registered most recently.
+        ///
+        /// This is unfair, and may starve waiters which registered early, but
+        /// favors waiters which ran most recently, whose data is more likely
+        /// to still be in cache, which may improve throughput.
+        Lifo,
+        /// Hand off to the waiter with the highest priority, as passed to
+        /// [`HandoffRawMutex::register_with_priority`]. Waiters with the same
+        /// priority are selected in FIFO order.
+        ///
+        /// A waiter which registers at the same time as the lock is released
+        /// may be ranked using the priority of the previous waiter in its
+        /// slot. This only affects which waiter is selected.
+        Priority,
+    }
+
+    /// Identifies a waiter registered with a [`HandoffRawMutex`].
+    ///
+    /// See the [module-level documentation](self#waiter-tokens) for the
+    /// lifecycle of a token.
+    #[derive(Copy, Clone, PartialEq, Eq)]
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct WaiterToken(NonZeroUsize);
+
+    /// A context registered to wait for a [`HandoffRawMutex`].
+    ///
+    /// This is returned by [`HandoffRawMutex::register`]. Dropping a `Waiter`
+    /// without locking the mutex deregisters it.
+    #[must_use = "dropping a `Waiter` deregisters it"]
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct Waiter<'a, const N: usize> {
+        mutex: &'a HandoffRawMutex<N>,
+        slot: usize,
+        token: WaiterToken,
+    }
+
+    /// A [`BlockingMutex`] backed by a [`HandoffRawMutex`].
+    ///
+    /// This type is [`Send`] and [`Sync`] whenever `T` is [`Send`].
+    pub type HandoffMutex<T, const N: usize> = BlockingMutex<HandoffRawMutex<N>, T>;
+
+    const _: fn() = || {
+        assert_send::<HandoffMutex<u32, 4>>();
+        assert_sync::<HandoffMutex<u32, 4>>();
+    };
+
+    impl<const N: usize> HandoffRawMutex<N> {
+        /// Create a new `HandoffRawMutex`, which hands the lock off to
+        /// waiters in FIFO order.
+        pub const fn new() -> Self {
+            Self::with_policy(WakePolicy::Fifo)
+        }
+
+        /// Create a new `HandoffRawMutex`, which hands the lock off to the
+        /// waiter selected by `policy`.
+        pub const fn with_policy(policy: WakePolicy) -> Self {
+            Self {
+                state: AtomicUsize::new(UNLOCKED),
+                next_token: AtomicUsize::new(LOCKED + 1),
+                waiters: [const { AtomicUsize::new(FREE) }; N],
+                priorities: [const { AtomicU8::new(0) }; N],
+                policy,
+                count: LockCount::new(),
+            }
+        }
+
+        /// Returns the [`WakePolicy`] used by this mutex.
+        pub fn policy(&self) -> WakePolicy {
+            self.policy
+        }
+
+        /// Returns the number of times this mutex has been successfully
+        /// locked.
+        #[cfg(feature = "test-util")]
+        pub fn lock_count(&self) -> usize {
+            self.count.get()
+        }
+
+        /// Registers the current context as a waiter for this mutex.
+        ///
+        /// Returns `None` if `N` waiters are already registered.
+        pub fn register(&self) -> Option<Waiter<'_, N>> {
+            self.register_with_priority(0)
+        }
+
+        /// Registers the current context as a waiter for this mutex, with the
+        /// given `priority`.
+        ///
+        /// The priority is only used by [`WakePolicy::Priority`], under which
+        /// higher values are handed the lock first.
+        ///
+        /// Returns `None` if `N` waiters are already registered.
+        pub fn register_with_priority(&self, priority: u8) -> Option<Waiter<'_, N>> {
+            let token = self.next_token();
+            self.waiters.iter().enumerate().find_map(|(slot, waiter)| {
+                waiter
+                    .compare_exchange(FREE, token.get(), Ordering::SeqCst, Ordering::Relaxed)
+                    .ok()
+                    .map(|_| {
+                        self.priorities[slot].store(priority, Ordering::SeqCst);
+                        Waiter {
+                            mutex: self,
+                            slot,
+                            token: WaiterToken(token),
+                        }
+                    })
+            })
+        }
+
+        /// Releases the lock, handing it off to the waiter identified by
+        /// `waiter`, if it is live.
+        ///
+        /// If `waiter` is stale, this behaves like [`RawMutex::unlock`],
+        /// handing the lock off to the waiter selected by the mutex's
+        /// [`WakePolicy`], if any.
+        ///
+        /// # Safety
+        ///
+        /// This method has the same safety requirements as
+        /// [`RawMutex::unlock`]: it may only be called if the mutex is held in
+        /// the current context.
+        pub unsafe fn unlock_to(&self, waiter: WaiterToken) {
+            if self.is_live(waiter.0.get()) {
+                self.state.store(waiter.0.get(), Ordering::SeqCst);
+            } else {
+                self.unlock();
+            }
+        }
+
+        fn next_token(&self) -> NonZeroUsize {
+            loop {
+                // Skip over the values reserved for `UNLOCKED` and `LOCKED`
+                // if the counter wraps around.
+                let token = self.next_token.fetch_add(1, Ordering::Relaxed);
+                if token > LOCKED {
+                    if let Some(token) = NonZeroUsize::new(token) {
+                        return token;
+                    }
+                }
+            }
+        }
+
+        /// Returns the token of the registered waiter to hand the lock off
+        /// to, according to the mutex's `WakePolicy`, if there is one.
+        fn select_waiter(&self) -> Option<usize> {
+            let mut waiters = self
+                .waiters
+                .iter()
+                .enumerate()
+                .map(|(slot, waiter)| (slot, waiter.load(Ordering::SeqCst)))
+                .filter(|&(_, token)| token != FREE);
+            // If no waiters are registered, return without consulting the
+            // policy, so that it costs nothing when the lock is uncontended.
+            let first = waiters.next()?;
+            let (_, token) = match self.policy {
+                // Tokens are allocated in increasing order, so the waiter
+                // which registered first has the lowest token.
+                WakePolicy::Fifo => waiters.fold(first, |a, b| if b.1 < a.1 { b } else { a }),
+                WakePolicy::Lifo => waiters.fold(first, |a, b| if b.1 > a.1 { b } else { a }),
+                WakePolicy::Priority => {
+                    let rank = |(slot, token): (usize, usize)| {
+                        let priority = self.priorities[slot].load(Ordering::SeqCst);
+                        (priority, core::cmp::Reverse(token))
+                    };
+                    waiters.fold(first, |a, b| if rank(b) > rank(a) { b } else { a })
+                }
+            };
+            Some(token)
+        }
+
+        /// Returns `true` if `token` belongs to a registered waiter.
+        fn is_live(&self, token: usize) -> bool {
+            self.waiters
+                .iter()
+                .any(|waiter| waiter.load(Ordering::SeqCst) == token)
+        }
+
+        /// Attempts to acquire the lock, either because it is unlocked, has
+        /// been handed off to `token`, or has been handed off to a stale
+        /// token.
+        fn try_claim(&self, token: Option<WaiterToken>) -> bool {
+            let state = self.state.load(Ordering::SeqCst);
+            let claimable = match state {
+                UNLOCKED => true,
+                LOCKED => false,
+                _ => token.map(|t| t.0.get()) == Some(state) || !self.is_live(state),
+            };
+            let claimed = claimable
+                && self
+                    .state
+                    .compare_exchange(state, LOCKED, Ordering::SeqCst, Ordering::Relaxed)
+                    .is_ok();
+            if claimed {
+                self.count.increment();
+            }
+            claimed
+        }
+    }
+
+    impl<const N: usize> ConstInit for HandoffRawMutex<N> {
+        const INIT: Self = Self::new();
+    }
+
+    unsafe impl<const N: usize> RawMutex for HandoffRawMutex<N> {
+        type GuardMarker = ();
+
+        #[inline]
+        fn lock(&self) {
+            while !self.try_claim(None) {
+                core::hint::spin_loop();
+            }
+        }
+
+        #[inline]
+        fn try_lock(&self) -> bool {
+            self.try_claim(None)
+        }
+
+        unsafe fn unlock(&self) {
+            let next = self.select_waiter();
+            self.state.store(next.unwrap_or(UNLOCKED), Ordering::SeqCst);
+        }
+
+        #[inline]
+        fn is_locked(&self) -> bool {
+            // NOTE: a mutex which has been handed off is considered locked on
+            // behalf of the waiter it was handed to.
+            self.state.load(Ordering::Relaxed) != UNLOCKED
+        }
+
+        #[inline]
+        fn is_locked_acquire(&self) -> bool {
+            self.state.load(Ordering::Acquire) != UNLOCKED
+        }
+    }
+
+    impl<'a, const N: usize> Waiter<'a, N> {
+        /// Returns the token identifying this waiter.
+        pub fn token(&self) -> WaiterToken {
+            self.token
+        }
+
+        /// Waits until the lock is handed off to this waiter (or is released
+        /// with no waiters registered), acquires it, and deregisters this
+        /// waiter.
+        ///
+        /// The lock must later be released by the caller, e.g. using
+        /// [`RawMutex::unlock`] or [`HandoffRawMutex::unlock_to`].
+        pub fn lock(self) {
+            while !self.mutex.try_claim(Some(self.token)) {
+                core::hint::spin_loop();
+            }
+            self.deregister();
+            core::mem::forget(self);
+        }
+
+        /// Waits until the lock is handed off to this waiter, as in
+        /// [`Waiter::lock`], and returns a guard for the data protected by
+        /// `mutex`.
+        ///
+        /// # Panics
+        ///
+        /// If this waiter was not registered with `mutex`'s raw mutex.
+        pub fn lock_data<T: ?Sized>(
+            self,
+            mutex: &'a BlockingMutex<HandoffRawMutex<N>, T>,
+        ) -> MutexGuard<'a, HandoffRawMutex<N>, T> {
+            assert!(
+                core::ptr::eq(self.mutex, &mutex.raw),
+                "waiter was registered with a different mutex"
+            );
+            self.lock();
+            MutexGuard {
+                lock: mutex,
+                _marker: PhantomData,
+            }
+        }
+
+        fn deregister(&self) {
+            self.mutex.waiters[self.slot].store(FREE, Ordering::SeqCst);
+        }
+    }
+
+    impl<const N: usize> Drop for Waiter<'_, N> {
+        fn drop(&mut self) {
+            self.deregister();
+            // If the lock was handed off to this waiter, pass it on, rather
+            // than leaving it reserved for a waiter which will never take it.
+            if self.mutex.try_claim(Some(self.token)) {
+                // SAFETY: we just claimed the lock.
+                unsafe { self.mutex.unlock() };
+            }
+        }
+    }
+
+    impl<'a, T: ?Sized, const N: usize> MutexGuard<'a, HandoffRawMutex<N>, T> {
+        /// Releases the lock, handing it off to the waiter identified by
+        /// `waiter`, if it is live.
+        ///
+        /// See [`HandoffRawMutex::unlock_to`] for details.
+        ///
+        /// This is an associated function, rather than a method, so that it does
+        /// not shadow any method named `unlock_to` on `T`.
+        pub fn unlock_to(this: Self, waiter: WaiterToken) {
+            let raw = &this.lock.raw;
+            core::mem::forget(this);
+            // SAFETY: a `MutexGuard` is only created when the lock has been
+            // acquired, and forgetting it ensures it will not unlock again.
+            unsafe { raw.unlock_to(waiter) };
+        }
+    }
+}
+
+// ================
+
+// Requires atomic compare-and-swap, as the lock is taken without entering a
+// critical section.
+#[cfg(any(target_has_atomic = "8", feature = "portable-atomic"))]
+pub mod cooperative {
+    //! Mutex which yields to a cooperative scheduler while contended
+
+    use super::rmw::AtomicBool;
+    use super::*;
+    use mutex_traits::RawMutex;
+
+    /// A way to hand control back to a cooperative scheduler.
+    ///
+    /// This is implemented for (or by) the scheduler on which a
+    /// [`CooperativeRawMutex`] is used.
+    pub trait Yield {
+        /// Suspends the current task, allowing the scheduler to run other
+        /// tasks before it resumes.
+        ///
+        /// This **must** actually reschedule: if it returns without giving
+        /// the task holding the lock a chance to run, a
+        /// [`CooperativeRawMutex`] which is contended on a single core will
+        /// never be released, and waiting for it will hang forever.
+        fn yield_now();
+    }
+
+    /// A mutex for cooperative schedulers, which yields to the scheduler while
+    /// waiting for the lock.
+    ///
+    /// On a cooperative single-core runtime, where the context holding a lock
+    /// is another task rather than an interrupt handler, neither of the usual
+    /// approaches to contention works: spinning hangs forever, as the task
+    /// holding the lock can never run to release it, and panicking on
+    /// contention (as [`CriticalSectionRawMutex`] does) rejects a situation
+    /// which would resolve itself if the holder were allowed to run. Instead,
+    /// when [`RawMutex::lock`] finds the lock taken, it calls
+    /// [`Y::yield_now`](Yield::yield_now) to hand control back to the
+    /// scheduler, and retries once it is resumed.
+    ///
+    /// This mutex does not track which task holds the lock, so a task which
+    /// attempts to lock a mutex it already holds will yield forever. It must
+    /// also not be locked from an interrupt handler (or any other context
+    /// which cannot yield).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mutex::BlockingMutex;
+    /// use mutex::raw_impls::cooperative::{CooperativeRawMutex, Yield};
+    ///
+    /// // On a real cooperative runtime, this would switch to another task.
+    /// struct Scheduler;
+    ///
+    /// impl Yield for Scheduler {
+    ///     fn yield_now() {
+    ///         std::thread::yield_now();
+    ///     }
+    /// }
+    ///
+    /// static COUNT: BlockingMutex<CooperativeRawMutex<Scheduler>, u32> =
+    ///     BlockingMutex::new(0);
+    ///
+    /// COUNT.with_lock(|count| *count += 1);
+    /// ```
+    ///
+    /// [`CriticalSectionRawMutex`]: super::cs::CriticalSectionRawMutex
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct CooperativeRawMutex<Y> {
+        locked: AtomicBool,
+        count: LockCount,
+        _yield: PhantomData<fn() -> Y>,
+    }
+
+    impl<Y> CooperativeRawMutex<Y> {
+        /// Create a new `CooperativeRawMutex`.
+        pub const fn new() -> Self {
+            Self {
+                locked: AtomicBool::new(false),
+                count: LockCount::new(),
+                _yield: PhantomData,
+            }
+        }
+
+        /// Returns the number of times this mutex has been successfully
+        /// locked.
+        #[cfg(feature = "test-util")]
+        pub fn lock_count(&self) -> usize {
+            self.count.get()
+        }
+    }
+
+    impl<Y> Default for CooperativeRawMutex<Y> {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl<Y> ConstInit for CooperativeRawMutex<Y> {
+        const INIT: Self = Self::new();
+    }
+
+    /// A [`BlockingMutex`] backed by a [`CooperativeRawMutex`].
+    ///
+    /// This type is [`Send`] and [`Sync`] whenever `T` is [`Send`].
+    pub type CooperativeMutex<Y, T> = BlockingMutex<CooperativeRawMutex<Y>, T>;
+
+    const _: fn() = || {
+        struct NoYield;
+        impl Yield for NoYield {
+            fn yield_now() {}
+        }
+        assert_send::<CooperativeMutex<NoYield, u32>>();
+        assert_sync::<CooperativeMutex<NoYield, u32>>();
+    };
+
+    unsafe impl<Y: Yield> RawMutex for CooperativeRawMutex<Y> {
+        type GuardMarker = ();
+
+        #[inline]
+        fn lock(&self) {
+            while !self.try_lock() {
+                // The lock is held by another task, which cannot release it
+                // until it runs again.
+                Y::yield_now();
+            }
+        }
+
+        #[inline]
+        fn try_lock(&self) -> bool {
+            let locked = self
+                .locked
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok();
+            if locked {
+                self.count.increment();
+            }
+            locked
+        }
+
+        #[inline]
+        unsafe fn unlock(&self) {
+            self.locked.store(false, Ordering::Release);
+        }
+
+        #[inline]
+        fn is_locked(&self) -> bool {
+            self.locked.load(Ordering::Relaxed)
+        }
+
+        #[inline]
+        fn is_locked_acquire(&self) -> bool {
+            self.locked.load(Ordering::Acquire)
+        }
+    }
+}
+
+// ================
+
+// Requires atomic compare-and-swap, to ensure that only one context constructs
+// the inner mutex.
+#[cfg(any(target_has_atomic = "8", feature = "portable-atomic"))]
+pub mod lazy {
+    //! Lazily constructed raw mutexes
+
+    use super::rmw::AtomicU8;
+    use super::*;
+    use core::cell::UnsafeCell;
+    use core::mem::MaybeUninit;
+
+    const UNINIT: u8 = 0;
+    const INITIALIZING: u8 = 1;
+    const READY: u8 = 2;
+    const POISONED: u8 = 3;
+
+    /// A [`ScopedRawMutex`] which constructs the inner mutex `M` using `F` the
+    /// first time it is used.
+    ///
+    /// This allows placing raw mutexes which cannot be constructed in const
+    /// context (such as ones which must allocate an OS resource) in a
+    /// `static`.
+    ///
+    /// Construction is concurrency-safe: if several contexts use the mutex
+    /// before it has been constructed, exactly one of them runs `F`, and the
+    /// others spin until it has completed. If `F` panics, the mutex is
+    /// poisoned, and all subsequent uses will panic.
+    pub struct LazyRawMutex<M, F = fn() -> M> {
+        state: AtomicU8,
+        init: UnsafeCell<Option<F>>,
+        inner: UnsafeCell<MaybeUninit<M>>,
+    }
+
+    // SAFETY: `F` is only accessed by the single context which transitions the
+    // state to `INITIALIZING`, and `M` is only shared once it is `READY`.
+    unsafe impl<M: Send + Sync, F: Send> Sync for LazyRawMutex<M, F> {}
+    unsafe impl<M: Send, F: Send> Send for LazyRawMutex<M, F> {}
+
+    impl<M, F: FnOnce() -> M> LazyRawMutex<M, F> {
+        /// Create a new `LazyRawMutex` which will construct the inner mutex
+        /// using `init`.
+        pub const fn new(init: F) -> Self {
+            Self {
+                state: AtomicU8::new(UNINIT),
+                init: UnsafeCell::new(Some(init)),
+                inner: UnsafeCell::new(MaybeUninit::uninit()),
+            }
+        }
+
+        /// Returns a reference to the inner mutex, constructing it if it has
+        /// not yet been constructed.
+        #[inline]
+        pub fn force(&self) -> &M {
+            if self.state.load(Ordering::Acquire) != READY {
+                self.init_slow();
+            }
+            // SAFETY: the state is `READY`, so the inner mutex has been
+            // initialized, and is never mutated again.
+            unsafe { (*self.inner.get()).assume_init_ref() }
+        }
+
+        /// Returns a reference to the inner mutex, or `None` if it has not yet
+        /// been constructed.
+        #[inline]
+        pub fn get(&self) -> Option<&M> {
+            if self.state.load(Ordering::Acquire) == READY {
+                // SAFETY: as above.
+                Some(unsafe { (*self.inner.get()).assume_init_ref() })
+            } else {
+                None
+            }
+        }
+
+        #[cold]
+        fn init_slow(&self) {
+            loop {
+                match self.state.compare_exchange_weak(
+                    UNINIT,
+                    INITIALIZING,
+                    Ordering::Acquire,
+                    Ordering::Acquire,
+                ) {
+                    Ok(_) => break,
+                    Err(READY) => return,
+                    Err(POISONED) => panic!("`LazyRawMutex` initialization panicked"),
+                    Err(_) => core::hint::spin_loop(),
+                }
+            }
+
+            // If `init` panics, poison the mutex, rather than leaving other
+            // contexts spinning forever.
+            struct Poison<'a>(&'a AtomicU8);
+            impl Drop for Poison<'_> {
+                fn drop(&mut self) {
+                    self.0.store(POISONED, Ordering::Release);
+                }
+            }
+
+            let poison = Poison(&self.state);
+            // SAFETY: we transitioned the state to `INITIALIZING`, so we have
+            // exclusive access to `init` and `inner`.
+            unsafe {
+                let init = (*self.init.get())
+                    .take()
+                    .expect("`LazyRawMutex` is only initialized once");
+                (*self.inner.get()).write(init());
+            }
+            core::mem::forget(poison);
+            self.state.store(READY, Ordering::Release);
+        }
+    }
+
+    impl<M: Default> ConstInit for LazyRawMutex<M> {
+        const INIT: Self = Self::new(M::default as fn() -> M);
+    }
+
+    impl<M, F> Drop for LazyRawMutex<M, F> {
+        fn drop(&mut self) {
+            if *self.state.get_mut() == READY {
+                // SAFETY: the state is `READY`, so the inner mutex has been
+                // initialized.
+                unsafe { self.inner.get_mut().assume_init_drop() }
+            }
+        }
+    }
+
+    unsafe impl<M: ScopedRawMutex, F: FnOnce() -> M> ScopedRawMutex for LazyRawMutex<M, F> {
+        #[inline]
+        #[track_caller]
+        fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            self.force().try_with_lock(f)
+        }
+
+        #[inline]
+        #[track_caller]
+        fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            self.force().with_lock(f)
+        }
+
+        /// If the inner mutex has not yet been constructed, it is not locked.
+        #[inline]
+        fn is_locked(&self) -> bool {
+            self.get().is_some_and(M::is_locked)
+        }
+
+        #[inline]
+        fn is_locked_acquire(&self) -> bool {
+            self.get().is_some_and(M::is_locked_acquire)
+        }
+
+        #[inline]
+        fn is_owned(&self) -> bool {
+            self.get().is_some_and(M::is_owned)
+        }
+    }
+
+    impl<M: DetectsDeadlock, F: FnOnce() -> M> DetectsDeadlock for LazyRawMutex<M, F> {}
+
+    #[cfg(feature = "fmt")]
+    impl<M: core::fmt::Debug, F: FnOnce() -> M> core::fmt::Debug for LazyRawMutex<M, F> {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            f.debug_struct("LazyRawMutex")
+                .field("inner", &self.get())
+                .finish_non_exhaustive()
+        }
+    }
+}
+
+// ================
+
+pub mod depth {
+    //! Tracking of the nesting depth of held locks
+    //!
+    //! This module provides [`DepthTrackedRawMutex`], a wrapper around another
+    //! [`ScopedRawMutex`] which tracks how many `DepthTrackedRawMutex`es are
+    //! currently held, and panics if too many locks are nested. This does not
+    //! enforce any lock ordering, but it catches runaway nesting and
+    //! accidental recursion through different locks.
+    //!
+    //! # Scope of the depth counter
+    //!
+    //! When the `std` feature is enabled, the depth is tracked per thread.
+    //! Otherwise, it is tracked using a single global counter. On single-core
+    //! systems, where lock nesting is strictly LIFO (e.g. when the wrapped
+    //! mutexes are [`CriticalSectionRawMutex`]es), this is equivalent to
+    //! tracking the depth per core. On multi-core systems without `std`, the
+    //! depth includes locks held by all cores. The counter is updated using
+    //! atomic read-modify-write operations where they are available (natively,
+    //! or via the `portable-atomic` feature); on targets without them, it is
+    //! only accurate on single-core systems.
+    //!
+    //! Depth tracking is only performed when debug assertions are enabled. In
+    //! release builds, a `DepthTrackedRawMutex` behaves exactly like the mutex
+    //! it wraps.
+    //!
+    //! [`CriticalSectionRawMutex`]: super::cs::CriticalSectionRawMutex
+
+    use super::*;
+
+    /// A [`ScopedRawMutex`] wrapper which panics if more than `MAX_DEPTH`
+    /// `DepthTrackedRawMutex`es are held at the same time.
+    ///
+    /// See the [module-level documentation](self) for details.
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct DepthTrackedRawMutex<M, const MAX_DEPTH: usize> {
+        inner: M,
+    }
+
+    /// Returns the number of [`DepthTrackedRawMutex`]es currently held.
+    ///
+    /// This always returns 0 if debug assertions are disabled.
+    pub fn current_depth() -> usize {
+        #[cfg(debug_assertions)]
+        {
+            counter::get()
+        }
+
+        #[cfg(not(debug_assertions))]
+        {
+            0
+        }
+    }
+
+    impl<M, const MAX_DEPTH: usize> DepthTrackedRawMutex<M, MAX_DEPTH> {
+        /// Create a new `DepthTrackedRawMutex` wrapping `inner`.
+        pub const fn new(inner: M) -> Self {
+            Self { inner }
+        }
+
+        /// Returns a reference to the wrapped mutex.
+        pub fn inner(&self) -> &M {
+            &self.inner
+        }
+    }
+
+    impl<M: ConstInit, const MAX_DEPTH: usize> ConstInit for DepthTrackedRawMutex<M, MAX_DEPTH> {
+        const INIT: Self = Self::new(M::INIT);
+    }
+
+    unsafe impl<M, const MAX_DEPTH: usize> ScopedRawMutex for DepthTrackedRawMutex<M, MAX_DEPTH>
+    where
+        M: ScopedRawMutex,
+    {
+        #[inline]
+        #[track_caller]
+        fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            self.inner.try_with_lock(|| {
+                let _depth = DepthGuard::enter(MAX_DEPTH);
+                f()
+            })
+        }
+
+        #[inline]
+        #[track_caller]
+        fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            self.inner.with_lock(|| {
+                let _depth = DepthGuard::enter(MAX_DEPTH);
+                f()
+            })
+        }
+
+        fn is_locked(&self) -> bool {
+            self.inner.is_locked()
+        }
+
+        fn is_locked_acquire(&self) -> bool {
+            self.inner.is_locked_acquire()
+        }
+
+        fn is_owned(&self) -> bool {
+            self.inner.is_owned()
+        }
+    }
+
+    impl<M: DetectsDeadlock, const MAX_DEPTH: usize> DetectsDeadlock
+        for DepthTrackedRawMutex<M, MAX_DEPTH>
+    {
+    }
+
+    /// Increments the depth counter when created, and decrements it when
+    /// dropped, even if the closure panics.
+    struct DepthGuard(());
+
+    impl DepthGuard {
+        #[inline]
+        #[track_caller]
+        fn enter(max: usize) -> Self {
+            #[cfg(debug_assertions)]
+            {
+                let depth = counter::increment();
+                if depth > max {
+                    counter::decrement();
+                    panic!("lock nesting depth {depth} exceeds the maximum of {max}");
+                }
+            }
+            #[cfg(not(debug_assertions))]
+            let _ = max;
+
+            Self(())
+        }
+    }
+
+    impl Drop for DepthGuard {
+        #[inline]
+        fn drop(&mut self) {
+            #[cfg(debug_assertions)]
+            counter::decrement();
+        }
+    }
+
+    #[cfg(all(debug_assertions, feature = "std"))]
+    mod counter {
+        use std::cell::Cell;
+
+        std::thread_local! {
+            static DEPTH: Cell<usize> = const { Cell::new(0) };
+        }
+
+        pub(super) fn get() -> usize {
+            DEPTH.with(Cell::get)
+        }
+
+        /// Increments the depth, returning the new depth.
+        pub(super) fn increment() -> usize {
+            DEPTH.with(|depth| {
+                let new = depth.get() + 1;
+                depth.set(new);
+                new
+            })
+        }
+
+        pub(super) fn decrement() {
+            DEPTH.with(|depth| depth.set(depth.get() - 1))
+        }
+    }
+
+    #[cfg(all(
+        debug_assertions,
+        not(feature = "std"),
+        any(target_has_atomic = "ptr", feature = "portable-atomic")
+    ))]
+    mod counter {
+        use super::super::rmw::AtomicUsize;
+        use core::sync::atomic::Ordering;
+
+        static DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+        pub(super) fn get() -> usize {
+            DEPTH.load(Ordering::Relaxed)
+        }
+
+        /// Increments the depth, returning the new depth.
+        pub(super) fn increment() -> usize {
+            DEPTH.fetch_add(1, Ordering::Relaxed) + 1
+        }
+
+        pub(super) fn decrement() {
+            DEPTH.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    #[cfg(all(
+        debug_assertions,
+        not(feature = "std"),
+        not(any(target_has_atomic = "ptr", feature = "portable-atomic"))
+    ))]
+    mod counter {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        // NOTE: separated load/stores are used on targets without atomic
+        // read-modify-write operations. This is only correct on a single
+        // core, where lock nesting is strictly LIFO: if we are interrupted
+        // between the load and the store, the interrupt handler releases
+        // every lock it takes before we resume, so the counter is restored to
+        // the value we loaded.
+        static DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+        pub(super) fn get() -> usize {
+            DEPTH.load(Ordering::Relaxed)
+        }
+
+        /// Increments the depth, returning the new depth.
+        pub(super) fn increment() -> usize {
+            let new = DEPTH.load(Ordering::Relaxed) + 1;
+            DEPTH.store(new, Ordering::Relaxed);
+            new
+        }
+
+        pub(super) fn decrement() {
+            let depth = DEPTH.load(Ordering::Relaxed);
+            DEPTH.store(depth - 1, Ordering::Relaxed);
+        }
+    }
+}
+
+// ================
+
+pub mod recursion {
+    //! Re-entrancy confined to a specific recursive call chain
+    //!
+    //! This module provides [`RecursionTokenRawMutex`], a wrapper around
+    //! another [`ScopedRawMutex`] which permits the lock to be re-entered, but
+    //! only by code which was explicitly handed a [`RecursionToken`] by the
+    //! outer critical section. This is a middle ground between mutexes which
+    //! treat any re-entry as a deadlock (such as [`CriticalSectionRawMutex`])
+    //! and a fully re-entrant mutex, which also permits accidental re-entry
+    //! from unrelated code.
+    //!
+    //! [`CriticalSectionRawMutex`]: super::cs::CriticalSectionRawMutex
+
+    use super::*;
+
+    /// A [`ScopedRawMutex`] wrapper which may be re-entered using a
+    /// [`RecursionToken`].
+    ///
+    /// [`with_lock_token`](Self::with_lock_token) locks the wrapped mutex, and
+    /// passes a [`RecursionToken`] to its closure. Passing that token to
+    /// [`with_lock_reentrant`](Self::with_lock_reentrant) while the lock is
+    /// still held runs a nested closure without locking the wrapped mutex
+    /// again. Any other attempt to lock the mutex while it is held, such as a
+    /// nested call to [`with_lock`](ScopedRawMutex::with_lock), behaves as it
+    /// would for the wrapped mutex: e.g. it panics if the wrapped mutex is a
+    /// [`CriticalSectionRawMutex`].
+    ///
+    /// As the closure passed to `with_lock_reentrant` runs while the lock is
+    /// already held, this is a raw-mutex-level API only: a [`BlockingMutex`]
+    /// never grants access to its data through a `RecursionToken`, as that
+    /// would alias the `&mut T` held by the outer critical section. Recursive
+    /// code which accesses the data of a `BlockingMutex` should pass the
+    /// `&mut T` down the call chain instead.
+    ///
+    /// # Examples
+    ///
+    /// Permitting a recursive function to re-enter the lock:
+    ///
+    /// ```
+    /// use mutex::raw_impls::cs::CriticalSectionRawMutex;
+    /// use mutex::raw_impls::recursion::{RecursionToken, RecursionTokenRawMutex};
+    ///
+    /// static LOCK: RecursionTokenRawMutex<CriticalSectionRawMutex> =
+    ///     RecursionTokenRawMutex::new(CriticalSectionRawMutex::new());
+    ///
+    /// fn depth(n: u32, token: RecursionToken<'_>) -> u32 {
+    ///     if n == 0 {
+    ///         return 0;
+    ///     }
+    ///     LOCK.with_lock_reentrant(token, |token| 1 + depth(n - 1, token))
+    /// }
+    ///
+    /// assert_eq!(LOCK.with_lock_token(|token| depth(3, token)), 3);
+    /// ```
+    ///
+    /// Accidental re-entry without the token is still detected by the wrapped
+    /// mutex:
+    ///
+    /// ```should_panic
+    /// use mutex::raw_impls::cs::CriticalSectionRawMutex;
+    /// use mutex::raw_impls::recursion::RecursionTokenRawMutex;
+    /// use mutex::ScopedRawMutex;
+    ///
+    /// static LOCK: RecursionTokenRawMutex<CriticalSectionRawMutex> =
+    ///     RecursionTokenRawMutex::new(CriticalSectionRawMutex::new());
+    ///
+    /// LOCK.with_lock_token(|_token| {
+    ///     // Panics: the mutex is already locked.
+    ///     LOCK.with_lock(|| {});
+    /// });
+    /// ```
+    ///
+    /// [`CriticalSectionRawMutex`]: super::cs::CriticalSectionRawMutex
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct RecursionTokenRawMutex<M> {
+        inner: M,
+    }
+
+    /// Proof that a [`RecursionTokenRawMutex`] is locked by the current call
+    /// chain.
+    ///
+    /// A `RecursionToken` is created by
+    /// [`RecursionTokenRawMutex::with_lock_token`], and may be passed to
+    /// [`RecursionTokenRawMutex::with_lock_reentrant`] on the same mutex to
+    /// re-enter it. The token's lifetime is bound to the closure it was passed
+    /// to, so it cannot outlive the critical section, and it is neither
+    /// [`Send`] nor [`Sync`], so it cannot be used by another thread.
+    #[derive(Copy, Clone)]
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct RecursionToken<'lock> {
+        /// The address of the mutex which issued this token.
+        mutex: *const (),
+        _lifetime: PhantomData<&'lock ()>,
+    }
+
+    impl<M> RecursionTokenRawMutex<M> {
+        /// Create a new `RecursionTokenRawMutex` wrapping `inner`.
+        pub const fn new(inner: M) -> Self {
+            Self { inner }
+        }
+
+        /// Returns a reference to the wrapped mutex.
+        pub fn inner(&self) -> &M {
+            &self.inner
+        }
+
+        #[inline]
+        fn token(&self) -> RecursionToken<'_> {
+            RecursionToken {
+                mutex: (self as *const Self).cast(),
+                _lifetime: PhantomData,
+            }
+        }
+    }
+
+    impl<M: ScopedRawMutex> RecursionTokenRawMutex<M> {
+        /// Locks the wrapped mutex, calling `f()` with a [`RecursionToken`]
+        /// which permits re-entering it.
+        ///
+        /// Behavior when the lock is already locked is dependent on the
+        /// behavior of the wrapped mutex. See [`ScopedRawMutex::with_lock()`]'s
+        /// documentation for more details
+        #[inline]
+        #[track_caller]
+        pub fn with_lock_token<U>(&self, f: impl FnOnce(RecursionToken<'_>) -> U) -> U {
+            self.inner.with_lock(|| f(self.token()))
+        }
+
+        /// Attempts to lock the wrapped mutex, calling `f()` with a
+        /// [`RecursionToken`] which permits re-entering it.
+        ///
+        /// Returns `None` without calling `f()` if the mutex was already
+        /// locked.
+        #[must_use]
+        #[inline]
+        #[track_caller]
+        pub fn try_with_lock_token<U>(&self, f: impl FnOnce(RecursionToken<'_>) -> U) -> Option<U> {
+            self.inner.try_with_lock(|| f(self.token()))
+        }
+
+        /// Re-enters the mutex using a [`RecursionToken`] issued by an
+        /// enclosing call to [`with_lock_token`](Self::with_lock_token),
+        /// calling `f()` with the token.
+        ///
+        /// As the token proves that the mutex is already held by the current
+        /// call chain, this does not lock the wrapped mutex again, and the lock
+        /// remains held until the outermost critical section ends.
+        ///
+        /// # Panics
+        ///
+        /// Panics if `token` was issued by a different mutex.
+        #[inline]
+        #[track_caller]
+        pub fn with_lock_reentrant<'lock, U>(
+            &'lock self,
+            token: RecursionToken<'lock>,
+            f: impl FnOnce(RecursionToken<'lock>) -> U,
+        ) -> U {
+            assert!(
+                core::ptr::eq(token.mutex, (self as *const Self).cast()),
+                "`RecursionToken` was issued by a different `RecursionTokenRawMutex`",
+            );
+            debug_assert!(self.inner.is_locked());
+            f(token)
+        }
+    }
+
+    impl<M: ConstInit> ConstInit for RecursionTokenRawMutex<M> {
+        const INIT: Self = Self::new(M::INIT);
+    }
+
+    unsafe impl<M: ScopedRawMutex> ScopedRawMutex for RecursionTokenRawMutex<M> {
+        #[inline]
+        #[track_caller]
+        fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            self.inner.try_with_lock(f)
+        }
+
+        #[inline]
+        #[track_caller]
+        fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            self.inner.with_lock(f)
+        }
+
+        fn is_locked(&self) -> bool {
+            self.inner.is_locked()
+        }
+
+        fn is_locked_acquire(&self) -> bool {
+            self.inner.is_locked_acquire()
+        }
+
+        fn is_owned(&self) -> bool {
+            self.inner.is_owned()
+        }
+    }
+
+    impl<M: DetectsDeadlock> DetectsDeadlock for RecursionTokenRawMutex<M> {}
+}
+
+// ================
+
+// Requires atomic read-modify-write operations, as the counter is shared by
+// all cores.
+#[cfg(any(target_has_atomic = "ptr", feature = "portable-atomic"))]
+pub mod barrier {
+    //! Tracking of in-progress critical sections, for quiescing on shutdown
+    //!
+    //! This module provides [`BarrierTrackedRawMutex`], a wrapper around
+    //! another [`ScopedRawMutex`] which counts how many
+    //! `BarrierTrackedRawMutex`es are currently held, across all threads and
+    //! cores, using a single global counter. Shutdown code may then call
+    //! [`wait_all_released`] to wait until no such critical section is in
+    //! progress, e.g. before powering down a peripheral which they access.
+    //!
+    //! Note that [`wait_all_released`] does not prevent new critical sections
+    //! from starting after it returns. Shutdown code must separately ensure
+    //! that no new critical sections are started, e.g. by first disabling the
+    //! interrupts or stopping the tasks which take the locks.
+
+    use super::rmw::AtomicUsize;
+    use super::*;
+
+    static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+
+    /// A [`ScopedRawMutex`] wrapper which is counted by the global count of
+    /// in-progress critical sections while it is held.
+    ///
+    /// See the [module-level documentation](self) for details.
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct BarrierTrackedRawMutex<M> {
+        inner: M,
+    }
+
+    /// Returns the number of [`BarrierTrackedRawMutex`]es currently held.
+    pub fn active_critical_sections() -> usize {
+        ACTIVE.load(Ordering::Acquire)
+    }
+
+    /// Spins until no [`BarrierTrackedRawMutex`] is held.
+    ///
+    /// When this returns, all critical sections which were in progress have
+    /// completed, and their effects are visible to the calling thread.
+    ///
+    /// This must not be called while holding a `BarrierTrackedRawMutex`, as
+    /// it would then spin forever.
+    pub fn wait_all_released() {
+        while active_critical_sections() != 0 {
+            core::hint::spin_loop();
+        }
+    }
+
+    impl<M> BarrierTrackedRawMutex<M> {
+        /// Create a new `BarrierTrackedRawMutex` wrapping `inner`.
+        pub const fn new(inner: M) -> Self {
+            Self { inner }
+        }
+
+        /// Returns a reference to the wrapped mutex.
+        pub fn inner(&self) -> &M {
+            &self.inner
+        }
+    }
+
+    impl<M: ConstInit> ConstInit for BarrierTrackedRawMutex<M> {
+        const INIT: Self = Self::new(M::INIT);
+    }
+
+    unsafe impl<M: ScopedRawMutex> ScopedRawMutex for BarrierTrackedRawMutex<M> {
+        #[inline]
+        #[track_caller]
+        fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            self.inner.try_with_lock(|| {
+                let _active = ActiveGuard::enter();
+                f()
+            })
+        }
+
+        #[inline]
+        #[track_caller]
+        fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            self.inner.with_lock(|| {
+                let _active = ActiveGuard::enter();
+                f()
+            })
+        }
+
+        fn is_locked(&self) -> bool {
+            self.inner.is_locked()
+        }
+
+        fn is_locked_acquire(&self) -> bool {
+            self.inner.is_locked_acquire()
+        }
+
+        fn is_owned(&self) -> bool {
+            self.inner.is_owned()
+        }
+    }
+
+    impl<M: DetectsDeadlock> DetectsDeadlock for BarrierTrackedRawMutex<M> {}
+
+    /// Increments the count of active critical sections when created, and
+    /// decrements it when dropped, even if the closure panics.
+    struct ActiveGuard(());
+
+    impl ActiveGuard {
+        #[inline]
+        fn enter() -> Self {
+            ACTIVE.fetch_add(1, Ordering::Relaxed);
+            Self(())
+        }
+    }
+
+    impl Drop for ActiveGuard {
+        #[inline]
+        fn drop(&mut self) {
+            // Release, so that the effects of the critical section are
+            // visible to `wait_all_released` once it observes the count
+            // reach zero.
+            ACTIVE.fetch_sub(1, Ordering::Release);
+        }
+    }
+}
+
+// ================
+
+#[cfg(feature = "warn-long-hold")]
+pub mod long_hold {
+    //! An adapter which logs a warning when a lock is held for too long
+
+    use super::*;
+    use core::panic::Location;
+    use core::sync::atomic::AtomicU64;
+    use core::time::Duration;
+    use std::time::Instant;
+
+    /// The threshold above which a warning is logged, in nanoseconds.
+    static THRESHOLD_NANOS: AtomicU64 = AtomicU64::new(1_000_000);
+
+    /// Sets the duration for which a [`WarnLongHold`] mutex may be held
+    /// before a warning is logged. The default is 1 millisecond.
+    ///
+    /// This applies to all `WarnLongHold` mutexes.
+    pub fn set_long_hold_threshold(threshold: Duration) {
+        let nanos = u64::try_from(threshold.as_nanos()).unwrap_or(u64::MAX);
+        THRESHOLD_NANOS.store(nanos, Ordering::Relaxed);
+    }
+
+    /// Returns the current threshold set by [`set_long_hold_threshold`].
+    pub fn long_hold_threshold() -> Duration {
+        Duration::from_nanos(THRESHOLD_NANOS.load(Ordering::Relaxed))
+    }
+
+    /// An adapter which logs a warning when the wrapped [`ScopedRawMutex`] is
+    /// held for longer than the [threshold](set_long_hold_threshold).
+    ///
+    /// The time for which the lock is held is measured from when it is
+    /// acquired until the closure returns. If it exceeds the threshold, a
+    /// warning is emitted using [`log::warn!`] *after* the lock has been
+    /// released, including the source location at which the lock was taken.
+    /// Unlike [`BlockingMutex::with_lock_budget`], this is purely
+    /// observational, and is intended for finding accidentally long critical
+    /// sections in production.
+    ///
+    /// [`log::warn!`]: https://docs.rs/log/0.4/log/macro.warn.html
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct WarnLongHold<M> {
+        inner: M,
+    }
+
+    impl<M> WarnLongHold<M> {
+        /// Create a new `WarnLongHold` wrapping `inner`.
+        pub const fn new(inner: M) -> Self {
+            Self { inner }
+        }
+
+        /// Returns a reference to the wrapped mutex.
+        pub fn inner(&self) -> &M {
+            &self.inner
+        }
+    }
+
+    impl<M: ConstInit> ConstInit for WarnLongHold<M> {
+        const INIT: Self = Self::new(M::INIT);
+    }
+
+    unsafe impl<M: ScopedRawMutex> ScopedRawMutex for WarnLongHold<M> {
+        #[inline]
+        #[track_caller]
+        fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            let location = Location::caller();
+            let mut held = Duration::ZERO;
+            let ret = self.inner.try_with_lock(|| timed(&mut held, f));
+            warn_if_long(held, location);
+            ret
+        }
+
+        #[inline]
+        #[track_caller]
+        fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            let location = Location::caller();
+            let mut held = Duration::ZERO;
+            let ret = self.inner.with_lock(|| timed(&mut held, f));
+            warn_if_long(held, location);
+            ret
+        }
+
+        fn is_locked(&self) -> bool {
+            self.inner.is_locked()
+        }
+
+        fn is_locked_acquire(&self) -> bool {
+            self.inner.is_locked_acquire()
+        }
+
+        fn is_owned(&self) -> bool {
+            self.inner.is_owned()
+        }
+    }
+
+    impl<M: DetectsDeadlock> DetectsDeadlock for WarnLongHold<M> {}
+
+    #[inline(always)]
+    fn timed<R>(held: &mut Duration, f: impl FnOnce() -> R) -> R {
+        let start = Instant::now();
+        let ret = f();
+        *held = start.elapsed();
+        ret
+    }
+
+    #[inline]
+    fn warn_if_long(held: Duration, location: &Location<'_>) {
+        let threshold = long_hold_threshold();
+        if held > threshold {
+            log::warn!("lock taken at {location} was held for {held:?}, exceeding {threshold:?}");
+        }
+    }
+}
+
+// ================
+
+#[cfg(feature = "usage-check")]
+pub mod usage_checked {
+    //! An adapter which logs a warning if a mutex is never locked
+
+    use super::*;
+    #[cfg(debug_assertions)]
+    use core::panic::Location;
+
+    /// An adapter which logs a warning when it is dropped if the wrapped
+    /// [`ScopedRawMutex`] was never successfully locked.
+    ///
+    /// A mutex which is never locked usually indicates a logic bug: the data
+    /// it "protects" is actually accessed through some other path (or not at
+    /// all), and the synchronization is dead code. When a `UsageCheckedRawMutex`
+    /// which was never locked is dropped, a warning is emitted using
+    /// [`log::warn!`], including the source location at which it was
+    /// created.
+    ///
+    /// This is purely a diagnostic aid: in builds with debug assertions
+    /// disabled, usage is not tracked, no warning is emitted, and this adapter
+    /// only forwards to the wrapped mutex. Note that mutexes in `static`s are
+    /// never dropped, and so are never checked.
+    ///
+    /// [`log::warn!`]: https://docs.rs/log/0.4/log/macro.warn.html
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct UsageCheckedRawMutex<M> {
+        inner: M,
+        #[cfg(debug_assertions)]
+        used: AtomicBool,
+        #[cfg(debug_assertions)]
+        location: &'static Location<'static>,
+    }
+
+    impl<M> UsageCheckedRawMutex<M> {
+        /// Create a new `UsageCheckedRawMutex` wrapping `inner`.
+        ///
+        /// The location of the call to this function is included in the
+        /// warning emitted if the mutex is never locked.
+        #[track_caller]
+        pub const fn new(inner: M) -> Self {
+            Self {
+                inner,
+                #[cfg(debug_assertions)]
+                used: AtomicBool::new(false),
+                #[cfg(debug_assertions)]
+                location: Location::caller(),
+            }
+        }
+
+        /// Returns a reference to the wrapped mutex.
+        pub fn inner(&self) -> &M {
+            &self.inner
+        }
+
+        #[inline(always)]
+        fn mark_used(&self) {
+            #[cfg(debug_assertions)]
+            self.used.store(true, Ordering::Relaxed);
+        }
+    }
+
+    impl<M: ConstInit> ConstInit for UsageCheckedRawMutex<M> {
+        const INIT: Self = Self::new(M::INIT);
+    }
+
+    unsafe impl<M: ScopedRawMutex> ScopedRawMutex for UsageCheckedRawMutex<M> {
+        #[inline]
+        fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            self.inner.try_with_lock(|| {
+                self.mark_used();
+                f()
+            })
+        }
+
+        #[inline]
+        fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            self.inner.with_lock(|| {
+                self.mark_used();
+                f()
+            })
+        }
+
+        fn is_locked(&self) -> bool {
+            self.inner.is_locked()
+        }
+
+        fn is_locked_acquire(&self) -> bool {
+            self.inner.is_locked_acquire()
+        }
+
+        fn is_owned(&self) -> bool {
+            self.inner.is_owned()
+        }
+    }
+
+    impl<M: DetectsDeadlock> DetectsDeadlock for UsageCheckedRawMutex<M> {}
+
+    impl<M> Drop for UsageCheckedRawMutex<M> {
+        fn drop(&mut self) {
+            #[cfg(debug_assertions)]
+            if !*self.used.get_mut() {
+                log::warn!(
+                    "mutex created at {} was dropped without ever being locked",
+                    self.location
+                );
+            }
+        }
+    }
+}
+
+// ================
+
+#[cfg(feature = "async-misuse-check")]
+pub mod yield_check {
+    //! An adapter which panics if a lock is held across a yield point
+    //!
+    //! A [`BlockingMutex`] must not be held across an `.await` in async code:
+    //! if the task holding the lock yields, and another task on the same
+    //! executor attempts to lock the mutex, that task will block (or deadlock)
+    //! the executor, rather than yielding to allow the lock to be released.
+    //! [`AsyncMisuseGuardRawMutex`] detects this by asking the executor how
+    //! many times tasks have yielded, through the [`YieldDetector`] trait.
+
+    use super::*;
+    #[cfg(debug_assertions)]
+    use core::cell::UnsafeCell;
+    use mutex_traits::RawMutex;
+
+    /// A source of the number of times tasks have yielded to the executor.
+    ///
+    /// This is implemented by (or for) an async executor, which increments the
+    /// count each time a task it is running yields, i.e. each time a call to
+    /// a task's `Future::poll` returns.
+    pub trait YieldDetector {
+        /// Returns the number of times a task has yielded to the executor on
+        /// which the caller is running.
+        ///
+        /// The count may wrap around; it is only compared for equality.
+        fn yield_count() -> u64;
+    }
+
+    /// An adapter which panics if the wrapped [`RawMutex`] is held while a
+    /// task yields to the executor.
+    ///
+    /// When the lock is acquired, the current yield count is read from `D`.
+    /// When the lock is released, the count is read again and, if it has
+    /// changed, a task yielded while the lock was held, i.e. a
+    /// [`MutexGuard`](crate::MutexGuard) was held across an `.await`. This
+    /// causes a panic.
+    ///
+    /// This is purely a diagnostic aid: in builds with debug assertions
+    /// disabled, the yield count is never read, and this adapter only forwards
+    /// to the wrapped mutex.
+    ///
+    /// # Panics
+    ///
+    /// The panic occurs when the lock is released, which for a guard-based
+    /// lock is in [`MutexGuard`](crate::MutexGuard)'s `Drop` impl. If the
+    /// guard is dropped while the thread is already unwinding from another
+    /// panic, this panic therefore aborts the process. The lock is released
+    /// before panicking, so it is not left locked if the panic is caught.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use core::sync::atomic::{AtomicU64, Ordering};
+    /// use mutex::BlockingMutex;
+    /// use mutex::raw_impls::spin::RandomizedSpinRawMutex;
+    /// use mutex::raw_impls::yield_check::{AsyncMisuseGuardRawMutex, YieldDetector};
+    ///
+    /// // Incremented by the executor each time a task yields.
+    /// static YIELDS: AtomicU64 = AtomicU64::new(0);
+    ///
+    /// struct Executor;
+    ///
+    /// impl YieldDetector for Executor {
+    ///     fn yield_count() -> u64 {
+    ///         YIELDS.load(Ordering::Relaxed)
+    ///     }
+    /// }
+    ///
+    /// type Raw = AsyncMisuseGuardRawMutex<RandomizedSpinRawMutex, Executor>;
+    /// static DATA: BlockingMutex<Raw, u32> = BlockingMutex::new(0);
+    ///
+    /// let mut guard = DATA.lock();
+    /// *guard += 1;
+    /// // Dropping the guard without yielding is fine.
+    /// drop(guard);
+    /// ```
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct AsyncMisuseGuardRawMutex<M, D> {
+        inner: M,
+        /// The yield count when the lock was acquired. This is only accessed
+        /// while the lock is held.
+        #[cfg(debug_assertions)]
+        locked_at: UnsafeCell<u64>,
+        _detector: PhantomData<fn() -> D>,
+    }
+
+    // SAFETY: `locked_at` is only accessed by the context holding the lock.
+    unsafe impl<M: Sync, D> Sync for AsyncMisuseGuardRawMutex<M, D> {}
+
+    impl<M, D> AsyncMisuseGuardRawMutex<M, D> {
+        /// Create a new `AsyncMisuseGuardRawMutex` wrapping `inner`.
+        pub const fn new(inner: M) -> Self {
+            Self {
+                inner,
+                #[cfg(debug_assertions)]
+                locked_at: UnsafeCell::new(0),
+                _detector: PhantomData,
+            }
+        }
+
+        /// Returns a reference to the wrapped mutex.
+        pub fn inner(&self) -> &M {
+            &self.inner
+        }
+    }
+
+    impl<M: RawMutex, D: YieldDetector> AsyncMisuseGuardRawMutex<M, D> {
+        /// Records the yield count. Must only be called after acquiring the
+        /// lock.
+        #[inline(always)]
+        fn locked(&self) {
+            #[cfg(debug_assertions)]
+            // SAFETY: the lock is held, so no other context accesses
+            // `locked_at`.
+            unsafe {
+                *self.locked_at.get() = D::yield_count()
+            };
+        }
+
+        /// Checks that the yield count is unchanged. Must only be called
+        /// before releasing the lock.
+        #[inline(always)]
+        fn unlocking(&self) {
+            #[cfg(debug_assertions)]
+            {
+                // SAFETY: the lock is held, so no other context accesses
+                // `locked_at`.
+                let locked_at = unsafe { *self.locked_at.get() };
+                let yields = D::yield_count().wrapping_sub(locked_at);
+                if yields != 0 {
+                    // Release the lock before panicking, so that it is not
+                    // held forever.
+                    // SAFETY: this is only called from `unlock` and
+                    // `unlock_and_wake`, whose callers guarantee that the lock
+                    // is held.
+                    unsafe { self.inner.unlock() };
+                    panic!(
+                        "`{}` was held across {yields} yield point(s); blocking \
+                         mutexes must not be held across an `.await`",
+                        core::any::type_name::<M>(),
+                    );
+                }
+            }
+        }
+    }
+
+    impl<M: ConstInit, D> ConstInit for AsyncMisuseGuardRawMutex<M, D> {
+        const INIT: Self = Self::new(M::INIT);
+    }
+
+    unsafe impl<M: RawMutex, D: YieldDetector> RawMutex for AsyncMisuseGuardRawMutex<M, D> {
+        type GuardMarker = M::GuardMarker;
+
+        #[inline]
+        fn lock(&self) {
+            self.inner.lock();
+            self.locked();
+        }
+
+        #[inline]
+        fn try_lock(&self) -> bool {
+            let locked = self.inner.try_lock();
+            if locked {
+                self.locked();
+            }
+            locked
+        }
+
+        #[inline]
+        unsafe fn unlock(&self) {
+            self.unlocking();
+            self.inner.unlock();
+        }
+
+        #[inline]
+        unsafe fn unlock_and_wake(&self) {
+            self.unlocking();
+            self.inner.unlock_and_wake();
+        }
+
+        #[inline]
+        fn is_locked(&self) -> bool {
+            RawMutex::is_locked(&self.inner)
+        }
+
+        #[inline]
+        fn is_locked_acquire(&self) -> bool {
+            RawMutex::is_locked_acquire(&self.inner)
+        }
+
+        #[inline]
+        fn is_owned(&self) -> bool {
+            RawMutex::is_owned(&self.inner)
+        }
+    }
+
+    impl<M: RawMutex + DetectsDeadlock, D: YieldDetector> DetectsDeadlock
+        for AsyncMisuseGuardRawMutex<M, D>
+    {
+    }
+}
+
+// ================
+
+// Requires 64-bit atomics for the generation counter.
+#[cfg(all(
+    feature = "generation-check",
+    any(target_has_atomic = "64", feature = "portable-atomic")
+))]
+pub mod generation {
+    //! An adapter which detects unbalanced unlocks using a generation counter
+    //!
+    //! A [`RawMutex`] implementation bug which calls `unlock` without a
+    //! matching `lock` (a "spurious unlock") is hard to diagnose: the mutex
+    //! may be locked again by another context before the original holder
+    //! releases it, so a check that the mutex is locked when it is released
+    //! (such as the one in [`MutexGuard`]'s `Drop` impl) passes, even though
+    //! two contexts believed they held the lock. [`GenerationCheckedRawMutex`]
+    //! detects this by numbering each acquisition of the lock.
+    //!
+    //! [`MutexGuard`]: crate::MutexGuard
+
+    use super::*;
+    use mutex_traits::RawMutex;
+    #[cfg(debug_assertions)]
+    use rmw::AtomicU64;
+
+    /// The value of `held` when the mutex is not locked.
+    #[cfg(debug_assertions)]
+    const UNLOCKED: u64 = 0;
+
+    /// An adapter which panics if the wrapped [`RawMutex`] is unlocked when
+    /// it is not held, or by a context other than the one which locked it.
+    ///
+    /// Each time the lock is acquired, a 64-bit generation counter is
+    /// incremented, and the new generation is recorded as the generation of
+    /// the current holder. When the lock is released:
+    ///
+    /// - [`unlock`](RawMutex::unlock) panics if no holder is recorded, i.e.
+    ///   the mutex was already unlocked.
+    /// - [`unlock_checked`](Self::unlock_checked) additionally panics if the
+    ///   recorded generation is not the one the caller captured when it
+    ///   locked the mutex, i.e. the mutex was spuriously unlocked and then
+    ///   locked again by another context in the meantime.
+    ///
+    /// [`BlockingMutex::lock_checked`] returns a [`GenerationGuard`], which
+    /// captures the generation when it is created and releases the lock
+    /// using `unlock_checked`.
+    ///
+    /// This is purely a diagnostic aid: in builds with debug assertions
+    /// disabled, the generation is not tracked, and this adapter only
+    /// forwards to the wrapped mutex.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mutex::BlockingMutex;
+    /// use mutex::raw_impls::generation::GenerationCheckedRawMutex;
+    /// use mutex::raw_impls::spin::RandomizedSpinRawMutex;
+    ///
+    /// type Raw = GenerationCheckedRawMutex<RandomizedSpinRawMutex>;
+    /// static DATA: BlockingMutex<Raw, u32> = BlockingMutex::new(0);
+    ///
+    /// let mut guard = DATA.lock_checked();
+    /// *guard += 1;
+    /// // Panics if the lock was released and re-acquired elsewhere while
+    /// // the guard was held.
+    /// drop(guard);
+    /// ```
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct GenerationCheckedRawMutex<M> {
+        inner: M,
+        /// The generation of the most recent acquisition of the lock.
+        #[cfg(debug_assertions)]
+        generation: AtomicU64,
+        /// The generation of the current holder of the lock, or `UNLOCKED`.
+        #[cfg(debug_assertions)]
+        held: AtomicU64,
+    }
+
+    /// A RAII guard for a [`BlockingMutex`] using a
+    /// [`GenerationCheckedRawMutex`], which checks that the lock is still
+    /// held by the acquisition that created it when it is dropped.
+    ///
+    /// This is returned by [`BlockingMutex::lock_checked`].
+    #[must_use]
+    pub struct GenerationGuard<'mutex, M: RawMutex, T: ?Sized> {
+        lock: &'mutex BlockingMutex<GenerationCheckedRawMutex<M>, T>,
+        generation: u64,
+        _marker: PhantomData<M::GuardMarker>,
+    }
+
+    // === impl GenerationCheckedRawMutex ===
+
+    impl<M> GenerationCheckedRawMutex<M> {
+        /// Create a new `GenerationCheckedRawMutex` wrapping `inner`.
+        pub const fn new(inner: M) -> Self {
+            Self {
+                inner,
+                #[cfg(debug_assertions)]
+                generation: AtomicU64::new(0),
+                #[cfg(debug_assertions)]
+                held: AtomicU64::new(0),
+            }
+        }
+
+        /// Returns a reference to the wrapped mutex.
+        pub fn inner(&self) -> &M {
+            &self.inner
+        }
+
+        /// Returns the generation of the current holder of the lock, or 0 if
+        /// the lock is not held.
+        ///
+        /// A context which has just acquired the lock may capture this value,
+        /// and pass it to [`unlock_checked`](Self::unlock_checked) when
+        /// releasing the lock.
+        ///
+        /// This always returns 0 if debug assertions are disabled.
+        pub fn generation(&self) -> u64 {
+            #[cfg(debug_assertions)]
+            {
+                self.held.load(Ordering::Relaxed)
+            }
+
+            #[cfg(not(debug_assertions))]
+            {
+                0
+            }
+        }
+    }
+
+    impl<M: RawMutex> GenerationCheckedRawMutex<M> {
+        /// Records a new generation. Must only be called after acquiring the
+        /// lock.
+        #[inline(always)]
+        fn locked(&self) {
+            #[cfg(debug_assertions)]
+            {
+                // The counter starts at zero, so the first generation is 1,
+                // and `UNLOCKED` is never a valid generation (barring
+                // wrap-around after 2^64 acquisitions).
+                let generation = self.generation.fetch_add(1, Ordering::Relaxed) + 1;
+                self.held.store(generation, Ordering::Relaxed);
+            }
+        }
+
+        /// Clears the recorded holder, checking that it matches `expected`
+        /// if one is provided. Must only be called before releasing the lock.
+        #[inline(always)]
+        #[track_caller]
+        fn unlocking(&self, expected: Option<u64>) {
+            #[cfg(debug_assertions)]
+            match expected {
+                Some(expected) => {
+                    if let Err(held) = self.held.compare_exchange(
+                        expected,
+                        UNLOCKED,
+                        Ordering::Relaxed,
+                        Ordering::Relaxed,
+                    ) {
+                        // NOTE: the lock is not released here, as it is held
+                        // by another context (or by nobody).
+                        panic!(
+                            "unbalanced unlock detected: `{}` was locked by \
+                             generation {expected}, but is held by generation \
+                             {held} when it is unlocked",
+                            core::any::type_name::<M>(),
+                        );
+                    }
+                }
+                None => {
+                    if self.held.swap(UNLOCKED, Ordering::Relaxed) == UNLOCKED {
+                        panic!(
+                            "unbalanced unlock detected: `{}` was not locked \
+                             when it was unlocked",
+                            core::any::type_name::<M>(),
+                        );
+                    }
+                }
+            }
+            #[cfg(not(debug_assertions))]
+            let _ = expected;
+        }
+
+        /// Unlocks this mutex, checking that it is still held by the
+        /// acquisition with the given `generation`.
+        ///
+        /// # Panics
+        ///
+        /// In builds with debug assertions enabled, panics with "unbalanced
+        /// unlock detected" if the current holder of the lock does not have
+        /// the given generation. The lock is *not* released in this case.
+        ///
+        /// # Safety
+        ///
+        /// This method has the same safety requirements as
+        /// [`RawMutex::unlock`]: it may only be called if the mutex is held in
+        /// the current context.
+        #[inline]
+        #[track_caller]
+        pub unsafe fn unlock_checked(&self, generation: u64) {
+            self.unlocking(Some(generation));
+            self.inner.unlock_and_wake();
+        }
+    }
+
+    impl<M: ConstInit> ConstInit for GenerationCheckedRawMutex<M> {
+        const INIT: Self = Self::new(M::INIT);
+    }
+
+    unsafe impl<M: RawMutex> RawMutex for GenerationCheckedRawMutex<M> {
+        type GuardMarker = M::GuardMarker;
+
+        #[inline]
+        fn lock(&self) {
+            self.inner.lock();
+            self.locked();
+        }
+
+        #[inline]
+        fn try_lock(&self) -> bool {
+            let locked = self.inner.try_lock();
+            if locked {
+                self.locked();
+            }
+            locked
+        }
+
+        #[inline]
+        #[track_caller]
+        unsafe fn unlock(&self) {
+            self.unlocking(None);
+            self.inner.unlock();
+        }
+
+        #[inline]
+        #[track_caller]
+        unsafe fn unlock_and_wake(&self) {
+            self.unlocking(None);
+            self.inner.unlock_and_wake();
+        }
+
+        #[inline]
+        fn is_locked(&self) -> bool {
+            RawMutex::is_locked(&self.inner)
+        }
+
+        #[inline]
+        fn is_locked_acquire(&self) -> bool {
+            RawMutex::is_locked_acquire(&self.inner)
+        }
+
+        #[inline]
+        fn is_owned(&self) -> bool {
+            RawMutex::is_owned(&self.inner)
+        }
+    }
+
+    impl<M: RawMutex + DetectsDeadlock> DetectsDeadlock for GenerationCheckedRawMutex<M> {}
+
+    impl<M: RawMutex, T: ?Sized> BlockingMutex<GenerationCheckedRawMutex<M>, T> {
+        /// Locks this mutex, returning a [`GenerationGuard`] which checks that
+        /// the lock is still held by this acquisition when it is dropped.
+        ///
+        /// This is equivalent to [`BlockingMutex::lock`], except that the
+        /// guard panics with "unbalanced unlock detected" when it is dropped
+        /// if the lock was released and re-acquired while it was held.
+        #[inline]
+        pub fn lock_checked(&self) -> GenerationGuard<'_, M, T> {
+            self.raw.lock();
+            GenerationGuard {
+                lock: self,
+                generation: self.raw.generation(),
+                _marker: PhantomData,
+            }
+        }
+    }
+
+    // === impl GenerationGuard ===
+
+    impl<M: RawMutex, T: ?Sized> GenerationGuard<'_, M, T> {
+        /// Returns the generation of the acquisition which created this
+        /// guard.
+        ///
+        /// This is always 0 if debug assertions are disabled.
+        pub fn generation(this: &Self) -> u64 {
+            this.generation
+        }
+    }
+
+    impl<M: RawMutex, T: ?Sized> Drop for GenerationGuard<'_, M, T> {
+        fn drop(&mut self) {
+            unsafe {
+                // SAFETY: a `GenerationGuard` is only created when the lock
+                // has been acquired, so we are allowed to unlock it.
+                self.lock.raw.unlock_checked(self.generation);
+            }
+        }
+    }
+
+    impl<M: RawMutex, T: ?Sized> core::ops::Deref for GenerationGuard<'_, M, T> {
+        type Target = T;
+
+        #[inline]
+        fn deref(&self) -> &Self::Target {
+            unsafe {
+                // SAFETY: a `GenerationGuard` is only constructed once the
+                // lock is locked, and the lock is not unlocked until the guard
+                // is dropped.
+                &*self.lock.data.get()
+            }
+        }
+    }
+
+    impl<M: RawMutex, T: ?Sized> core::ops::DerefMut for GenerationGuard<'_, M, T> {
+        #[inline]
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            unsafe {
+                // SAFETY: a `GenerationGuard` is only constructed once the
+                // lock is locked, and the lock is not unlocked until the guard
+                // is dropped.
+                &mut *self.lock.data.get()
+            }
+        }
+    }
+
+    #[cfg(feature = "fmt")]
+    impl<M, T> core::fmt::Debug for GenerationGuard<'_, M, T>
+    where
+        M: RawMutex,
+        T: ?Sized + core::fmt::Debug,
+    {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            f.debug_struct("GenerationGuard")
+                .field("data", &&**self)
+                .field("generation", &self.generation)
+                .finish()
+        }
+    }
+}
+
+// ================
+
+#[cfg(feature = "deadlock-detection")]
+pub mod tracked {
+    //! An adapter which detects potential deadlocks caused by inconsistent
+    //! lock ordering
+
+    use super::*;
+    use core::panic::Location;
+    use core::sync::atomic::AtomicUsize;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::sync::{Mutex, PoisonError};
+
+    /// The next ID to assign to a [`TrackedRawMutex`]. IDs start at 1, so that
+    /// 0 may represent an unassigned ID.
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+    /// The lock orderings observed so far. An entry `a -> b -> location`
+    /// records that the lock with ID `b` was acquired at `location` while the
+    /// lock with ID `a` was held.
+    static ORDERINGS: Mutex<Option<HashMap<usize, HashMap<usize, &'static Location<'static>>>>> =
+        Mutex::new(None);
+
+    std::thread_local! {
+        /// The IDs of the locks held by the current thread, and where they
+        /// were acquired.
+        static HELD: RefCell<Vec<(usize, &'static Location<'static>)>> =
+            const { RefCell::new(Vec::new()) };
+    }
+
+    /// An adapter which detects inconsistent lock ordering, which may lead to
+    /// deadlock, between all `TrackedRawMutex`es.
+    ///
+    /// Each `TrackedRawMutex` is assigned a unique ID. The locks held by each
+    /// thread are tracked, and whenever a lock is acquired while others are
+    /// held, the ordering is recorded in a global registry. If acquiring a
+    /// lock would introduce a cycle in the recorded orderings (for example,
+    /// if lock `A` was previously acquired while holding lock `B`, and lock
+    /// `B` is now being acquired while holding lock `A`), this panics *before*
+    /// acquiring the lock, with the IDs and source locations of the
+    /// conflicting acquisitions. This detects potential deadlocks even if
+    /// they do not actually occur in a particular test run.
+    ///
+    /// Locks acquired using [`try_with_lock`](ScopedRawMutex::try_with_lock)
+    /// cannot deadlock, so they do not record orderings, but are tracked as
+    /// held while the closure runs.
+    ///
+    /// This is intended for use in testing: tracking is only performed when
+    /// debug assertions are enabled, and requires a global lock, so it adds
+    /// significant overhead.
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct TrackedRawMutex<M> {
+        inner: M,
+        id: AtomicUsize,
+    }
+
+    impl<M> TrackedRawMutex<M> {
+        /// Create a new `TrackedRawMutex` wrapping `inner`.
+        pub const fn new(inner: M) -> Self {
+            Self {
+                inner,
+                id: AtomicUsize::new(0),
+            }
+        }
+
+        /// Returns a reference to the wrapped mutex.
+        pub fn inner(&self) -> &M {
+            &self.inner
+        }
+
+        /// Returns the unique ID of this mutex, which is included in the
+        /// panic message when a potential deadlock is detected.
+        pub fn id(&self) -> usize {
+            let id = self.id.load(Ordering::Acquire);
+            if id != 0 {
+                return id;
+            }
+            let new = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+            match self
+                .id
+                .compare_exchange(0, new, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => new,
+                Err(id) => id,
+            }
+        }
+    }
+
+    impl<M: ConstInit> ConstInit for TrackedRawMutex<M> {
+        const INIT: Self = Self::new(M::INIT);
+    }
+
+    unsafe impl<M: ScopedRawMutex> ScopedRawMutex for TrackedRawMutex<M> {
+        #[inline]
+        #[track_caller]
+        fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            if !cfg!(debug_assertions) {
+                return self.inner.try_with_lock(f);
+            }
+            let location = Location::caller();
+            let id = self.id();
+            self.inner.try_with_lock(|| {
+                let _held = Held::push(id, location);
+                f()
+            })
+        }
+
+        #[inline]
+        #[track_caller]
+        fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            if !cfg!(debug_assertions) {
+                return self.inner.with_lock(f);
+            }
+            let location = Location::caller();
+            let id = self.id();
+            check_ordering(id, location);
+            self.inner.with_lock(|| {
+                let _held = Held::push(id, location);
+                f()
+            })
+        }
+
+        fn is_locked(&self) -> bool {
+            self.inner.is_locked()
+        }
+
+        fn is_locked_acquire(&self) -> bool {
+            self.inner.is_locked_acquire()
+        }
+
+        fn is_owned(&self) -> bool {
+            self.inner.is_owned()
+        }
+    }
+
+    impl<M: DetectsDeadlock> DetectsDeadlock for TrackedRawMutex<M> {}
+
+    /// Records that the lock `id` is being acquired at `location` while the
+    /// current thread's held locks are held, panicking if this conflicts with
+    /// a previously recorded ordering.
+    #[track_caller]
+    fn check_ordering(id: usize, location: &'static Location<'static>) {
+        let held = HELD.with(|held| held.borrow().clone());
+        if held.is_empty() {
+            return;
+        }
+
+        let mut guard = ORDERINGS.lock().unwrap_or_else(PoisonError::into_inner);
+        let orderings = guard.get_or_insert_with(HashMap::new);
+        for &(held_id, held_location) in &held {
+            if held_id == id {
+                // Reentrant locking is detected by the inner mutex, if it is
+                // able to do so.
+                continue;
+            }
+            if orderings
+                .get(&held_id)
+                .is_some_and(|after| after.contains_key(&id))
+            {
+                continue;
+            }
+            if let Some((from, to, conflict)) = find_path(orderings, id, held_id) {
+                drop(guard);
+                panic!(
+                    "potential deadlock: lock {id} acquired at {location} while \
+                     holding lock {held_id} (acquired at {held_location}), but \
+                     lock {to} was previously acquired at {conflict} while \
+                     holding lock {from}",
+                );
+            }
+            orderings.entry(held_id).or_default().insert(id, location);
+        }
+    }
+
+    /// Searches the recorded orderings for a path from `from` to `to`,
+    /// returning the last edge of the path (which ends at `to`) if one exists.
+    fn find_path(
+        orderings: &HashMap<usize, HashMap<usize, &'static Location<'static>>>,
+        from: usize,
+        to: usize,
+    ) -> Option<(usize, usize, &'static Location<'static>)> {
+        let mut stack = Vec::from([from]);
+        let mut visited = Vec::new();
+        while let Some(node) = stack.pop() {
+            if visited.contains(&node) {
+                continue;
+            }
+            visited.push(node);
+            for (&next, &location) in orderings.get(&node).into_iter().flatten() {
+                if next == to {
+                    return Some((node, next, location));
+                }
+                stack.push(next);
+            }
+        }
+        None
+    }
+
+    /// Drop guard which records a lock as held by the current thread.
+    struct Held(usize);
+
+    impl Held {
+        fn push(id: usize, location: &'static Location<'static>) -> Self {
+            HELD.with(|held| held.borrow_mut().push((id, location)));
+            Self(id)
+        }
+    }
+
+    impl Drop for Held {
+        fn drop(&mut self) {
+            HELD.with(|held| {
+                let mut held = held.borrow_mut();
+                if let Some(idx) = held.iter().rposition(|&(id, _)| id == self.0) {
+                    held.remove(idx);
+                }
+            });
+        }
+    }
+}
+
+// ================
+
+#[cfg(feature = "std")]
+pub mod assert_thread_local {
+    //! An adapter for using single-threaded mutexes in `Send` contexts
+
+    use super::*;
+    use std::sync::OnceLock;
+    use std::thread::{self, ThreadId};
+
+    /// An adapter which makes a [`ScopedRawMutex`] that is not [`Send`] or
+    /// [`Sync`] (such as a [`LocalRawMutex`]) appear to be both.
+    ///
+    /// This is an escape hatch for passing data which is only ever accessed
+    /// from a single thread to APIs which require [`Send`] or [`Sync`]. When
+    /// debug assertions are enabled, the ID of the thread which first locks the
+    /// mutex is recorded, and all subsequent locks panic if they occur on a
+    /// different thread.
+    ///
+    /// [`LocalRawMutex`]: super::local::LocalRawMutex
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct AssertThreadLocal<M> {
+        inner: M,
+        owner: OnceLock<ThreadId>,
+    }
+
+    // SAFETY: the caller of `AssertThreadLocal::new` guarantees that the mutex
+    // is only ever used from a single thread.
+    unsafe impl<M> Send for AssertThreadLocal<M> {}
+    unsafe impl<M> Sync for AssertThreadLocal<M> {}
+
+    impl<M> AssertThreadLocal<M> {
+        /// Create a new `AssertThreadLocal` wrapping `inner`.
+        ///
+        /// # Safety
+        ///
+        /// The returned mutex must only ever be used (including being dropped)
+        /// on a single thread. This is only checked at runtime when debug
+        /// assertions are enabled, and only when the mutex is locked or
+        /// [`is_locked`](ScopedRawMutex::is_locked) is called.
+        pub const unsafe fn new(inner: M) -> Self {
+            Self {
+                inner,
+                owner: OnceLock::new(),
+            }
+        }
+
+        /// Returns a reference to the wrapped mutex.
+        pub fn inner(&self) -> &M {
+            &self.inner
+        }
+
+        #[inline]
+        #[track_caller]
+        fn check_thread(&self) {
+            if cfg!(debug_assertions) {
+                let current = thread::current().id();
+                let owner = *self.owner.get_or_init(|| current);
+                assert_eq!(
+                    owner, current,
+                    "`AssertThreadLocal` mutex locked on a different thread \
+                     than the one which first locked it",
+                );
+            }
+        }
+    }
+
+    unsafe impl<M: ScopedRawMutex> ScopedRawMutex for AssertThreadLocal<M> {
+        #[inline]
+        #[track_caller]
+        fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            self.check_thread();
+            self.inner.try_with_lock(f)
+        }
+
+        #[inline]
+        #[track_caller]
+        fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            self.check_thread();
+            self.inner.with_lock(f)
+        }
+
+        #[track_caller]
+        fn is_locked(&self) -> bool {
+            self.check_thread();
+            self.inner.is_locked()
+        }
+
+        #[track_caller]
+        fn is_locked_acquire(&self) -> bool {
+            self.check_thread();
+            self.inner.is_locked_acquire()
+        }
+
+        #[track_caller]
+        fn is_owned(&self) -> bool {
+            self.check_thread();
+            self.inner.is_owned()
+        }
+    }
+
+    impl<M: DetectsDeadlock> DetectsDeadlock for AssertThreadLocal<M> {}
+}
+
+// ================
+
+#[cfg(feature = "test-util")]
+pub mod scripted {
+    //! Mock implementation which injects contention, for testing
+
+    use super::*;
+    use core::cell::Cell;
+
+    /// A mutex whose [`try_with_lock`] fails according to a script, for
+    /// testing code which handles contention.
+    ///
+    /// A `ScriptedRawMutex` is constructed with a sequence of attempt
+    /// outcomes. Each call to [`try_with_lock`] consumes the next outcome:
+    /// `false` means that the attempt fails as if the mutex were held by
+    /// another context, and `true` means that it succeeds. Once the script
+    /// has been exhausted, every attempt succeeds. For example, a script of
+    /// `&[false, false, true]` fails twice and then succeeds. This allows
+    /// testing retry and back-off logic deterministically, without real
+    /// threads.
+    ///
+    /// Regardless of the script, an attempt to lock the mutex while it is
+    /// actually locked (i.e. reentrantly) fails, just like a
+    /// [`LocalRawMutex`].
+    ///
+    /// [`with_lock`] calls [`try_with_lock`] repeatedly, consuming failures
+    /// from the script as if it were spinning, until an attempt succeeds. If
+    /// the mutex is actually locked, it panics, as the lock can never be
+    /// acquired.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mutex::BlockingMutex;
+    /// use mutex::raw_impls::scripted::ScriptedRawMutex;
+    ///
+    /// let mutex = BlockingMutex::const_new(ScriptedRawMutex::new(&[false, false, true]), 0);
+    ///
+    /// assert_eq!(mutex.try_with_lock(|x| *x + 1), None);
+    /// assert_eq!(mutex.try_with_lock(|x| *x + 1), None);
+    /// assert_eq!(mutex.try_with_lock(|x| *x + 1), Some(1));
+    /// assert_eq!(mutex.raw().attempts(), 3);
+    /// ```
+    ///
+    /// [`try_with_lock`]: ScopedRawMutex::try_with_lock
+    /// [`with_lock`]: ScopedRawMutex::with_lock
+    /// [`LocalRawMutex`]: super::local::LocalRawMutex
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct ScriptedRawMutex<'script> {
+        script: &'script [bool],
+        attempts: Cell<usize>,
+        taken: Cell<bool>,
+        count: LockCount,
+    }
+
+    impl<'script> ScriptedRawMutex<'script> {
+        /// Create a new `ScriptedRawMutex`, whose lock attempts succeed or
+        /// fail according to `script`.
+        pub const fn new(script: &'script [bool]) -> Self {
+            Self {
+                script,
+                attempts: Cell::new(0),
+                taken: Cell::new(false),
+                count: LockCount::new(),
+            }
+        }
+
+        /// Returns the number of times an attempt to lock this mutex has been
+        /// made, whether or not it succeeded.
+        pub fn attempts(&self) -> usize {
+            self.attempts.get()
+        }
+
+        /// Returns the number of times this mutex has been successfully
+        /// locked.
+        pub fn lock_count(&self) -> usize {
+            self.count.get()
+        }
+    }
+
+    unsafe impl ScopedRawMutex for ScriptedRawMutex<'_> {
+        #[inline]
+        fn try_with_lock<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+            run_locked(
+                || {
+                    let attempt = self.attempts.get();
+                    self.attempts.set(attempt + 1);
+                    let scripted = self.script.get(attempt).copied().unwrap_or(true);
+                    if !scripted || self.taken.get() {
+                        return false;
+                    }
+                    self.taken.set(true);
+                    self.count.increment();
+                    true
+                },
+                || self.taken.set(false),
+                f,
+            )
+        }
+
+        #[inline]
+        fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+            let mut f = Some(f);
+            loop {
+                if self.taken.get() {
+                    // This mutex is `!Sync`, so it can only have been locked
+                    // by the current thread, which we would wait for forever.
+                    deadlocked("Deadlocked");
+                }
+                // `f` is only taken if the lock was obtained, after which we
+                // return, so it is always present here.
+                if let Some(ret) = self.try_with_lock(|| f.take().map(|f| f())) {
+                    return ret.expect("closure is only called once");
+                }
+                core::hint::spin_loop();
+            }
+        }
+
+        fn is_locked(&self) -> bool {
+            self.taken.get()
+        }
+
+        /// As this mutex is `!Sync`, it can only be locked by the current
+        /// thread, so it is owned by the current context whenever it is
+        /// locked.
+        fn is_owned(&self) -> bool {
+            self.is_locked()
+        }
+    }
+}
+
+// ================
+
+#[cfg(feature = "instrument")]
+pub mod histogram {
+    //! Instrumentation recording a histogram of lock contention
+
+    use super::*;
+    use core::sync::atomic::AtomicUsize;
+    use mutex_traits::RawMutex;
+
+    /// The number of buckets in a [`HistogramRawMutex`]'s histogram.
+    pub const BUCKETS: usize = 4;
+
+    /// A [`RawMutex`] which can report how many times it retried acquiring
+    /// the lock, for use with [`HistogramRawMutex`].
+    ///
+    /// # Safety
+    ///
+    /// [`lock_counting_retries`] must acquire the lock, exactly as
+    /// [`RawMutex::lock`] does.
+    ///
+    /// [`lock_counting_retries`]: CountRetries::lock_counting_retries
+    pub unsafe trait CountRetries: RawMutex {
+        /// Acquires this mutex, blocking the current thread/CPU core until it
+        /// is able to do so, and returns the number of failed attempts to
+        /// acquire it.
+        ///
+        /// For a spinlock, this is the number of times it spun before
+        /// acquiring the lock. A return value of 0 means that the lock was
+        /// uncontended.
+        fn lock_counting_retries(&self) -> usize;
+    }
+
+    /// A [`RawMutex`] which records a histogram of the number of retries
+    /// needed to acquire the lock.
+    ///
+    /// Each time the mutex is locked, the number of failed attempts to
+    /// acquire the inner mutex, as reported by [`CountRetries`], is recorded
+    /// in one of the following buckets:
+    ///
+    /// | Index | Retries |
+    /// |-------|---------|
+    /// | 0     | 0       |
+    /// | 1     | 1-3     |
+    /// | 2     | 4-15    |
+    /// | 3     | 16+     |
+    ///
+    /// This provides a profile of how contended the lock is, for tuning
+    /// performance. The histogram is returned by
+    /// [`histogram`](Self::histogram). A successful
+    /// [`try_lock`](RawMutex::try_lock) is recorded as an uncontended
+    /// acquisition; failed calls to `try_lock` are not recorded.
+    ///
+    /// The buckets are only updated while the lock is held, so they do not
+    /// require atomic read-modify-write operations. They are `usize`s,
+    /// rather than `u64`s, so that they may be used on targets without
+    /// 64-bit atomics.
+    #[cfg_attr(feature = "fmt", derive(Debug))]
+    pub struct HistogramRawMutex<M> {
+        inner: M,
+        buckets: [AtomicUsize; BUCKETS],
+    }
+
+    impl<M> HistogramRawMutex<M> {
+        /// Wraps `inner`, recording a histogram of retries needed to acquire
+        /// it.
+        pub const fn new(inner: M) -> Self {
+            Self {
+                inner,
+                buckets: [const { AtomicUsize::new(0) }; BUCKETS],
+            }
+        }
+
+        /// Returns the number of times the lock has been acquired after a
+        /// number of retries within each bucket.
+        ///
+        /// If the lock is held by another context, the returned values may
+        /// not include its most recent acquisition.
+        pub fn histogram(&self) -> [usize; BUCKETS] {
+            core::array::from_fn(|i| self.buckets[i].load(Ordering::Relaxed))
+        }
+
+        /// Returns a reference to the inner mutex.
+        pub fn inner(&self) -> &M {
+            &self.inner
+        }
+
+        /// Records an acquisition after `retries` failed attempts. This must
+        /// only be called while the lock is held.
+        #[inline]
+        fn record(&self, retries: usize) {
+            let bucket = match retries {
+                0 => 0,
+                1..=3 => 1,
+                4..=15 => 2,
+                _ => 3,
+            };
+            // NOTE: separated load/stores are acceptable as this is only
+            // called while the lock is held.
+            let count = &self.buckets[bucket];
+            count.store(count.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+        }
+    }
+
+    impl<M: ConstInit> ConstInit for HistogramRawMutex<M> {
+        const INIT: Self = Self::new(M::INIT);
+    }
+
+    unsafe impl<M: CountRetries> RawMutex for HistogramRawMutex<M> {
+        type GuardMarker = M::GuardMarker;
+
+        #[inline]
+        fn lock(&self) {
+            let retries = self.inner.lock_counting_retries();
+            self.record(retries);
+        }
+
+        #[inline]
+        fn try_lock(&self) -> bool {
+            let locked = self.inner.try_lock();
+            if locked {
+                self.record(0);
+            }
+            locked
+        }
+
+        #[inline]
+        unsafe fn unlock(&self) {
+            self.inner.unlock()
+        }
+
+        #[inline]
+        unsafe fn unlock_and_wake(&self) {
+            self.inner.unlock_and_wake()
+        }
+
+        #[inline]
+        fn is_locked(&self) -> bool {
+            self.inner.is_locked()
+        }
+
+        #[inline]
+        fn is_locked_acquire(&self) -> bool {
+            self.inner.is_locked_acquire()
+        }
+
+        #[inline]
+        fn is_owned(&self) -> bool {
+            self.inner.is_owned()
+        }
+    }
+
+    #[cfg(any(target_has_atomic = "8", feature = "portable-atomic"))]
+    unsafe impl<O: super::spin::LockOrdering> CountRetries for super::spin::RandomizedSpinRawMutex<O> {
+        #[inline]
+        fn lock_counting_retries(&self) -> usize {
+            self.lock_counting_retries()
         }
     }
 }
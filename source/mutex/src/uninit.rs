@@ -0,0 +1,168 @@
+//! Mutexes protecting data which is initialized after the mutex is created.
+//!
+//! This module provides [`UninitMutex`], which serves the common pattern of
+//! reserving space for a value in a `static`, and initializing it later (e.g.
+//! at boot).
+
+use core::mem::MaybeUninit;
+
+use crate::{BlockingMutex, ConstInit, ScopedRawMutex};
+
+/// A [`BlockingMutex`] whose data may be initialized after the mutex is
+/// created.
+///
+/// The mutex tracks whether the data has been initialized, and only grants
+/// access to it once it has been. Attempting to access the data before it is
+/// initialized returns an [`Uninit`] error.
+pub struct UninitMutex<R, T> {
+    inner: BlockingMutex<R, Slot<T>>,
+}
+
+/// Error returned when accessing the data in an [`UninitMutex`] that has not
+/// yet been initialized.
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "fmt", derive(Debug))]
+pub struct Uninit(());
+
+/// A possibly-initialized value, and a flag tracking whether it has been
+/// initialized.
+struct Slot<T> {
+    init: bool,
+    value: MaybeUninit<T>,
+}
+
+// === impl UninitMutex ===
+
+impl<R: ConstInit, T> UninitMutex<R, T> {
+    /// Creates a new, uninitialized mutex in an unlocked state.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            inner: BlockingMutex::new(Slot::UNINIT),
+        }
+    }
+}
+
+impl<R: ConstInit, T> Default for UninitMutex<R, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R, T> UninitMutex<R, T> {
+    /// Creates a new, uninitialized mutex based on a pre-existing raw mutex.
+    #[inline]
+    pub const fn const_new(raw_mutex: R) -> Self {
+        Self {
+            inner: BlockingMutex::const_new(raw_mutex, Slot::UNINIT),
+        }
+    }
+
+    /// Consumes this mutex, returning the underlying data if it was
+    /// initialized.
+    #[inline]
+    pub fn into_inner(self) -> Option<T> {
+        self.inner.into_inner().take()
+    }
+}
+
+impl<R: ScopedRawMutex, T> UninitMutex<R, T> {
+    /// Locks the raw mutex and initializes the inner data with `val`.
+    ///
+    /// If the data was already initialized, the previous value is returned.
+    pub fn write(&self, val: T) -> Option<T> {
+        self.inner.with_lock(|slot| {
+            let prev = slot.take();
+            slot.value.write(val);
+            slot.init = true;
+            prev
+        })
+    }
+
+    /// Locks the raw mutex and grants temporary access to the inner data.
+    ///
+    /// Returns an [`Uninit`] error without calling `f` if the data has not yet
+    /// been initialized.
+    ///
+    /// Behavior when the lock is already locked is dependent on the behavior
+    /// of the Raw mutex. See [`ScopedRawMutex::with_lock()`]'s documentation for
+    /// more details
+    pub fn with_lock<U>(&self, f: impl FnOnce(&mut T) -> U) -> Result<U, Uninit> {
+        self.inner.with_lock(|slot| slot.get_mut().map(f))
+    }
+
+    /// Locks the raw mutex and grants temporary access to the inner data
+    ///
+    /// Returns `None` if the lock was already locked. Returns
+    /// `Some(Err(Uninit))` without calling `f` if the data has not yet been
+    /// initialized.
+    #[must_use]
+    pub fn try_with_lock<U>(&self, f: impl FnOnce(&mut T) -> U) -> Option<Result<U, Uninit>> {
+        self.inner.try_with_lock(|slot| slot.get_mut().map(f))
+    }
+
+    /// Locks the raw mutex and returns whether the inner data has been
+    /// initialized.
+    pub fn is_initialized(&self) -> bool {
+        self.inner.with_lock(|slot| slot.init)
+    }
+}
+
+#[cfg(feature = "fmt")]
+impl<R, T> core::fmt::Debug for UninitMutex<R, T>
+where
+    R: ScopedRawMutex + core::fmt::Debug,
+    T: core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut s = f.debug_struct("UninitMutex");
+        s.field("raw", self.inner.raw());
+
+        self.try_with_lock(|data| s.field("data", &data).finish())
+            .unwrap_or_else(|| Ok(s.field("data", &format_args!("<locked>")).finish()))
+            .unwrap_or_else(|_| s.field("data", &format_args!("<uninit>")).finish())
+    }
+}
+
+// === impl Uninit ===
+
+#[cfg(feature = "fmt")]
+impl core::fmt::Display for Uninit {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("mutex data has not been initialized")
+    }
+}
+
+// === impl Slot ===
+
+impl<T> Slot<T> {
+    const UNINIT: Self = Self {
+        init: false,
+        value: MaybeUninit::uninit(),
+    };
+
+    fn get_mut(&mut self) -> Result<&mut T, Uninit> {
+        if self.init {
+            // SAFETY: the value is initialized if the flag is set.
+            Ok(unsafe { self.value.assume_init_mut() })
+        } else {
+            Err(Uninit(()))
+        }
+    }
+
+    fn take(&mut self) -> Option<T> {
+        if core::mem::replace(&mut self.init, false) {
+            // SAFETY: the value was initialized if the flag was set, and we
+            // have cleared the flag, so it will not be read again.
+            Some(unsafe { self.value.assume_init_read() })
+        } else {
+            None
+        }
+    }
+}
+
+impl<T> Drop for Slot<T> {
+    fn drop(&mut self) {
+        drop(self.take());
+    }
+}
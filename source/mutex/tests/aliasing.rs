@@ -0,0 +1,129 @@
+//! Exercises the `UnsafeCell` accesses made by `BlockingMutex` and
+//! `MutexGuard` with the raw mutexes which can run on the host.
+//!
+//! These tests pass on any host, but are mainly intended to be run under Miri,
+//! which reports aliasing violations and data races that are invisible on real
+//! hardware:
+//!
+//! ```text
+//! cargo +nightly miri test -p mutex --features std --test aliasing
+//! ```
+#![cfg(feature = "impl-critical-section")]
+
+use mutex::raw_impls::cs::CriticalSectionRawMutex;
+use mutex::raw_impls::local::LocalRawMutex;
+use mutex::raw_impls::spin::RandomizedSpinRawMutex;
+use mutex::{BlockingMutex, ConstInit, RawMutex, ScopedRawMutex};
+
+/// Threads are slow under Miri, so fewer iterations are run there.
+const ITERS: usize = if cfg!(miri) { 16 } else { 1000 };
+
+/// Locks `mutex` while it is nested inside a lock on `outer`, writing through
+/// both closures' `&mut` references, and checks the results.
+fn nested_scoped<R: ScopedRawMutex + ConstInit>() {
+    let outer = BlockingMutex::<R, Vec<u32>>::new(Vec::new());
+    let inner = BlockingMutex::<R, u32>::new(0);
+
+    outer.with_lock(|outer_data| {
+        outer_data.push(1);
+        inner.with_lock(|inner_data| {
+            *inner_data += 1;
+            outer_data.push(*inner_data);
+        });
+        // The inner mutex is already unlocked, so its data may be read again.
+        assert_eq!(inner.try_with_lock(|inner_data| *inner_data), Some(1));
+    });
+
+    assert_eq!(outer.into_inner(), [1, 1]);
+}
+
+/// Holds a guard while reading and writing through it, and then checks that
+/// the data is accessible using `get_mut` once the guard is dropped.
+fn guard_access<R: RawMutex + ConstInit>() {
+    let mut mutex = BlockingMutex::<R, [u8; 4]>::new([0; 4]);
+
+    {
+        let mut guard = mutex.lock();
+        guard[0] = 1;
+        let first = &guard[0];
+        assert_eq!(*first, 1);
+        guard[3] = 4;
+        assert!(mutex.try_lock().is_none());
+    }
+
+    mutex.get_mut()[1] = 2;
+    assert_eq!(*mutex.lock(), [1, 2, 0, 4]);
+}
+
+/// Increments a counter from several threads.
+fn concurrent_increments<R: ScopedRawMutex + ConstInit + Sync>() {
+    const THREADS: usize = 4;
+
+    let mutex = BlockingMutex::<R, usize>::new(0);
+    std::thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for _ in 0..ITERS {
+                    mutex.with_lock(|n| *n += 1);
+                }
+            });
+        }
+    });
+    assert_eq!(mutex.into_inner(), THREADS * ITERS);
+}
+
+#[test]
+fn cs_nested() {
+    nested_scoped::<CriticalSectionRawMutex>();
+}
+
+#[test]
+fn cs_concurrent() {
+    concurrent_increments::<CriticalSectionRawMutex>();
+}
+
+#[test]
+fn local_nested() {
+    nested_scoped::<LocalRawMutex>();
+}
+
+#[test]
+fn spin_nested() {
+    nested_scoped::<RandomizedSpinRawMutex>();
+}
+
+#[test]
+fn spin_guard() {
+    guard_access::<RandomizedSpinRawMutex>();
+}
+
+#[test]
+fn spin_concurrent() {
+    concurrent_increments::<RandomizedSpinRawMutex>();
+}
+
+#[cfg(feature = "std")]
+mod std_sync {
+    use super::*;
+    use mutex::raw_impls::std_sync::{StdRawMutex, StdScopedRawMutex};
+
+    #[test]
+    fn std_guard() {
+        guard_access::<StdRawMutex>();
+    }
+
+    #[test]
+    fn std_concurrent() {
+        concurrent_increments::<StdRawMutex>();
+    }
+
+    #[test]
+    fn std_scoped_nested() {
+        nested_scoped::<StdScopedRawMutex>();
+    }
+
+    #[test]
+    fn std_scoped_concurrent() {
+        concurrent_increments::<StdScopedRawMutex>();
+    }
+}
@@ -0,0 +1,51 @@
+//! Tests for `CanaryMutex`.
+//!
+//! Corruption is simulated by writing over the canaries through a raw pointer
+//! to a heap-allocated `CanaryMutex`, while no references to it exist.
+#![cfg(all(feature = "canary", feature = "impl-critical-section"))]
+
+use mutex::canary::CanaryMutex;
+use mutex::raw_impls::cs::CriticalSectionRawMutex;
+
+type Mutex = CanaryMutex<CriticalSectionRawMutex, u32>;
+
+/// Allocates a `CanaryMutex`, overwrites the `usize` at byte `offset` within
+/// it, and then locks it.
+#[cfg(debug_assertions)]
+fn lock_after_clobbering(offset: usize) {
+    let mutex = Box::into_raw(Box::new(Mutex::new(1)));
+    unsafe {
+        // SAFETY: `offset` is within the allocation, and suitably aligned, as
+        // the canaries are `usize`s at the start and end of the `repr(C)`
+        // struct. No references to the mutex exist while it is written to.
+        mutex
+            .cast::<u8>()
+            .add(offset)
+            .cast::<usize>()
+            .write_volatile(0);
+        let mutex = Box::from_raw(mutex);
+        mutex.with_lock(|data| *data += 1);
+    }
+}
+
+#[test]
+fn intact_canaries_do_not_panic() {
+    let mutex = Mutex::new(1);
+    mutex.with_lock(|data| *data += 1);
+    assert_eq!(mutex.try_with_lock(|data| *data), Some(2));
+    assert_eq!(mutex.into_inner(), 2);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "memory surrounding `CanaryMutex<_, u32>` was corrupted")]
+fn clobbered_head_canary_panics() {
+    lock_after_clobbering(0);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "memory surrounding `CanaryMutex<_, u32>` was corrupted")]
+fn clobbered_tail_canary_panics() {
+    lock_after_clobbering(std::mem::size_of::<Mutex>() - std::mem::size_of::<usize>());
+}
@@ -0,0 +1,378 @@
+//! Runs the `mutex_traits::conformance` checks against every raw mutex
+//! provided by this crate which can run on the host.
+//!
+//! The checks assert that `is_locked` returns `true` for the duration of the
+//! closure passed to `with_lock`/`try_with_lock`, and that the closure's
+//! return value is dropped only after the lock has been released.
+//!
+//! The implementations based on `critical-section` use the `std` critical
+//! section implementation enabled in this crate's `[dev-dependencies]`.
+
+use mutex::{ConstInit, ScopedRawMutex};
+use mutex_traits::conformance;
+
+/// Runs all conformance checks against a newly constructed `M`.
+fn check_init<M: ScopedRawMutex + ConstInit>() {
+    conformance::check_all(&M::INIT);
+}
+
+/// The guarantee checked by `return_value_dropped_outside_lock` also holds
+/// for the values returned through `BlockingMutex`.
+#[test]
+fn blocking_mutex_return_value_dropped_outside_lock() {
+    use mutex::raw_impls::local::LocalRawMutex;
+    use mutex::BlockingMutex;
+    use std::cell::Cell;
+
+    struct Relock<'a> {
+        mutex: &'a BlockingMutex<LocalRawMutex, u32>,
+        relocked: &'a Cell<Option<u32>>,
+    }
+
+    impl Drop for Relock<'_> {
+        fn drop(&mut self) {
+            self.relocked.set(self.mutex.try_with_lock(|data| *data));
+        }
+    }
+
+    let mutex = BlockingMutex::<LocalRawMutex, u32>::new(1);
+    let relocked = Cell::new(None);
+
+    drop(mutex.with_lock(|_| Relock {
+        mutex: &mutex,
+        relocked: &relocked,
+    }));
+    assert_eq!(relocked.take(), Some(1));
+
+    drop(mutex.try_with_lock(|_| Relock {
+        mutex: &mutex,
+        relocked: &relocked,
+    }));
+    assert_eq!(relocked.take(), Some(1));
+}
+
+/// Test implementations of the traits which some wrappers require from the
+/// scheduler or executor.
+mod hooks {
+    use core::num::NonZeroUsize;
+    use mutex::raw_impls::context::ExecutionContext;
+    use mutex::raw_impls::cooperative::Yield;
+    use mutex::raw_impls::donating::Priority;
+    use std::sync::OnceLock;
+    use std::thread::{self, ThreadId};
+
+    pub struct StdYield;
+
+    impl Yield for StdYield {
+        fn yield_now() {
+            thread::yield_now();
+        }
+    }
+
+    pub struct FixedPriority;
+
+    impl Priority for FixedPriority {
+        fn current_task() -> NonZeroUsize {
+            NonZeroUsize::MIN
+        }
+
+        fn current() -> u8 {
+            0
+        }
+
+        fn donate(_: NonZeroUsize, _: u8) {}
+
+        fn restore(_: NonZeroUsize) {}
+
+        fn yield_now() {
+            thread::yield_now();
+        }
+    }
+
+    /// An execution context consisting of the first thread to check whether
+    /// it is current.
+    pub struct FirstThread;
+
+    static FIRST_THREAD: OnceLock<ThreadId> = OnceLock::new();
+
+    // SAFETY: exactly one thread is ever in this context, so code in it never
+    // runs concurrently with other code in it.
+    unsafe impl ExecutionContext for FirstThread {
+        fn is_current() -> bool {
+            *FIRST_THREAD.get_or_init(|| thread::current().id()) == thread::current().id()
+        }
+    }
+}
+
+mod host {
+    use super::*;
+    use mutex::raw_impls::context::ContextRawMutex;
+    use mutex::raw_impls::depth::DepthTrackedRawMutex;
+    use mutex::raw_impls::local::LocalRawMutex;
+    use mutex::raw_impls::pair::PairRawMutex;
+    use mutex::raw_impls::recursion::RecursionTokenRawMutex;
+    use mutex::raw_impls::tiered::TieredRawMutex;
+
+    #[test]
+    fn local() {
+        check_init::<LocalRawMutex>();
+    }
+
+    #[test]
+    fn context() {
+        check_init::<ContextRawMutex<hooks::FirstThread>>();
+    }
+
+    #[test]
+    fn pair() {
+        check_init::<PairRawMutex<LocalRawMutex, LocalRawMutex>>();
+    }
+
+    #[test]
+    fn tiered() {
+        check_init::<TieredRawMutex<LocalRawMutex, LocalRawMutex>>();
+    }
+
+    #[test]
+    fn depth() {
+        check_init::<DepthTrackedRawMutex<LocalRawMutex, 4>>();
+    }
+
+    #[test]
+    fn recursion() {
+        check_init::<RecursionTokenRawMutex<LocalRawMutex>>();
+    }
+}
+
+#[cfg(any(target_has_atomic = "8", feature = "portable-atomic"))]
+mod atomic {
+    use super::*;
+    use mutex::raw_impls::cooperative::CooperativeRawMutex;
+    use mutex::raw_impls::hierarchical::HierarchicalRawMutex;
+    use mutex::raw_impls::lazy::LazyRawMutex;
+    use mutex::raw_impls::local::LocalRawMutex;
+    use mutex::raw_impls::spin::{RandomizedSpinRawMutex, SequentiallyConsistent};
+
+    #[test]
+    fn spin() {
+        check_init::<RandomizedSpinRawMutex>();
+        check_init::<RandomizedSpinRawMutex<SequentiallyConsistent>>();
+    }
+
+    #[test]
+    fn cooperative() {
+        check_init::<CooperativeRawMutex<hooks::StdYield>>();
+    }
+
+    #[test]
+    fn lazy() {
+        let mutex = LazyRawMutex::new(LocalRawMutex::new);
+        conformance::check_all(&mutex);
+    }
+
+    #[test]
+    fn hierarchical() {
+        let parent = LocalRawMutex::new();
+        conformance::check_all(&HierarchicalRawMutex::new(&parent));
+
+        let child = HierarchicalRawMutex::new(&parent);
+        child.with_parent_lock(|| conformance::check_all(&child));
+    }
+}
+
+#[cfg(any(target_has_atomic = "ptr", feature = "portable-atomic"))]
+mod atomic_ptr {
+    use super::*;
+    use mutex::raw_impls::barrier::BarrierTrackedRawMutex;
+    use mutex::raw_impls::donating::DonatingRawMutex;
+    use mutex::raw_impls::handoff::HandoffRawMutex;
+    use mutex::raw_impls::local::LocalRawMutex;
+
+    #[test]
+    fn handoff() {
+        check_init::<HandoffRawMutex<4>>();
+    }
+
+    #[test]
+    fn donating() {
+        check_init::<DonatingRawMutex<hooks::FixedPriority>>();
+    }
+
+    #[test]
+    fn barrier() {
+        check_init::<BarrierTrackedRawMutex<LocalRawMutex>>();
+    }
+}
+
+#[cfg(feature = "impl-critical-section")]
+mod critical_section {
+    use super::*;
+    use mutex::raw_impls::cs::CriticalSectionRawMutex;
+    use mutex::raw_impls::cs_raw::CriticalSectionFullRawMutex;
+    use mutex::raw_impls::dma::DmaCoherentRawMutex;
+    use mutex::raw_impls::pool::PoolRawMutex;
+
+    #[test]
+    fn cs() {
+        check_init::<CriticalSectionRawMutex>();
+        conformance::check_all(&CriticalSectionRawMutex::new_named("named"));
+    }
+
+    #[test]
+    fn cs_full() {
+        check_init::<CriticalSectionFullRawMutex>();
+    }
+
+    #[test]
+    fn dma() {
+        check_init::<DmaCoherentRawMutex>();
+    }
+
+    #[test]
+    fn pool() {
+        check_init::<PoolRawMutex>();
+    }
+}
+
+#[cfg(feature = "std")]
+mod std_sync {
+    use super::*;
+    use mutex::raw_impls::assert_thread_local::AssertThreadLocal;
+    use mutex::raw_impls::local::LocalRawMutex;
+    use mutex::raw_impls::std_sync::{StdRawMutex, StdScopedRawMutex};
+
+    #[test]
+    fn std() {
+        check_init::<StdRawMutex>();
+    }
+
+    #[test]
+    fn std_scoped() {
+        check_init::<StdScopedRawMutex>();
+    }
+
+    #[test]
+    fn assert_thread_local() {
+        // SAFETY: the mutex is only used by this thread.
+        let mutex = unsafe { AssertThreadLocal::new(LocalRawMutex::new()) };
+        conformance::check_all(&mutex);
+    }
+}
+
+#[cfg(all(feature = "linux-futex", target_os = "linux"))]
+#[test]
+fn futex() {
+    check_init::<mutex::raw_impls::futex::FutexRawMutex>();
+}
+
+#[cfg(feature = "impl-lock_api-0_4")]
+mod lock_api {
+    use super::*;
+    use lock_api_0_4::GuardSend;
+    use mutex::raw_impls::lock_api_0_4::LockApiRawMutex;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// A minimal spinlock implementing `lock_api::RawMutex`.
+    struct Spin(AtomicBool);
+
+    unsafe impl lock_api_0_4::RawMutex for Spin {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const INIT: Self = Spin(AtomicBool::new(false));
+
+        type GuardMarker = GuardSend;
+
+        fn lock(&self) {
+            while !self.try_lock() {
+                std::hint::spin_loop();
+            }
+        }
+
+        fn try_lock(&self) -> bool {
+            !self.0.swap(true, Ordering::Acquire)
+        }
+
+        unsafe fn unlock(&self) {
+            self.0.store(false, Ordering::Release);
+        }
+    }
+
+    #[test]
+    fn lock_api() {
+        check_init::<LockApiRawMutex<Spin>>();
+    }
+}
+
+#[cfg(feature = "test-util")]
+#[test]
+fn scripted() {
+    use mutex::raw_impls::scripted::ScriptedRawMutex;
+
+    // Scripted failures would (deliberately) make `try_with_lock` fail while
+    // the mutex is unlocked, so only an empty script is checked.
+    conformance::check_all(&ScriptedRawMutex::new(&[]));
+}
+
+#[cfg(feature = "instrument")]
+#[test]
+fn histogram() {
+    use mutex::raw_impls::histogram::HistogramRawMutex;
+    use mutex::raw_impls::spin::RandomizedSpinRawMutex;
+
+    check_init::<HistogramRawMutex<RandomizedSpinRawMutex>>();
+}
+
+#[cfg(feature = "warn-long-hold")]
+#[test]
+fn warn_long_hold() {
+    use mutex::raw_impls::local::LocalRawMutex;
+    use mutex::raw_impls::long_hold::WarnLongHold;
+
+    check_init::<WarnLongHold<LocalRawMutex>>();
+}
+
+#[cfg(feature = "usage-check")]
+#[test]
+fn usage_checked() {
+    use mutex::raw_impls::local::LocalRawMutex;
+    use mutex::raw_impls::usage_checked::UsageCheckedRawMutex;
+
+    check_init::<UsageCheckedRawMutex<LocalRawMutex>>();
+}
+
+#[cfg(feature = "async-misuse-check")]
+#[test]
+fn async_misuse_check() {
+    use mutex::raw_impls::spin::RandomizedSpinRawMutex;
+    use mutex::raw_impls::yield_check::{AsyncMisuseGuardRawMutex, YieldDetector};
+
+    struct NeverYields;
+
+    impl YieldDetector for NeverYields {
+        fn yield_count() -> u64 {
+            0
+        }
+    }
+
+    check_init::<AsyncMisuseGuardRawMutex<RandomizedSpinRawMutex, NeverYields>>();
+}
+
+#[cfg(all(
+    feature = "generation-check",
+    any(target_has_atomic = "64", feature = "portable-atomic")
+))]
+#[test]
+fn generation_check() {
+    use mutex::raw_impls::generation::GenerationCheckedRawMutex;
+    use mutex::raw_impls::spin::RandomizedSpinRawMutex;
+
+    check_init::<GenerationCheckedRawMutex<RandomizedSpinRawMutex>>();
+}
+
+#[cfg(feature = "deadlock-detection")]
+#[test]
+fn tracked() {
+    use mutex::raw_impls::local::LocalRawMutex;
+    use mutex::raw_impls::tracked::TrackedRawMutex;
+
+    check_init::<TrackedRawMutex<LocalRawMutex>>();
+}
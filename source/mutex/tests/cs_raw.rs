@@ -0,0 +1,36 @@
+//! Tests for `CriticalSectionFullRawMutex`.
+#![cfg(feature = "impl-critical-section")]
+
+use mutex::raw_impls::cs_raw::CriticalSectionFullRawMutex;
+use mutex::{BlockingMutex, ScopedRawMutex};
+use std::panic::{self, AssertUnwindSafe};
+
+#[test]
+fn nested_lock_and_unlock() {
+    let outer = CriticalSectionFullRawMutex::new();
+    let inner = CriticalSectionFullRawMutex::new();
+
+    unsafe {
+        // SAFETY: the mutexes are unlocked in the reverse order in which
+        // they were locked, on the same thread.
+        outer.lock();
+        inner.lock();
+        assert!(!inner.try_lock());
+        inner.unlock();
+        outer.unlock();
+    }
+
+    assert!(!outer.is_locked());
+    assert!(!inner.is_locked());
+}
+
+#[test]
+fn scoped_lock_is_released_on_panic() {
+    let mutex = BlockingMutex::<CriticalSectionFullRawMutex, u32>::new(0);
+
+    let res = panic::catch_unwind(AssertUnwindSafe(|| mutex.with_lock(|_| panic!("oh no"))));
+    assert!(res.is_err());
+
+    assert!(!mutex.raw().is_locked());
+    assert_eq!(mutex.try_with_lock(|data| *data), Some(0));
+}
@@ -0,0 +1,62 @@
+//! Tests for `DeferredMutex`.
+
+use mutex::deferred::DeferredMutex;
+use mutex::raw_impls::local::LocalRawMutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[test]
+fn deferred_while_locked() {
+    let mutex = DeferredMutex::<LocalRawMutex, Vec<u32>, 4>::new(Vec::new());
+
+    mutex.with_lock(|data| {
+        data.push(1);
+        assert!(mutex.with_lock_or_defer(|data| data.push(2)).is_ok());
+        assert!(mutex.with_lock_or_defer(|data| data.push(3)).is_ok());
+        assert_eq!(data, &[1]);
+    });
+    assert!(mutex.with_lock_or_defer(|data| data.push(4)).is_ok());
+
+    assert_eq!(mutex.into_inner(), [1, 2, 3, 4]);
+}
+
+/// Checks that every operation which was successfully deferred has run once
+/// all threads have finished, without locking the mutex again.
+#[cfg(any(target_has_atomic = "8", feature = "portable-atomic"))]
+#[test]
+fn no_stranded_operations() {
+    use mutex::raw_impls::spin::RandomizedSpinRawMutex;
+
+    const THREADS: usize = 4;
+    const ITERS: usize = if cfg!(miri) { 10 } else { 10_000 };
+
+    static RAN: AtomicUsize = AtomicUsize::new(0);
+
+    let mutex = DeferredMutex::<RandomizedSpinRawMutex, usize, 8>::new(0);
+    let deferred = AtomicUsize::new(0);
+
+    std::thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for _ in 0..ITERS {
+                    mutex.with_lock(|n| *n += 1);
+                }
+            });
+            s.spawn(|| {
+                for _ in 0..ITERS {
+                    let op = |_: &mut usize| {
+                        RAN.fetch_add(1, Ordering::Relaxed);
+                    };
+                    if mutex.with_lock_or_defer(op).is_ok() {
+                        deferred.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+    });
+
+    assert_eq!(
+        RAN.load(Ordering::Relaxed),
+        deferred.load(Ordering::Relaxed)
+    );
+    assert_eq!(mutex.into_inner(), THREADS * ITERS);
+}
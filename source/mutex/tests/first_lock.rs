@@ -0,0 +1,56 @@
+//! Tests for `FirstLockMutex`.
+//!
+//! These use `CriticalSectionRawMutex`, which is backed by the
+//! `critical-section` crate's `std` implementation on the host.
+#![cfg(feature = "impl-critical-section")]
+
+use mutex::first::FirstLockMutex;
+use mutex::raw_impls::cs::CriticalSectionRawMutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Barrier;
+
+#[test]
+fn only_first_acquisition_is_first() {
+    let mutex = FirstLockMutex::<CriticalSectionRawMutex, u32>::new(0);
+
+    assert!(mutex.with_lock_first(|_, first| first));
+    assert!(!mutex.with_lock_first(|_, first| first));
+    assert_eq!(mutex.try_with_lock_first(|_, first| first), Some(false));
+}
+
+#[test]
+fn with_lock_counts_as_first_acquisition() {
+    let mutex = FirstLockMutex::<CriticalSectionRawMutex, u32>::new(0);
+
+    mutex.with_lock(|data| *data += 1);
+    assert!(!mutex.with_lock_first(|_, first| first));
+    assert_eq!(mutex.into_inner(), 1);
+}
+
+#[test]
+fn concurrent_first_lockers_observe_one_first() {
+    const THREADS: usize = 8;
+
+    let mutex = FirstLockMutex::<CriticalSectionRawMutex, Vec<usize>>::new(Vec::new());
+    let firsts = AtomicUsize::new(0);
+    let barrier = Barrier::new(THREADS);
+
+    std::thread::scope(|s| {
+        for i in 0..THREADS {
+            let (mutex, firsts, barrier) = (&mutex, &firsts, &barrier);
+            s.spawn(move || {
+                barrier.wait();
+                mutex.with_lock_first(|data, first| {
+                    if first {
+                        assert!(data.is_empty(), "first locker saw initialized data");
+                        firsts.fetch_add(1, Ordering::Relaxed);
+                    }
+                    data.push(i);
+                });
+            });
+        }
+    });
+
+    assert_eq!(firsts.load(Ordering::Relaxed), 1);
+    assert_eq!(mutex.into_inner().len(), THREADS);
+}
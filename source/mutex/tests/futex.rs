@@ -0,0 +1,67 @@
+//! Contention tests for `FutexRawMutex`.
+#![cfg(all(feature = "linux-futex", target_os = "linux"))]
+
+use mutex::raw_impls::futex::FutexMutex;
+use mutex::RawMutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Barrier;
+use std::time::Duration;
+
+const THREADS: usize = 16;
+const ITERS: usize = if cfg!(miri) { 10 } else { 5_000 };
+
+#[test]
+fn many_threads_increment() {
+    let mutex = FutexMutex::new(0usize);
+    let barrier = Barrier::new(THREADS);
+
+    std::thread::scope(|s| {
+        for i in 0..THREADS {
+            let (mutex, barrier) = (&mutex, &barrier);
+            s.spawn(move || {
+                barrier.wait();
+                for _ in 0..ITERS {
+                    if i % 2 == 0 {
+                        mutex.with_lock(|n| *n += 1);
+                    } else {
+                        *mutex.lock() += 1;
+                    }
+                }
+            });
+        }
+    });
+
+    assert!(!mutex.raw().is_locked());
+    assert_eq!(mutex.into_inner(), THREADS * ITERS);
+}
+
+/// Holds the lock while other threads block on it, so that they wait in
+/// `FUTEX_WAIT`, and checks that unlocking wakes all of them.
+#[test]
+fn blocked_waiters_are_woken() {
+    let mutex = FutexMutex::new(Vec::new());
+    let started = AtomicUsize::new(0);
+
+    std::thread::scope(|s| {
+        let guard = mutex.lock();
+        for i in 0..THREADS {
+            let (mutex, started) = (&mutex, &started);
+            s.spawn(move || {
+                started.fetch_add(1, Ordering::Relaxed);
+                mutex.with_lock(|waiters| waiters.push(i));
+            });
+        }
+
+        while started.load(Ordering::Relaxed) < THREADS {
+            std::thread::yield_now();
+        }
+        // Give the waiters time to go to sleep on the futex.
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(mutex.try_lock().is_none());
+        drop(guard);
+    });
+
+    let mut waiters = mutex.into_inner();
+    waiters.sort_unstable();
+    assert_eq!(waiters, (0..THREADS).collect::<Vec<_>>());
+}
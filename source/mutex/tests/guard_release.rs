@@ -0,0 +1,70 @@
+//! Checks that `MutexGuard` only checks that the mutex is locked when debug
+//! assertions are enabled.
+//!
+//! `MutexGuard`'s `Deref`, `DerefMut`, and `Drop` impls `debug_assert!` that
+//! the mutex is locked. When debug assertions are disabled, this must not
+//! call `RawMutex::is_locked`, which typically performs an atomic load, as
+//! this would add a load to every dereference of a guard. This test uses a
+//! raw mutex which counts calls to `is_locked`, and checks that it is never
+//! called when this test is built without debug assertions. Run it with:
+//!
+//! ```text
+//! cargo test -p mutex --release --test guard_release
+//! ```
+
+use mutex::{BlockingMutex, RawMutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// A `RawMutex` which counts the number of calls to `is_locked`.
+struct CountingRawMutex {
+    locked: AtomicBool,
+    is_locked_calls: AtomicUsize,
+}
+
+unsafe impl RawMutex for CountingRawMutex {
+    type GuardMarker = ();
+
+    fn lock(&self) {
+        assert!(self.try_lock(), "mutex is already locked");
+    }
+
+    fn try_lock(&self) -> bool {
+        !self.locked.swap(true, Ordering::Acquire)
+    }
+
+    unsafe fn unlock(&self) {
+        self.locked.store(false, Ordering::Release);
+    }
+
+    fn is_locked(&self) -> bool {
+        self.is_locked_calls.fetch_add(1, Ordering::Relaxed);
+        self.locked.load(Ordering::Relaxed)
+    }
+}
+
+#[test]
+fn guard_checks_is_locked_only_with_debug_assertions() {
+    let mutex = BlockingMutex::const_new(
+        CountingRawMutex {
+            locked: AtomicBool::new(false),
+            is_locked_calls: AtomicUsize::new(0),
+        },
+        0u32,
+    );
+
+    let mut guard = mutex.lock();
+    *guard += 1;
+    assert_eq!(*guard, 1);
+    drop(guard);
+
+    let calls = mutex.raw().is_locked_calls.load(Ordering::Relaxed);
+    if cfg!(debug_assertions) {
+        // One call each for `deref_mut`, `deref`, and `drop`.
+        assert_eq!(calls, 3);
+    } else {
+        assert_eq!(
+            calls, 0,
+            "`MutexGuard` called `is_locked` in a release build"
+        );
+    }
+}
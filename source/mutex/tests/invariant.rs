@@ -0,0 +1,61 @@
+//! Tests for `InvariantMutex`.
+
+use mutex::invariant::InvariantMutex;
+use mutex::raw_impls::local::LocalRawMutex;
+
+fn is_even(n: &u32) -> bool {
+    n & 1 == 0
+}
+
+#[test]
+fn invariant_may_be_violated_within_closure() {
+    let mutex = InvariantMutex::<LocalRawMutex, u32>::new(0, is_even);
+
+    mutex.with_lock(|n| {
+        *n += 1;
+        assert!(!is_even(n));
+        *n += 1;
+    });
+    assert_eq!(mutex.try_with_lock(|n| *n), Some(2));
+    assert_eq!(mutex.into_inner(), 2);
+}
+
+#[test]
+fn try_new_checks_initial_value() {
+    assert!(InvariantMutex::<LocalRawMutex, u32>::try_new(2, is_even).is_ok());
+    assert_eq!(
+        InvariantMutex::<LocalRawMutex, u32>::try_new(3, is_even).err(),
+        Some(3)
+    );
+}
+
+#[cfg(debug_assertions)]
+#[test]
+fn violation_panics_and_releases_lock() {
+    use mutex::ScopedRawMutex;
+    use std::panic::{self, AssertUnwindSafe};
+
+    let mutex = InvariantMutex::<LocalRawMutex, u32>::new(0, is_even);
+
+    let res = panic::catch_unwind(AssertUnwindSafe(|| mutex.with_lock(|n| *n += 1)));
+    let msg = *res.unwrap_err().downcast::<String>().unwrap();
+    assert!(
+        msg.contains("invariant of `InvariantMutex<_, u32>` violated"),
+        "{msg}"
+    );
+    assert!(msg.contains(file!()), "{msg}");
+    assert!(!mutex.raw().is_locked());
+
+    let res = panic::catch_unwind(AssertUnwindSafe(|| mutex.try_with_lock(|n| *n += 2)));
+    assert!(res.is_err());
+    assert!(!mutex.raw().is_locked());
+}
+
+#[cfg(not(debug_assertions))]
+#[test]
+fn violation_is_not_checked_in_release() {
+    let mutex = InvariantMutex::<LocalRawMutex, u32>::new(0, is_even);
+
+    mutex.with_lock(|n| *n += 1);
+    assert_eq!(mutex.into_inner(), 1);
+}
@@ -0,0 +1,36 @@
+//! Tests for `MaxSizeMutex`.
+//!
+//! That data larger than `MAX` is rejected is checked by a `compile_fail`
+//! doctest on `MaxSizeMutex`; these tests check that data up to the limit is
+//! accepted, and that the wrapper otherwise behaves like a `BlockingMutex`.
+#![cfg(feature = "impl-critical-section")]
+
+use mutex::max_size::MaxSizeMutex;
+use mutex::raw_impls::cs::CriticalSectionRawMutex;
+
+#[test]
+fn data_of_exactly_max_size_is_accepted() {
+    static EXACT: MaxSizeMutex<CriticalSectionRawMutex, [u8; 64], 64> = MaxSizeMutex::new([0; 64]);
+
+    EXACT.with_lock(|data| data[63] = 1);
+    assert_eq!(EXACT.with_lock(|data| data[63]), 1);
+}
+
+#[test]
+fn zero_sized_data_is_accepted_with_zero_max() {
+    let mutex = MaxSizeMutex::<CriticalSectionRawMutex, (), 0>::new(());
+    mutex.with_lock(|()| {});
+}
+
+#[test]
+fn try_with_lock_fails_while_locked() {
+    let mutex = MaxSizeMutex::<CriticalSectionRawMutex, u32, 4>::new(1);
+
+    let nested = mutex.with_lock(|data| {
+        *data += 1;
+        mutex.try_with_lock(|data| *data)
+    });
+    assert_eq!(nested, None);
+    assert_eq!(mutex.try_with_lock(|data| *data), Some(2));
+    assert_eq!(mutex.into_inner(), 2);
+}
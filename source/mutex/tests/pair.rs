@@ -0,0 +1,55 @@
+//! Tests for `PairRawMutex`'s handling of a partial acquisition, where `A` is
+//! acquired but `B` is not.
+
+use mutex::raw_impls::local::LocalRawMutex;
+use mutex::raw_impls::pair::PairRawMutex;
+use mutex::{BlockingMutex, ScopedRawMutex};
+
+#[test]
+fn first_is_released_when_second_is_held() {
+    let mutex = BlockingMutex::<PairRawMutex<LocalRawMutex, LocalRawMutex>, u32>::new(0);
+    let pair = mutex.raw();
+
+    // Hold `B` separately, so that the pair can only acquire `A`.
+    let res = pair.second().with_lock(|| {
+        let res = mutex.try_with_lock(|data| *data += 1);
+        assert!(!pair.first().is_locked(), "`A` was not released");
+        res
+    });
+    assert_eq!(res, None);
+    assert!(!pair.is_locked());
+
+    // Both locks are free again, so the pair may now be acquired.
+    assert_eq!(mutex.try_with_lock(|data| *data + 1), Some(1));
+    assert_eq!(mutex.into_inner(), 0);
+}
+
+#[test]
+fn second_is_not_tried_when_first_is_held() {
+    let pair = PairRawMutex::new(LocalRawMutex::new(), LocalRawMutex::new());
+
+    let res = pair.first().with_lock(|| pair.try_with_lock(|| ()));
+    assert_eq!(res, None);
+    assert!(!pair.second().is_locked());
+}
+
+#[cfg(feature = "test-util")]
+#[test]
+fn scripted_rollback() {
+    use mutex::raw_impls::scripted::ScriptedRawMutex;
+
+    let pair = PairRawMutex::new(ScriptedRawMutex::new(&[]), ScriptedRawMutex::new(&[false]));
+
+    // `A` is acquired, but the attempt to acquire `B` fails, so `A` is
+    // released without running the closure.
+    assert_eq!(pair.try_with_lock(|| unreachable!()), None::<()>);
+    assert_eq!(pair.first().lock_count(), 1);
+    assert_eq!(pair.second().attempts(), 1);
+    assert_eq!(pair.second().lock_count(), 0);
+    assert!(!pair.first().is_locked());
+
+    // The script is exhausted, so the next attempt acquires both.
+    assert_eq!(pair.try_with_lock(|| pair.is_locked()), Some(true));
+    assert_eq!(pair.first().lock_count(), 2);
+    assert_eq!(pair.second().lock_count(), 1);
+}
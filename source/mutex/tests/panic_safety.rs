@@ -0,0 +1,43 @@
+//! Checks that a panic inside a critical section releases the lock, rather
+//! than leaving it locked forever.
+
+use mutex::raw_impls::local::LocalRawMutex;
+use mutex::{BlockingMutex, ConstInit, ScopedRawMutex};
+use std::panic::{self, AssertUnwindSafe};
+
+/// Panics inside `with_lock` and `try_with_lock`, and checks that the mutex
+/// may be locked again afterwards.
+fn relock_after_panic<R: ScopedRawMutex + ConstInit>() {
+    let mutex = BlockingMutex::<R, u32>::new(0);
+
+    let res = panic::catch_unwind(AssertUnwindSafe(|| {
+        mutex.with_lock(|data| {
+            *data += 1;
+            panic!("panicking in `with_lock`");
+        })
+    }));
+    assert!(res.is_err());
+    assert!(!mutex.raw().is_locked());
+    assert_eq!(mutex.with_lock(|data| *data), 1);
+
+    let res = panic::catch_unwind(AssertUnwindSafe(|| {
+        mutex.try_with_lock(|data| {
+            *data += 1;
+            panic!("panicking in `try_with_lock`");
+        })
+    }));
+    assert!(res.is_err());
+    assert!(!mutex.raw().is_locked());
+    assert_eq!(mutex.try_with_lock(|data| *data), Some(2));
+}
+
+#[test]
+fn local() {
+    relock_after_panic::<LocalRawMutex>();
+}
+
+#[cfg(feature = "impl-critical-section")]
+#[test]
+fn cs() {
+    relock_after_panic::<mutex::raw_impls::cs::CriticalSectionRawMutex>();
+}
@@ -0,0 +1,85 @@
+//! Tests for `PeripheralMutex`.
+
+use mutex::peripheral::PeripheralMutex;
+use mutex::raw_impls::local::LocalRawMutex;
+use std::panic::{self, AssertUnwindSafe};
+
+/// A stand-in for a peripheral singleton.
+#[derive(Debug, PartialEq)]
+struct Serial {
+    written: Vec<u8>,
+}
+
+impl Serial {
+    fn new() -> Self {
+        Self {
+            written: Vec::new(),
+        }
+    }
+}
+
+#[test]
+fn access_without_peripheral_is_an_error() {
+    let mutex = PeripheralMutex::<LocalRawMutex, Serial>::new();
+
+    assert!(!mutex.has_peripheral());
+    assert!(mutex.with_lock(|_| unreachable!()).is_err());
+    assert!(matches!(
+        mutex.try_with_lock(|_| unreachable!()),
+        Some(Err(_))
+    ));
+    assert_eq!(mutex.take(), None);
+}
+
+#[test]
+fn init_and_take() {
+    let mutex = PeripheralMutex::<LocalRawMutex, Serial>::new();
+
+    assert_eq!(mutex.init(Serial::new()), Ok(()));
+    assert!(mutex.has_peripheral());
+
+    // A second peripheral is handed back, leaving the first in place.
+    let second = Serial { written: vec![1] };
+    assert_eq!(mutex.init(second), Err(Serial { written: vec![1] }));
+
+    assert!(mutex.with_lock(|serial| serial.written.push(2)).is_ok());
+    assert_eq!(mutex.take(), Some(Serial { written: vec![2] }));
+    assert!(!mutex.has_peripheral());
+    assert_eq!(mutex.into_inner(), None);
+}
+
+#[test]
+fn lend_returns_peripheral() {
+    let mutex = PeripheralMutex::<LocalRawMutex, Serial>::new();
+
+    let (serial, ret) = mutex.lend(Serial::new(), || {
+        // Other contexts may use the peripheral while it is lent.
+        assert!(mutex.with_lock(|serial| serial.written.push(1)).is_ok());
+        mutex
+            .try_with_lock(|serial| serial.written.len())
+            .map(Result::ok)
+    });
+    assert_eq!(ret, Some(Some(1)));
+    assert_eq!(serial.written, [1]);
+    assert!(!mutex.has_peripheral());
+}
+
+#[test]
+fn lend_panics_if_taken() {
+    let mutex = PeripheralMutex::<LocalRawMutex, Serial>::new();
+
+    let res = panic::catch_unwind(AssertUnwindSafe(|| {
+        mutex.lend(Serial::new(), || drop(mutex.take()))
+    }));
+    assert!(res.is_err());
+}
+
+#[test]
+fn lend_panics_if_already_initialized() {
+    let mutex = PeripheralMutex::<LocalRawMutex, Serial>::new();
+    mutex.init(Serial::new()).unwrap();
+
+    let res = panic::catch_unwind(AssertUnwindSafe(|| mutex.lend(Serial::new(), || ())));
+    assert!(res.is_err());
+    assert!(mutex.has_peripheral());
+}
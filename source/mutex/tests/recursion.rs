@@ -0,0 +1,81 @@
+//! Tests for `RecursionTokenRawMutex`.
+//!
+//! These wrap `CriticalSectionRawMutex`, which panics when it is re-entered,
+//! so that re-entry which is not permitted by a token is detected.
+#![cfg(feature = "impl-critical-section")]
+
+use mutex::raw_impls::cs::CriticalSectionRawMutex;
+use mutex::raw_impls::recursion::{RecursionToken, RecursionTokenRawMutex};
+use mutex::ScopedRawMutex;
+
+type Lock = RecursionTokenRawMutex<CriticalSectionRawMutex>;
+
+fn new_lock() -> Lock {
+    RecursionTokenRawMutex::new(CriticalSectionRawMutex::new())
+}
+
+/// Recurses `n` times through `with_lock_reentrant`, returning the number of
+/// levels at which the lock was observed to be held.
+fn recurse(lock: &Lock, n: u32, token: RecursionToken<'_>) -> u32 {
+    let held = u32::from(lock.is_locked());
+    if n == 0 {
+        return held;
+    }
+    lock.with_lock_reentrant(token, |token| held + recurse(lock, n - 1, token))
+}
+
+#[test]
+fn token_permits_recursive_reentry() {
+    let lock = new_lock();
+
+    assert_eq!(lock.with_lock_token(|token| recurse(&lock, 16, token)), 17);
+    assert!(
+        !lock.is_locked(),
+        "lock was not released by the outermost scope"
+    );
+
+    // The lock may be taken again once the outermost scope has ended.
+    assert_eq!(
+        lock.try_with_lock_token(|token| recurse(&lock, 1, token)),
+        Some(2)
+    );
+}
+
+#[test]
+fn try_with_lock_token_fails_while_held() {
+    let lock = new_lock();
+
+    let nested = lock.with_lock_token(|_token| lock.try_with_lock_token(|_| ()));
+    assert_eq!(nested, None);
+}
+
+// Deadlocks only panic if none of the features which make them abort or hang
+// are enabled.
+#[test]
+#[cfg(not(any(
+    feature = "deadlock-abort",
+    feature = "deadlock-no-panic",
+    all(feature = "deadlock-hang-in-release", not(debug_assertions)),
+)))]
+#[should_panic]
+fn reentry_without_token_panics() {
+    let lock = new_lock();
+
+    lock.with_lock_token(|_token| {
+        lock.with_lock(|| {});
+    });
+}
+
+#[test]
+#[should_panic(expected = "issued by a different `RecursionTokenRawMutex`")]
+fn token_from_another_mutex_is_rejected() {
+    let a = new_lock();
+    let b = new_lock();
+
+    a.with_lock_token(|token| {
+        b.with_lock_token(|_| {
+            // `token` was issued by `a`, so it must not permit re-entering `b`.
+            b.with_lock_reentrant(token, |_| {});
+        });
+    });
+}
@@ -0,0 +1,96 @@
+//! Stress tests for `RandomizedSpinRawMutex` under contention from many
+//! threads.
+
+use mutex::raw_impls::spin::{RandomizedSpinRawMutex, SequentiallyConsistent};
+use mutex::{BlockingMutex, ConstInit, RawMutex};
+use std::sync::Barrier;
+
+const THREADS: usize = 8;
+const ITERS: usize = if cfg!(miri) { 10 } else { 10_000 };
+
+/// The data protected by the mutex, which detects overlapping critical
+/// sections.
+#[derive(Default)]
+struct State {
+    inside: bool,
+    count: usize,
+}
+
+impl State {
+    fn enter(&mut self) {
+        assert!(!self.inside, "two critical sections overlapped");
+        self.inside = true;
+    }
+
+    fn exit(&mut self) {
+        self.count += 1;
+        self.inside = false;
+    }
+}
+
+/// Has `THREADS` threads start at the same time, and lock the mutex `ITERS`
+/// times each, alternating between `with_lock` and guards, and checks that
+/// every thread finished and no critical sections overlapped.
+fn stress<R: RawMutex + ConstInit + Sync>() {
+    let mutex = BlockingMutex::<R, State>::new(State::default());
+    let barrier = Barrier::new(THREADS);
+
+    std::thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                barrier.wait();
+                for i in 0..ITERS {
+                    if i % 2 == 0 {
+                        mutex.with_lock(|state| {
+                            state.enter();
+                            std::hint::spin_loop();
+                            state.exit();
+                        });
+                    } else {
+                        let mut state = mutex.lock();
+                        state.enter();
+                        std::hint::spin_loop();
+                        state.exit();
+                    }
+                }
+            });
+        }
+    });
+
+    let state = mutex.into_inner();
+    assert!(!state.inside);
+    assert_eq!(state.count, THREADS * ITERS);
+}
+
+#[test]
+fn contended_acquire_release() {
+    stress::<RandomizedSpinRawMutex>();
+}
+
+#[test]
+fn contended_seq_cst() {
+    stress::<RandomizedSpinRawMutex<SequentiallyConsistent>>();
+}
+
+#[cfg(feature = "instrument")]
+#[test]
+fn contended_acquisitions_make_progress() {
+    use mutex::raw_impls::histogram::HistogramRawMutex;
+
+    let mutex = BlockingMutex::<HistogramRawMutex<RandomizedSpinRawMutex>, usize>::new(0);
+    std::thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for _ in 0..ITERS {
+                    mutex.with_lock(|n| *n += 1);
+                }
+            });
+        }
+    });
+
+    // Every acquisition is recorded in exactly one bucket, however many
+    // retries it took, so none of them were lost or left spinning.
+    let histogram = mutex.raw().histogram();
+    assert_eq!(histogram.iter().sum::<usize>(), THREADS * ITERS);
+    assert_eq!(mutex.into_inner(), THREADS * ITERS);
+}
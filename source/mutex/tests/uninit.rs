@@ -0,0 +1,53 @@
+//! Tests for `UninitMutex`.
+
+use mutex::raw_impls::local::LocalRawMutex;
+use mutex::uninit::UninitMutex;
+use std::rc::Rc;
+
+#[test]
+fn access_before_init_is_an_error() {
+    let mutex = UninitMutex::<LocalRawMutex, u32>::new();
+
+    assert!(!mutex.is_initialized());
+    assert!(mutex.with_lock(|_| unreachable!()).is_err());
+    assert!(matches!(
+        mutex.try_with_lock(|_| unreachable!()),
+        Some(Err(_))
+    ));
+    assert_eq!(mutex.into_inner(), None);
+}
+
+#[test]
+fn write_initializes_and_replaces() {
+    let mutex = UninitMutex::<LocalRawMutex, u32>::new();
+
+    assert_eq!(mutex.write(1), None);
+    assert!(mutex.is_initialized());
+    assert_eq!(mutex.with_lock(|n| std::mem::replace(n, 2)).ok(), Some(1));
+    assert_eq!(mutex.write(3), Some(2));
+    assert_eq!(mutex.try_with_lock(|n| *n).map(Result::ok), Some(Some(3)));
+    assert_eq!(mutex.into_inner(), Some(3));
+}
+
+#[test]
+fn try_with_lock_fails_while_locked() {
+    let mutex = UninitMutex::<LocalRawMutex, u32>::new();
+    mutex.write(1);
+
+    let nested = mutex.with_lock(|_| mutex.try_with_lock(|n| *n));
+    assert!(matches!(nested, Ok(None)));
+}
+
+#[test]
+fn initialized_value_is_dropped() {
+    let value = Rc::new(());
+
+    let mutex = UninitMutex::<LocalRawMutex, Rc<()>>::new();
+    mutex.write(value.clone());
+    assert_eq!(Rc::strong_count(&value), 2);
+    drop(mutex);
+    assert_eq!(Rc::strong_count(&value), 1);
+
+    // An uninitialized mutex must not drop anything.
+    drop(UninitMutex::<LocalRawMutex, Rc<()>>::new());
+}